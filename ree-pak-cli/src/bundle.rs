@@ -0,0 +1,256 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use indicatif::ProgressStyle;
+use ree_pak_core::{
+    content_hash::{content_digest, to_hex, ContentManifest, HashingReader},
+    export::{ExportSink, ZipExporter},
+    filename::FileNameTable,
+    metadata::{PakMetadata, METADATA_PATH},
+    read::io::{archive::PakArchiveReader, entry::PakEntryReader},
+    write::{FileOptions, PakWriter},
+};
+use serde_json::json;
+
+use crate::{
+    error::{check_output_file, failure, Failure},
+    i18n::{tr, Msg},
+    info::codec_name,
+    pack::collect_inputs,
+    report,
+    unpack::{load_filename_table, load_list_file, resolve_path},
+    BundleCommand,
+};
+
+/// Name of the content manifest in the bundle.
+const MANIFEST_NAME: &str = "manifest.json";
+/// Name of the mod metadata in the bundle.
+const METADATA_NAME: &str = "mod.json";
+const README_NAME: &str = "README.md";
+/// Name of the thumbnail in the bundle, with the extension of the source file.
+const THUMBNAIL_STEM: &str = "thumbnail";
+
+pub fn bundle(cmd: &BundleCommand) -> anyhow::Result<()> {
+    let input = Path::new(&cmd.input);
+    if !input.exists() {
+        return Err(failure(
+            Failure::InputMissing,
+            format!("Input `{}` not found.", &cmd.input),
+        ));
+    }
+    for path in [&cmd.readme, &cmd.thumbnail].into_iter().flatten() {
+        if !Path::new(path).is_file() {
+            return Err(failure(Failure::InputMissing, format!("File `{}` not found.", path)));
+        }
+    }
+    let output_path = Path::new(&cmd.output);
+    check_output_file(output_path)?;
+    if !cmd.r#override && output_path.exists() {
+        anyhow::bail!("Output file `{}` already exists.", &cmd.output);
+    }
+    // the bundle is written next to the output and renamed into place when complete,
+    // so a failure leaves no partial bundle behind
+    let tmp_output = RemoveOnDrop(PathBuf::from(format!("{}.tmp", &cmd.output)));
+    let output = File::create(&tmp_output.0).context(format!("Failed to create output file `{}`.", &cmd.output))?;
+
+    // a directory is packed next to the output first, the file is removed when done
+    let packed = input
+        .is_dir()
+        .then(|| RemoveOnDrop(PathBuf::from(format!("{}.pak.tmp", &cmd.output))));
+    let (pak_path, file_name_table) = match &packed {
+        Some(RemoveOnDrop(pak_path)) => (pak_path.as_path(), pack_dir(cmd, input, pak_path)?),
+        None => {
+            let file_name_table = match &cmd.project {
+                Some(project) => load_filename_table(project)?,
+                None => FileNameTable::default(),
+            };
+            (input, file_name_table)
+        }
+    };
+    let pak_name = match &cmd.pak_name {
+        Some(name) => name.clone(),
+        None => default_pak_name(input)?,
+    };
+
+    let (manifest, pak_metadata) = describe_pak(pak_path, &pak_name, file_name_table)?;
+    let metadata = mod_metadata(cmd, pak_path, &pak_name, pak_metadata, &manifest)?;
+
+    let mut exporter = ZipExporter::new(BufWriter::new(output));
+    add_file(&mut exporter, &pak_name, pak_path)?;
+    exporter.add_file(MANIFEST_NAME, manifest.len() as u64, &mut manifest.as_slice())?;
+    exporter.add_file(METADATA_NAME, metadata.len() as u64, &mut metadata.as_slice())?;
+    if let Some(readme) = &cmd.readme {
+        add_file(&mut exporter, README_NAME, Path::new(readme))?;
+    }
+    if let Some(thumbnail) = &cmd.thumbnail {
+        add_file(
+            &mut exporter,
+            &thumbnail_name(Path::new(thumbnail)),
+            Path::new(thumbnail),
+        )?;
+    }
+    let files = exporter.len();
+    exporter.finish()?.flush()?;
+    std::fs::rename(&tmp_output.0, output_path).context(format!("Failed to write output file `{}`.", &cmd.output))?;
+
+    report::emit(json!({
+        "event": "report",
+        "output": &cmd.output,
+        "pak": &pak_name,
+        "files": files,
+    }));
+    report::message(tr!(Msg::BundleDone, files, &cmd.output));
+
+    Ok(())
+}
+
+/// Pack the files of a directory into a pak, returning their names.
+fn pack_dir(cmd: &BundleCommand, input_dir: &Path, pak_path: &Path) -> anyhow::Result<FileNameTable> {
+    let list = match &cmd.list_file {
+        Some(list_file) => {
+            Some(load_list_file(list_file).context(format!("Failed to load list file `{}`.", list_file))?)
+        }
+        None => None,
+    };
+    let inputs = collect_inputs(input_dir, list.as_ref())?;
+    if inputs.is_empty() {
+        anyhow::bail!("Input directory `{}` has no files.", input_dir.display());
+    }
+
+    let output = File::create(pak_path).context(format!("Failed to create `{}`.", pak_path.display()))?;
+    let options = FileOptions::default().with_compression(cmd.compression.into());
+    let bar = report::progress_bar(inputs.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::BundleProgress))?);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    let mut writer = PakWriter::new(BufWriter::new(output), inputs.len() as u32)?;
+    let mut file_name_table = FileNameTable::default();
+    for input in &inputs {
+        let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.path))?;
+        writer.start_file_hash(input.hash, options)?;
+        io::copy(&mut BufReader::new(file), &mut writer).context(format!("Failed to pack `{}`.", input.path))?;
+        file_name_table.push_str(&input.path);
+        bar.inc(1);
+    }
+    writer.finish()?.flush()?;
+    bar.finish();
+
+    Ok(file_name_table)
+}
+
+/// Content manifest of the pak as JSON, and the metadata entry of the pak if it has one.
+///
+/// Each file is listed with its hash, path, sizes and SHA-256, with the merkle
+/// root over all files, so users can check what the pak contains.
+fn describe_pak(
+    pak_path: &Path,
+    pak_name: &str,
+    mut file_name_table: FileNameTable,
+) -> anyhow::Result<(Vec<u8>, Option<PakMetadata>)> {
+    file_name_table.push_str(METADATA_PATH);
+    let file = File::open(pak_path).context(format!("Failed to open `{}`.", pak_path.display()))?;
+    let mut reader = BufReader::new(file);
+    let archive = ree_pak_core::read::read_archive(&mut reader)?;
+    let pak_metadata = PakArchiveReader::new(&mut reader, &archive)
+        .read_metadata()
+        .context("Failed to read pak metadata")?;
+
+    let mut content = ContentManifest::new();
+    let mut files = vec![];
+    for entry in archive.entries() {
+        let path = resolve_path(entry, &file_name_table);
+        let mut entry_reader = HashingReader::new(PakEntryReader::new_streaming(&mut reader, entry)?);
+        io::copy(&mut entry_reader, &mut io::sink()).context(format!("Failed to read `{}`.", path))?;
+        let digest = entry_reader.finish();
+        files.push(json!({
+            "hash": report::hash_string(entry.hash()),
+            "path": &path,
+            "compressed_size": entry.compressed_size(),
+            "uncompressed_size": entry.uncompressed_size(),
+            "compression": codec_name(entry.compression_method()),
+            "sha256": to_hex(&digest),
+        }));
+        content.add(path, digest);
+    }
+    let manifest = json!({
+        "pak": pak_name,
+        "content_root": to_hex(&content.root()),
+        "files": files,
+    });
+
+    Ok((serde_json::to_vec_pretty(&manifest)?, pak_metadata))
+}
+
+/// Mod metadata as JSON, options of the command override the metadata entry of the pak.
+fn mod_metadata(
+    cmd: &BundleCommand,
+    pak_path: &Path,
+    pak_name: &str,
+    pak_metadata: Option<PakMetadata>,
+    manifest: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let pak_metadata = pak_metadata.unwrap_or_default();
+    let mut pak_file = HashingReader::new(File::open(pak_path)?);
+    let pak_size = io::copy(&mut pak_file, &mut io::sink())?;
+    let metadata = json!({
+        "name": cmd.mod_name.as_ref().or(pak_metadata.name.as_ref()),
+        "author": cmd.author.as_ref().or(pak_metadata.author.as_ref()),
+        "version": cmd.mod_version.as_ref().or(pak_metadata.version.as_ref()),
+        "build_timestamp": pak_metadata.build_timestamp,
+        "pak": {
+            "name": pak_name,
+            "size": pak_size,
+            "sha256": to_hex(&pak_file.finish()),
+        },
+        "manifest": MANIFEST_NAME,
+        "manifest_sha256": to_hex(&content_digest(manifest)),
+        "readme": cmd.readme.as_ref().map(|_| README_NAME),
+        "thumbnail": cmd.thumbnail.as_ref().map(|path| thumbnail_name(Path::new(path))),
+        "tool_version": format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+    });
+
+    Ok(serde_json::to_vec_pretty(&metadata)?)
+}
+
+fn add_file<S: ExportSink>(sink: &mut S, name: &str, path: &Path) -> anyhow::Result<()> {
+    let file = File::open(path).context(format!("Failed to open `{}`.", path.display()))?;
+    let size = file.metadata()?.len();
+    sink.add_file(name, size, &mut BufReader::new(file))
+        .context(format!("Failed to add `{}` to the bundle.", path.display()))?;
+
+    Ok(())
+}
+
+/// File name of the input, a directory is named like a pak.
+fn default_pak_name(input: &Path) -> anyhow::Result<String> {
+    let name = input
+        .canonicalize()?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .context("Invalid input path.")?;
+    if input.is_dir() {
+        Ok(format!("{}.pak", name))
+    } else {
+        Ok(name)
+    }
+}
+
+fn thumbnail_name(path: &Path) -> String {
+    match path.extension() {
+        Some(ext) => format!("{}.{}", THUMBNAIL_STEM, ext.to_string_lossy().to_lowercase()),
+        None => THUMBNAIL_STEM.to_string(),
+    }
+}
+
+/// Removes a temporary file when dropped.
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
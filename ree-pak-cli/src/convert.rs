@@ -0,0 +1,53 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Write},
+};
+
+use anyhow::Context;
+use ree_pak_core::pak::PakHeader;
+
+use crate::ConvertCommand;
+
+pub fn convert(cmd: &ConvertCommand) -> anyhow::Result<()> {
+    let (major_version, minor_version) = parse_version(&cmd.to_version)?;
+    PakHeader::new(major_version, minor_version, 0, 0, 0)
+        .context(format!("Unsupported target version `{}`.", &cmd.to_version))?;
+
+    // load PAK file
+    let file = File::open(&cmd.input).context(format!("Input file `{}` not found.", &cmd.input))?;
+    let mut reader = BufReader::new(file);
+    let archive = ree_pak_core::read::read_archive(&mut reader)?;
+
+    let output = if cmd.r#override {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&cmd.output)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&cmd.output)
+    }
+    .context(format!("Failed to create output file `{}`.", &cmd.output))?;
+    let mut writer = BufWriter::new(output);
+    ree_pak_core::write::convert_archive(&mut reader, &archive, &mut writer, major_version, minor_version)?;
+    writer.flush()?;
+
+    println!(
+        "Converted {} entries from version {}.{} to {}.{}.",
+        archive.entries().len(),
+        archive.header().major_version(),
+        archive.header().minor_version(),
+        major_version,
+        minor_version
+    );
+
+    Ok(())
+}
+
+fn parse_version(version: &str) -> anyhow::Result<(u8, u8)> {
+    let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+    let major = major.parse().context(format!("Invalid version `{}`.", version))?;
+    let minor = minor.parse().context(format!("Invalid version `{}`.", version))?;
+
+    Ok((major, minor))
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::DedupMode;
+
+/// Replaces extracted files with links to an earlier file of the same content.
+///
+/// Files are matched by size and digest, then compared byte by byte before
+/// linking, so a digest collision never links different files.
+pub(crate) struct Dedup {
+    mode: DedupMode,
+    root: PathBuf,
+    files: Mutex<HashMap<(u64, u64), PathBuf>>,
+    linked: AtomicU64,
+    saved_bytes: AtomicU64,
+}
+
+impl Dedup {
+    /// Deduplicate files written under `root`.
+    pub(crate) fn new(mode: DedupMode, root: PathBuf) -> Self {
+        Self {
+            mode,
+            root,
+            files: Mutex::default(),
+            linked: AtomicU64::new(0),
+            saved_bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Link a written file to the first file with the same content, returns whether it was linked.
+    ///
+    /// The link is created next to the file and renamed over it, so the written
+    /// copy is kept if links are not supported, e.g. symlinks on Windows without
+    /// the privilege to create them.
+    pub(crate) fn link_duplicate(&self, path: &Path, size: u64, digest: u64) -> io::Result<bool> {
+        let original = {
+            let mut files = self.files.lock().unwrap();
+            match files.get(&(size, digest)) {
+                Some(original) => original.clone(),
+                None => {
+                    files.insert((size, digest), path.to_path_buf());
+                    return Ok(false);
+                }
+            }
+        };
+        if !same_content(&original, path)? {
+            return Ok(false);
+        }
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".dedup.tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let linked = match self.mode {
+            DedupMode::Hardlink => std::fs::hard_link(&original, &tmp_path),
+            DedupMode::Symlink => symlink(&self.link_target(&original, path), &tmp_path),
+        }
+        .and_then(|()| std::fs::rename(&tmp_path, path));
+        if linked.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Ok(false);
+        }
+        self.linked.fetch_add(1, Ordering::Relaxed);
+        self.saved_bytes.fetch_add(size, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Number of files replaced by links.
+    pub(crate) fn linked(&self) -> u64 {
+        self.linked.load(Ordering::Relaxed)
+    }
+
+    /// Bytes not written thanks to links.
+    pub(crate) fn saved_bytes(&self) -> u64 {
+        self.saved_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Path of `original` relative to the directory of `link`, so the output can be moved.
+    fn link_target(&self, original: &Path, link: &Path) -> PathBuf {
+        let (Ok(original), Ok(link)) = (original.strip_prefix(&self.root), link.strip_prefix(&self.root)) else {
+            return original.to_path_buf();
+        };
+        let mut target = PathBuf::new();
+        for _ in 1..link.components().count() {
+            target.push("..");
+        }
+
+        target.join(original)
+    }
+}
+
+/// Reader computing the size and digest of the bytes read through it.
+pub(crate) struct DigestReader<R> {
+    inner: R,
+    hasher: DefaultHasher,
+    len: u64,
+}
+
+impl<R: Read> DigestReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: DefaultHasher::new(),
+            len: 0,
+        }
+    }
+
+    /// Get the size and digest of the bytes read.
+    pub(crate) fn finish(self) -> (u64, u64) {
+        (self.len, self.hasher.finish())
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    let mut buf_a = vec![0; 64 * 1024];
+    let mut buf_b = vec![0; 64 * 1024];
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        if n != read_full(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until `buf` is full or the end is reached.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+
+    Ok(filled)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
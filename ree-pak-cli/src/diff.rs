@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use ree_pak_core::{
+    compare::{ContentComparer, Strictness},
+    filename::FileNameTable,
+    pak::{PakArchive, PakEntry},
+    read::io::entry::PakEntryReader,
+};
+
+use crate::{unpack::load_filename_table, DiffCommand, DiffStrictness};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl Change {
+    fn status(&self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Removed => 'D',
+            Change::Modified => 'M',
+        }
+    }
+}
+
+pub fn diff(cmd: &DiffCommand) -> anyhow::Result<()> {
+    // load project file name table
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+
+    let (mut reader_a, archive_a) = open_pak(&cmd.a)?;
+    let (mut reader_b, archive_b) = open_pak(&cmd.b)?;
+
+    let comparer = ContentComparer::new(cmd.strictness.into());
+    let mut changes: Vec<(Change, String, u64)> = vec![];
+    for entry_b in archive_b.entries() {
+        let change = match archive_a.find_entry(entry_b.hash()) {
+            None => Some(Change::Added),
+            Some(entry_a) => {
+                let equal = match (entry_a.checksum(), entry_b.checksum()) {
+                    // trust checksums when both are present
+                    (0, _) | (_, 0) => comparer.eq_entries((&mut reader_a, entry_a), (&mut reader_b, entry_b))?,
+                    (checksum_a, checksum_b) => {
+                        checksum_a == checksum_b && entry_a.uncompressed_size() == entry_b.uncompressed_size()
+                    }
+                };
+                (!equal).then_some(Change::Modified)
+            }
+        };
+        if let Some(change) = change {
+            changes.push((change, resolve_path(entry_b.hash(), &file_name_table), entry_b.hash()));
+        }
+    }
+    for entry_a in archive_a.entries() {
+        if archive_b.find_entry(entry_a.hash()).is_none() {
+            changes.push((
+                Change::Removed,
+                resolve_path(entry_a.hash(), &file_name_table),
+                entry_a.hash(),
+            ));
+        }
+    }
+    changes.sort_by(|a, b| (&a.1, a.0).cmp(&(&b.1, b.0)));
+
+    for (change, path, _) in &changes {
+        println!("{}\t{}", change.status(), path);
+    }
+    let count = |kind| changes.iter().filter(|(change, _, _)| *change == kind).count();
+    eprintln!(
+        "{} added, {} removed, {} modified.",
+        count(Change::Added),
+        count(Change::Removed),
+        count(Change::Modified)
+    );
+
+    if let Some(extract_dir) = &cmd.extract {
+        let mut extracted = 0;
+        for (change, path, hash) in &changes {
+            if *change == Change::Removed {
+                continue;
+            }
+            let entry = archive_b.find_entry(*hash).context("Changed entry not found.")?;
+            extract_entry(&mut reader_b, entry, Path::new(extract_dir), path)
+                .context(format!("Failed to extract `{}`.", path))?;
+            extracted += 1;
+        }
+        eprintln!("Extracted {} files to `{}`.", extracted, extract_dir);
+    }
+
+    Ok(())
+}
+
+fn open_pak(path: &str) -> anyhow::Result<(BufReader<File>, PakArchive)> {
+    let file = File::open(path).context(format!("Input file `{}` not found.", path))?;
+    let mut reader = BufReader::new(file);
+    let archive = ree_pak_core::read::read_archive(&mut reader)?;
+
+    Ok((reader, archive))
+}
+
+fn resolve_path(hash: u64, file_name_table: &FileNameTable) -> String {
+    file_name_table
+        .get_file_name(hash)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("_Unknown/{:08X}", hash))
+}
+
+fn extract_entry(reader: &mut BufReader<File>, entry: &PakEntry, output_dir: &Path, path: &str) -> anyhow::Result<()> {
+    let filepath = output_dir.join(path);
+    std::fs::create_dir_all(filepath.parent().unwrap())?;
+
+    let mut entry_reader = PakEntryReader::new_streaming(reader, entry)?;
+    let mut writer = BufWriter::new(File::create(&filepath)?);
+    std::io::copy(&mut entry_reader, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    // guess unknown file extension
+    if filepath.extension().is_none() {
+        if let Some(ext) = entry_reader.determine_extension() {
+            std::fs::rename(&filepath, filepath.with_extension(ext))?;
+        }
+    }
+
+    Ok(())
+}
+
+impl From<DiffStrictness> for Strictness {
+    fn from(value: DiffStrictness) -> Self {
+        match value {
+            DiffStrictness::Size => Strictness::Size,
+            DiffStrictness::Preview => Strictness::Preview,
+            DiffStrictness::Full => Strictness::Full,
+        }
+    }
+}
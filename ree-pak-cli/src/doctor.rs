@@ -0,0 +1,270 @@
+use std::{
+    fs::OpenOptions,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+
+use ree_pak_core::{
+    filename::FileNameTable,
+    pak::CompressionMethod,
+    read::{self, io::archive::PakArchiveReader},
+    write::{FileOptions, PakWriter},
+};
+
+use crate::{
+    unpack::{filelist_dir, load_list_file},
+    DoctorCommand,
+};
+
+/// Tiny pak with one stored and one deflate entry, used for the read check.
+const FIXTURE_PAK: &[u8] = include_bytes!("../fixtures/doctor.pak");
+const FIXTURE_FILES: [(&str, &str); 2] = [
+    ("natives/stm/doctor/store.txt", "ree-pak-cli doctor stored entry"),
+    (
+        "natives/stm/doctor/deflate.txt",
+        "ree-pak-cli doctor deflate entry, ree-pak-cli doctor deflate entry",
+    ),
+];
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn item(&mut self, status: Status, title: &str, detail: impl AsRef<str>) {
+        let tag = match status {
+            Status::Ok => "OK",
+            Status::Warn => "WARN",
+            Status::Fail => {
+                self.failed += 1;
+                "FAIL"
+            }
+        };
+        println!("[{:<4}] {}: {}", tag, title, detail.as_ref());
+    }
+}
+
+pub fn doctor(cmd: &DoctorCommand) -> anyhow::Result<()> {
+    let mut report = Report { failed: 0 };
+
+    println!("ree-pak-cli {} diagnostic report", env!("CARGO_PKG_VERSION"));
+    println!("Platform: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    println!();
+
+    check_filelists(&mut report);
+    check_memory(&mut report);
+    check_cpu(&mut report);
+    check_writable(&mut report, "Temp directory", &std::env::temp_dir());
+    if let Some(output) = &cmd.output {
+        check_writable(&mut report, "Output directory", Path::new(output));
+    }
+    check_fixture(&mut report);
+    check_round_trip(&mut report);
+
+    println!();
+    if report.failed > 0 {
+        anyhow::bail!("{} check(s) failed.", report.failed);
+    }
+    println!("All checks passed.");
+
+    Ok(())
+}
+
+fn check_filelists(report: &mut Report) {
+    let dir = match filelist_dir() {
+        Ok(dir) => dir,
+        Err(e) => return report.item(Status::Fail, "File lists", format!("cannot locate executable: {}", e)),
+    };
+    let read_dir = match std::fs::read_dir(&dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            return report.item(
+                Status::Fail,
+                "File lists",
+                format!("cannot read `{}`: {}", dir.display(), e),
+            )
+        }
+    };
+
+    let mut list_paths: Vec<_> = read_dir
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| list_name(path).is_some())
+        .collect();
+    list_paths.sort();
+    if list_paths.is_empty() {
+        return report.item(
+            Status::Fail,
+            "File lists",
+            format!("no `.list` or `.list.zst` files found in `{}`", dir.display()),
+        );
+    }
+
+    for path in list_paths {
+        let name = list_name(&path).unwrap_or_default();
+        match load_list_file(&path) {
+            Ok(table) if table.is_empty() => report.item(Status::Warn, "File list", format!("{}: empty", name)),
+            Ok(table) => report.item(Status::Ok, "File list", format!("{}: {} names", name, table.len())),
+            Err(e) => report.item(Status::Fail, "File list", format!("{}: {:#}", name, e)),
+        }
+    }
+}
+
+/// File name of a `.list` or `.list.zst` file without the suffix, `None` for other files.
+fn list_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_string_lossy();
+    [".list", ".list.zst"]
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .map(|name| name.to_string())
+}
+
+fn check_memory(report: &mut Report) {
+    match available_memory() {
+        Some(bytes) => report.item(Status::Ok, "Available memory", format!("{} MiB", bytes / (1024 * 1024))),
+        None => report.item(Status::Warn, "Available memory", "unknown on this platform"),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+fn check_cpu(report: &mut Report) {
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    report.item(Status::Ok, "CPU threads", threads.to_string());
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        let features = [
+            ("sse4.2", std::arch::is_x86_feature_detected!("sse4.2")),
+            ("avx2", std::arch::is_x86_feature_detected!("avx2")),
+        ];
+        let detail = features
+            .iter()
+            .map(|(name, detected)| format!("{}={}", name, if *detected { "yes" } else { "no" }))
+            .collect::<Vec<_>>()
+            .join(", ");
+        report.item(Status::Ok, "CPU features", detail);
+    }
+}
+
+fn check_writable(report: &mut Report, title: &str, dir: &Path) {
+    let probe_path = dir.join(format!(".ree-pak-doctor-{}", std::process::id()));
+    let result = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&probe_path)
+        .and_then(|file| {
+            drop(file);
+            std::fs::remove_file(&probe_path)
+        });
+
+    match result {
+        Ok(()) => report.item(Status::Ok, title, format!("`{}` is writable", dir.display())),
+        Err(e) => report.item(
+            Status::Fail,
+            title,
+            format!("`{}` is not writable: {}", dir.display(), e),
+        ),
+    }
+}
+
+fn check_fixture(report: &mut Report) {
+    match read_fixture() {
+        Ok(0) => report.item(Status::Ok, "Read check", "embedded fixture decoded correctly"),
+        Ok(skipped) => report.item(
+            Status::Warn,
+            "Read check",
+            format!(
+                "embedded fixture decoded, {} entries skipped as their codec is not in this build",
+                skipped
+            ),
+        ),
+        Err(e) => report.item(Status::Fail, "Read check", e.to_string()),
+    }
+}
+
+fn check_round_trip(report: &mut Report) {
+    match write_fixture().and_then(|pak| read_pak(&pak)) {
+        Ok(_) => report.item(Status::Ok, "Write check", "written pak read back correctly"),
+        Err(e) => report.item(Status::Fail, "Write check", e.to_string()),
+    }
+}
+
+/// Write the fixture files into a new pak in memory, deflating the second one if supported.
+fn write_fixture() -> anyhow::Result<Vec<u8>> {
+    let mut writer = PakWriter::new(Cursor::new(vec![]), FIXTURE_FILES.len() as u32)?;
+    for (i, (name, content)) in FIXTURE_FILES.iter().enumerate() {
+        let compression = if i == 1 && CompressionMethod::Deflate.is_supported() {
+            CompressionMethod::Deflate
+        } else {
+            CompressionMethod::None
+        };
+        writer.start_file(name, FileOptions::default().with_compression(compression))?;
+        writer.write_all(content.as_bytes())?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+fn read_fixture() -> anyhow::Result<usize> {
+    read_pak(FIXTURE_PAK)
+}
+
+/// Read a pak of the fixture files and check their contents, returning the
+/// number of entries skipped as their codec is disabled in this build.
+fn read_pak(pak: &[u8]) -> anyhow::Result<usize> {
+    let mut table = FileNameTable::default();
+    for (name, _) in FIXTURE_FILES {
+        table.push_str(name);
+    }
+
+    let mut reader = Cursor::new(pak);
+    let archive = read::read_archive(&mut reader)?;
+    if archive.entries().len() != FIXTURE_FILES.len() {
+        anyhow::bail!(
+            "expected {} entries, found {}",
+            FIXTURE_FILES.len(),
+            archive.entries().len()
+        );
+    }
+
+    let mut archive_reader = PakArchiveReader::new(reader, &archive);
+    let mut skipped = 0;
+    for entry in archive.entries() {
+        if !entry.compression_method().is_supported() {
+            skipped += 1;
+            continue;
+        }
+        let Some(file_name) = table.get_file_name(entry.hash()) else {
+            anyhow::bail!("entry {:016X} has no matching name", entry.hash());
+        };
+        let (_, expected) = FIXTURE_FILES.iter().find(|(name, _)| *name == file_name).unwrap();
+
+        let mut data = String::new();
+        archive_reader
+            .owned_entry_reader(entry.clone())?
+            .read_to_string(&mut data)?;
+        if data != *expected {
+            anyhow::bail!("content mismatch in `{}`", file_name);
+        }
+    }
+
+    Ok(skipped)
+}
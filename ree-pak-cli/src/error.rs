@@ -0,0 +1,207 @@
+//! Failure classes of commands, each with its own exit code.
+//!
+//! Errors stay [`anyhow::Error`]s, a class is either attached where it is known
+//! with [`failure`] or derived from the io and pak errors in the chain, so
+//! wrappers can branch on the exit code and users get a hint on what to do.
+
+use std::{fmt, io, path::Path, process::ExitCode};
+
+use ree_pak_core::error::PakError;
+
+use crate::i18n::{tr, Msg};
+
+/// Class of a failed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// Any other error.
+    Other,
+    InputMissing,
+    OutputIsDirectory,
+    PermissionDenied,
+    /// Not a pak, or a pak version or algorithm this build cannot read.
+    UnsupportedPak,
+    /// The project list is missing or cannot be loaded.
+    BadList,
+}
+
+impl Failure {
+    /// Exit code of the process, 2 is left to argument errors reported by clap.
+    pub(crate) fn exit_code(self) -> u8 {
+        match self {
+            Failure::Other => 1,
+            Failure::InputMissing => 3,
+            Failure::OutputIsDirectory => 4,
+            Failure::PermissionDenied => 5,
+            Failure::UnsupportedPak => 6,
+            Failure::BadList => 7,
+        }
+    }
+
+    /// Name of the class in JSON output.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Failure::Other => "other",
+            Failure::InputMissing => "input_missing",
+            Failure::OutputIsDirectory => "output_is_directory",
+            Failure::PermissionDenied => "permission_denied",
+            Failure::UnsupportedPak => "unsupported_pak",
+            Failure::BadList => "bad_list",
+        }
+    }
+
+    fn hint(self) -> Option<Msg> {
+        match self {
+            Failure::Other => None,
+            Failure::InputMissing => Some(Msg::HintInputMissing),
+            Failure::OutputIsDirectory => Some(Msg::HintOutputIsDirectory),
+            Failure::PermissionDenied => Some(Msg::HintPermissionDenied),
+            Failure::UnsupportedPak => Some(Msg::HintUnsupportedPak),
+            Failure::BadList => Some(Msg::HintBadList),
+        }
+    }
+}
+
+/// An error with a known [`Failure`] class.
+#[derive(Debug)]
+struct ClassifiedError {
+    failure: Failure,
+    message: String,
+    source: Option<anyhow::Error>,
+}
+
+impl fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ClassifiedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|source| source.as_ref() as _)
+    }
+}
+
+/// An error of a known class, e.g. `return Err(failure(Failure::InputMissing, message))`.
+pub(crate) fn failure(failure: Failure, message: impl fmt::Display) -> anyhow::Error {
+    anyhow::Error::new(ClassifiedError {
+        failure,
+        message: message.to_string(),
+        source: None,
+    })
+}
+
+/// Attach a class to an error as context, keeping its causes.
+pub(crate) fn classify_as(error: anyhow::Error, failure: Failure, message: impl fmt::Display) -> anyhow::Error {
+    anyhow::Error::new(ClassifiedError {
+        failure,
+        message: message.to_string(),
+        source: Some(error),
+    })
+}
+
+/// Fail before creating an output file at a directory, which Windows reports as a bare "os error 5".
+pub(crate) fn check_output_file(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        return Err(failure(
+            Failure::OutputIsDirectory,
+            format!("Output `{}` is a directory, expected a file path.", path.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Class of an error, the outermost attached class or the first io or pak error with one.
+pub(crate) fn classify(error: &anyhow::Error) -> Failure {
+    // a class stays attached when the error is wrapped with more context
+    if let Some(classified) = error.chain().find_map(|cause| cause.downcast_ref::<ClassifiedError>()) {
+        return classified.failure;
+    }
+    for cause in error.chain() {
+        let failure = if let Some(e) = cause.downcast_ref::<PakError>() {
+            classify_pak_error(e)
+        } else if let Some(e) = cause.downcast_ref::<io::Error>() {
+            classify_io_error(e)
+        } else {
+            None
+        };
+        if let Some(failure) = failure {
+            return failure;
+        }
+    }
+
+    Failure::Other
+}
+
+fn classify_pak_error(e: &PakError) -> Option<Failure> {
+    match e {
+        PakError::IO(e) => classify_io_error(e),
+        PakError::InvalidMagic { .. }
+        | PakError::UnsupportedVersion { .. }
+        | PakError::UnsupportedAlgorithm(_)
+        | PakError::HeaderRejected(_)
+        | PakError::EncryptedEntry { .. }
+        | PakError::SuspiciousEntry { .. } => Some(Failure::UnsupportedPak),
+        PakError::InvalidFileName { .. }
+        | PakError::UnsafeFileName(_)
+        | PakError::InvalidListInclude { .. }
+        | PakError::UnknownEncoding(_)
+        | PakError::ListHashMismatch { .. } => Some(Failure::BadList),
+        _ => None,
+    }
+}
+
+fn classify_io_error(e: &io::Error) -> Option<Failure> {
+    match e.kind() {
+        io::ErrorKind::NotFound => Some(Failure::InputMissing),
+        io::ErrorKind::PermissionDenied => Some(Failure::PermissionDenied),
+        io::ErrorKind::IsADirectory => Some(Failure::OutputIsDirectory),
+        _ => None,
+    }
+}
+
+/// Print the error with a hint for its class, returning the exit code.
+pub(crate) fn report_error(error: &anyhow::Error) -> ExitCode {
+    let failure = classify(error);
+    eprintln!("Error: {:?}", error);
+    if let Some(hint) = failure.hint() {
+        eprintln!("{}", tr!(hint));
+    }
+    crate::report::emit(serde_json::json!({
+        "event": "error",
+        "message": format!("{:#}", error),
+        "kind": failure.name(),
+        "exit_code": failure.exit_code(),
+    }));
+
+    ExitCode::from(failure.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    fn exit_code(error: anyhow::Error) -> u8 {
+        classify(&error).exit_code()
+    }
+
+    #[test]
+    fn test_classify() {
+        let not_found = || anyhow::Error::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(exit_code(not_found()), 3);
+        assert_eq!(exit_code(not_found().context("Failed to open input")), 3);
+        let denied = anyhow::Error::from(PakError::IO(io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert_eq!(exit_code(denied), 5);
+        assert_eq!(exit_code(anyhow::anyhow!("unknown")), 1);
+
+        // an attached class wins over the causes, also under later context
+        let wrapped = Err::<(), _>(failure(Failure::UnsupportedPak, "not a pak"))
+            .context("Failed to read input")
+            .unwrap_err();
+        assert_eq!(exit_code(wrapped), 6);
+        let classified = classify_as(not_found(), Failure::BadList, "Failed to load list").context("Failed to unpack");
+        assert_eq!(exit_code(classified), 7);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::Context;
+use indicatif::{ProgressBar, ProgressStyle};
+use ree_pak_core::{
+    export::{DirExporter, ExportSink, TarExporter, ZipCompression, ZipExporter},
+    filename::FileNameTable,
+    read::{multi::MultiPakEntry, source::SourceReader},
+};
+
+use crate::{
+    error::check_output_file,
+    i18n::{tr, Msg},
+    report,
+    unpack::{load_filename_table, open_multi_pak, resolve_path},
+    ExportCommand, ExportFormat,
+};
+
+/// Output path writing to stdout.
+const STDOUT: &str = "-";
+
+pub fn export(cmd: &ExportCommand) -> anyhow::Result<()> {
+    let to_stdout = cmd.output == STDOUT;
+    if to_stdout && !matches!(cmd.format, ExportFormat::Tar | ExportFormat::TarZst) {
+        anyhow::bail!("Only tar formats can be written to stdout.");
+    }
+    if cmd.metadata_only && !matches!(cmd.format, ExportFormat::Sqlite) {
+        anyhow::bail!("`--metadata-only` is only supported by the sqlite format.");
+    }
+    if to_stdout && report::is_json() {
+        anyhow::bail!("JSON output cannot be combined with writing the archive to stdout.");
+    }
+
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+    let multi_pak = open_multi_pak(&cmd.input)?;
+    let mut readers = (0..multi_pak.paks().len())
+        .map(|pak_index| multi_pak.open_reader(pak_index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let entries = multi_pak.entries();
+    let bar = report::progress_bar(entries.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::ExportProgress))?);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    match cmd.format {
+        ExportFormat::Zip => {
+            let compression = if cmd.store {
+                ZipCompression::Store
+            } else {
+                ZipCompression::Deflate
+            };
+            let mut exporter = ZipExporter::new(BufWriter::new(create_output(cmd)?)).with_compression(compression);
+            export_entries(&mut exporter, &mut readers, entries, &file_name_table, &bar)?;
+            exporter.finish()?;
+        }
+        ExportFormat::Tar => {
+            let mut exporter = TarExporter::new(BufWriter::new(open_stream(cmd)?));
+            export_entries(&mut exporter, &mut readers, entries, &file_name_table, &bar)?;
+            exporter.finish()?;
+        }
+        #[cfg(feature = "zstd")]
+        ExportFormat::TarZst => {
+            let encoder = zstd::Encoder::new(open_stream(cmd)?, 0)?;
+            let mut exporter = TarExporter::new(BufWriter::new(encoder));
+            export_entries(&mut exporter, &mut readers, entries, &file_name_table, &bar)?;
+            let encoder = exporter.finish()?.into_inner().map_err(|e| e.into_error())?;
+            encoder.finish()?.flush()?;
+        }
+        #[cfg(not(feature = "zstd"))]
+        ExportFormat::TarZst => anyhow::bail!("The tar-zst format needs a build with the `zstd` feature."),
+        ExportFormat::Dir => {
+            let output = Path::new(&cmd.output);
+            if output.exists() && !cmd.r#override {
+                anyhow::bail!("Output directory `{}` already exists.", output.display());
+            }
+            let mut exporter = DirExporter::new(output);
+            export_entries(&mut exporter, &mut readers, entries, &file_name_table, &bar)?;
+        }
+        #[cfg(feature = "sqlite")]
+        ExportFormat::Sqlite => {
+            let output = Path::new(&cmd.output);
+            if output.exists() {
+                if !cmd.r#override {
+                    anyhow::bail!("Output file `{}` already exists.", output.display());
+                }
+                std::fs::remove_file(output)?;
+            }
+            let mut exporter = crate::sqlite::SqliteExporter::create(output, !cmd.metadata_only)
+                .context(format!("Failed to create database `{}`.", output.display()))?;
+            export_entries(&mut exporter, &mut readers, entries, &file_name_table, &bar)?;
+            exporter.finish()?;
+        }
+        #[cfg(not(feature = "sqlite"))]
+        ExportFormat::Sqlite => anyhow::bail!("The sqlite format needs a build with the `sqlite` feature."),
+    }
+    bar.finish();
+
+    if to_stdout {
+        // stdout carries the archive
+        eprintln!("{}", tr!(Msg::ExportDoneStdout, entries.len()));
+    } else {
+        report::message(tr!(Msg::ExportDone, entries.len(), &cmd.output));
+    }
+
+    Ok(())
+}
+
+/// Decode every entry into the sink under its resolved path.
+fn export_entries<S: ExportSink>(
+    sink: &mut S,
+    readers: &mut [SourceReader],
+    entries: &[MultiPakEntry],
+    file_name_table: &FileNameTable,
+    bar: &ProgressBar,
+) -> anyhow::Result<()> {
+    for item in entries {
+        let path = resolve_path(&item.entry, file_name_table);
+        sink.add_entry(&mut readers[item.pak_index], &item.entry, &path)
+            .context(format!("Failed to export `{}`.", path))?;
+        bar.inc(1);
+    }
+
+    Ok(())
+}
+
+fn create_output(cmd: &ExportCommand) -> anyhow::Result<File> {
+    check_output_file(Path::new(&cmd.output))?;
+    let file = if cmd.r#override {
+        File::create(&cmd.output)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&cmd.output)
+    }
+    .context(format!("Failed to create output file `{}`.", &cmd.output))?;
+
+    Ok(file)
+}
+
+/// The output file, or stdout for `-`.
+fn open_stream(cmd: &ExportCommand) -> anyhow::Result<Box<dyn Write>> {
+    if cmd.output == STDOUT {
+        Ok(Box::new(std::io::stdout().lock()))
+    } else {
+        Ok(Box::new(create_output(cmd)?))
+    }
+}
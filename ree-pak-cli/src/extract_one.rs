@@ -0,0 +1,72 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use ree_pak_core::{filename::FileName, read::io::archive::PakArchiveReader};
+
+use crate::ExtractOneCommand;
+
+pub fn extract_one(cmd: &ExtractOneCommand) -> anyhow::Result<()> {
+    let hash = match (&cmd.path, &cmd.hash) {
+        (_, Some(hash)) => parse_hash(hash)?,
+        (Some(path), None) => FileName::new(path).hash_mixed(),
+        (None, None) => anyhow::bail!("Either `--path` or `--hash` is required."),
+    };
+
+    // load PAK file
+    let file = File::open(&cmd.input).context(format!("Input file `{}` not found.", &cmd.input))?;
+    let mut reader = BufReader::new(file);
+    let archive = ree_pak_core::read::read_archive(&mut reader)?;
+    let entry = archive
+        .find_entry(hash)
+        .context(format!("Entry {:016X} not found in `{}`.", hash, &cmd.input))?;
+
+    let output_path = match (&cmd.output, &cmd.path) {
+        (Some(output), _) => PathBuf::from(output),
+        (None, Some(path)) => PathBuf::from(path.rsplit('/').next().unwrap_or(path)),
+        (None, None) => PathBuf::from(format!("{:016X}", hash)),
+    };
+    let file = if cmd.r#override {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&output_path)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&output_path)
+    }
+    .context(format!("Failed to create output file `{}`.", output_path.display()))?;
+
+    let mut archive_reader = PakArchiveReader::new(reader, &archive);
+    let mut entry_reader = archive_reader.entry_reader(entry)?;
+    let mut writer = BufWriter::new(file);
+    let size = std::io::copy(&mut entry_reader, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    // guess unknown file extension
+    let mut final_path = output_path.clone();
+    if output_path.extension().is_none() {
+        if let Some(ext) = entry_reader.determine_extension() {
+            final_path = output_path.with_extension(ext);
+            std::fs::rename(&output_path, &final_path)?;
+        }
+    }
+
+    println!("Extracted {} bytes to `{}`.", size, final_path.display());
+
+    Ok(())
+}
+
+/// Parse a hexadecimal entry hash, with or without a `0x` prefix.
+pub(crate) fn parse_hash(hash: &str) -> anyhow::Result<u64> {
+    let digits = hash
+        .strip_prefix("0x")
+        .or_else(|| hash.strip_prefix("0X"))
+        .unwrap_or(hash);
+
+    u64::from_str_radix(digits, 16).context(format!("Invalid hash `{}`.", hash))
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+};
+
+use anyhow::Context;
+use ree_pak_core::{
+    filename::FileNameTable,
+    read::multi::MultiPak,
+    write::{self, PakOptions},
+};
+use serde_json::{json, Value};
+
+use crate::{
+    i18n::{tr, Msg},
+    info::codec_name,
+    report,
+    unpack::{load_filename_table, open_multi_pak},
+    FlattenCommand,
+};
+
+pub fn flatten(cmd: &FlattenCommand) -> anyhow::Result<()> {
+    if cmd.toc.is_none() && cmd.output.is_none() {
+        anyhow::bail!("Nothing to write, pass `--toc` and/or `--output`.");
+    }
+
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+    let multi_pak = open_multi_pak(&cmd.input)?;
+    let overridden_count: usize = multi_pak
+        .paks()
+        .iter()
+        .map(|pak| pak.archive.entries().len())
+        .sum::<usize>()
+        - multi_pak.entries().len();
+
+    if let Some(toc_path) = &cmd.toc {
+        let mut writer = BufWriter::new(create_output(toc_path, cmd.r#override)?);
+        serde_json::to_writer_pretty(&mut writer, &merged_toc(&multi_pak, &file_name_table))?;
+        writeln!(writer)?;
+        writer.flush()?;
+        report::message(tr!(Msg::FlattenToc, toc_path));
+    }
+    if let Some(output) = &cmd.output {
+        let writer = BufWriter::new(create_output(output, cmd.r#override)?);
+        let options = PakOptions::default().with_encrypted_toc(cmd.encrypt_toc);
+        write::flatten(&multi_pak, writer, options)?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .flush()?;
+        report::message(tr!(Msg::FlattenPak, output));
+    }
+
+    report::message(tr!(
+        Msg::FlattenDone,
+        multi_pak.paks().len(),
+        multi_pak.entries().len(),
+        overridden_count
+    ));
+
+    Ok(())
+}
+
+/// The effective entries with the pak each one comes from and the paks it overrides.
+fn merged_toc(multi_pak: &MultiPak, file_name_table: &FileNameTable) -> Value {
+    let paks: Vec<Value> = multi_pak
+        .paks()
+        .iter()
+        .enumerate()
+        .map(|(index, pak)| {
+            json!({
+                "index": index,
+                "source": pak.source.to_string(),
+                "entries": pak.archive.entries().len(),
+            })
+        })
+        .collect();
+    let entries: Vec<Value> = multi_pak
+        .entries()
+        .iter()
+        .zip(multi_pak.provenance())
+        .map(|(item, provenance)| {
+            let entry = &item.entry;
+            json!({
+                "hash": report::hash_string(entry.hash()),
+                "path": file_name_table.get_file_name(entry.hash()),
+                "pak_index": provenance.pak_index,
+                "overrides": provenance.overridden,
+                "offset": entry.offset(),
+                "compressed_size": entry.compressed_size(),
+                "uncompressed_size": entry.uncompressed_size(),
+                "compression": codec_name(entry.compression_method()),
+                "encryption_type": entry.encryption_type(),
+                "checksum": report::hash_string(entry.checksum()),
+            })
+        })
+        .collect();
+
+    json!({ "paks": paks, "entries": entries })
+}
+
+fn create_output(path: &str, r#override: bool) -> anyhow::Result<File> {
+    let file = if r#override {
+        File::create(path)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(path)
+    }
+    .context(format!("Failed to create output file `{}`.", path))?;
+
+    Ok(file)
+}
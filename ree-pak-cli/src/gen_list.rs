@@ -0,0 +1,56 @@
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+};
+
+use anyhow::Context;
+use ree_pak_core::filename::{collect::Candidates, FileNameTable};
+
+use crate::{
+    unpack::{load_filename_table, open_multi_pak},
+    GenListCommand,
+};
+
+pub fn gen_list(cmd: &GenListCommand) -> anyhow::Result<()> {
+    // load project file name table
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+
+    // hashes not resolved by the known list
+    let multi_pak = open_multi_pak(&cmd.pak)?;
+    let unknown: HashSet<u64> = multi_pak
+        .entries()
+        .iter()
+        .map(|item| item.entry.hash())
+        .filter(|hash| file_name_table.get_file_name(*hash).is_none())
+        .collect();
+
+    let mut candidates = Candidates::default();
+    candidates
+        .add_dir(&cmd.input, cmd.scan)
+        .context(format!("Failed to collect candidates from `{}`.", &cmd.input))?;
+    let resolved = candidates.resolve(&unknown);
+
+    let output = if cmd.r#override {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&cmd.output)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&cmd.output)
+    }
+    .context(format!("Failed to create output file `{}`.", &cmd.output))?;
+    let mut writer = BufWriter::new(output);
+    for name in &resolved {
+        writeln!(writer, "{}", name)?;
+    }
+    writer.flush()?;
+
+    println!("Resolved {} of {} unknown hashes.", resolved.len(), unknown.len());
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+//! Translations of messages for humans, selected with the global `--lang` option or the locale.
+//!
+//! Messages live in a compile-time table keyed by [`Msg`], `{}` placeholders are
+//! filled in order. Only text for humans is translated: JSON events, their
+//! fields and files written by commands stay in English, so scripts work the
+//! same in every locale.
+
+use std::{fmt::Display, sync::OnceLock};
+
+use clap::ValueEnum;
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Lang {
+    En,
+    /// Simplified Chinese
+    Zh,
+}
+
+/// Set the language of messages, detected from the locale environment variables if not given.
+pub(crate) fn set_lang(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(detect_lang));
+}
+
+fn lang() -> Lang {
+    *LANG.get().unwrap_or(&Lang::En)
+}
+
+/// Language of the first set variable of `LC_ALL`, `LC_MESSAGES` and `LANG`, e.g. `zh_CN.UTF-8`.
+fn detect_lang() -> Lang {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    if locale.to_ascii_lowercase().starts_with("zh") {
+        Lang::Zh
+    } else {
+        Lang::En
+    }
+}
+
+macro_rules! messages {
+    ($($id:ident => $en:literal, $zh:literal;)*) => {
+        /// A translated message.
+        #[derive(Debug, Clone, Copy)]
+        pub(crate) enum Msg {
+            $($id,)*
+        }
+
+        impl Msg {
+            fn template(self, lang: Lang) -> &'static str {
+                match (self, lang) {
+                    $(
+                        (Msg::$id, Lang::En) => $en,
+                        (Msg::$id, Lang::Zh) => $zh,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+messages! {
+    ProjectListMissing =>
+        "Project file `{}` not found, no lists are installed in `{}`.",
+        "未找到项目文件 `{}`，`{}` 中没有安装任何列表。";
+    ProjectListNotFound =>
+        "Project file `{}` not found, available projects: {}.",
+        "未找到项目文件 `{}`，可用的项目：{}。";
+    NoPakFiles =>
+        "No PAK files found in `{}`.",
+        "在 `{}` 中未找到 PAK 文件。";
+    InputNotFound =>
+        "Input file `{}` not found.",
+        "未找到输入文件 `{}`。";
+    ListMismatch =>
+        "Warning: the list of `{}` resolves only {} of {} sampled entries.",
+        "警告：`{}` 的列表在抽样的条目中只能解析 {} 个，共 {} 个。";
+    ListSuggestion =>
+        "The list of `{}` resolves {} of them, did you mean `--project {}`?",
+        "`{}` 的列表可以解析其中 {} 个，是否应使用 `--project {}`？";
+    PatchOnly =>
+        "Warning: `{}` is a patch PAK and only holds files changed since `{}`.",
+        "警告：`{}` 是补丁 PAK，只包含自 `{}` 以来修改过的文件。";
+    UseWithBase =>
+        "Use `--with-base` to unpack it merged with its base and earlier patches.",
+        "使用 `--with-base` 可将其与基础 PAK 及之前的补丁合并后解包。";
+    IgnoreFile =>
+        "Ignore file: `{}`",
+        "忽略文件：`{}`";
+    IgnoredFiles =>
+        "Ignored {} of {} files.",
+        "已忽略 {} 个文件，共 {} 个。";
+    SelectedFiles =>
+        "Selected {} of {} files.",
+        "已选择 {} 个文件，共 {} 个。";
+    ChangedFiles =>
+        "{} of {} files changed since the previous TOC, {} files were removed.",
+        "自上一个 TOC 以来 {} 个文件有变化，共 {} 个，{} 个文件已被移除。";
+    SampledFiles =>
+        "Sampled {} of {} files with seed {}.",
+        "已抽取 {} 个文件，共 {} 个，种子 {}。";
+    UnpackProgress =>
+        "{pos}/{len} files written {wide_bar} elapsed: {elapsed} eta: {eta}",
+        "已写入 {pos}/{len} 个文件 {wide_bar} 用时：{elapsed} 剩余：{eta}";
+    InputPak =>
+        "Input: `{}`",
+        "输入：`{}`";
+    OutputArchive =>
+        "Output archive: `{}`",
+        "输出归档：`{}`";
+    OutputDirectory =>
+        "Output directory: `{}`",
+        "输出目录：`{}`";
+    EntryError =>
+        "Error processing entry: {}\nEntry: {}",
+        "处理条目时出错：{}\n条目：{}";
+    LinkedDuplicates =>
+        "Linked {} duplicate files, saved {}.",
+        "已链接 {} 个重复文件，节省 {}。";
+    ShortenedPaths =>
+        "Renamed {} paths exceeding the length limit or with invalid characters.",
+        "已重命名 {} 个超出长度限制或包含无效字符的路径。";
+    ContentRoot =>
+        "Content root of {} files: {}",
+        "{} 个文件的内容根哈希：{}";
+    ManifestWritten =>
+        "Wrote the manifest of extracted files into `{}`.",
+        "已将解包文件清单写入 `{}`。";
+    DoneWithErrors =>
+        "Done with {} errors",
+        "完成，出现 {} 个错误";
+    Done =>
+        "Done.",
+        "完成。";
+    PackPredictedSize =>
+        "Predicted size: {} bytes.",
+        "预计大小：{} 字节。";
+    PackFat32Warning =>
+        "Warning: the pak is likely larger than 4 GiB and cannot be stored on FAT32 drives.",
+        "警告：pak 文件可能大于 4 GiB，无法存放在 FAT32 驱动器上。";
+    PackProgress =>
+        "{bytes}/{total_bytes} packed {wide_bar} elapsed: {elapsed} eta: {eta} {msg}",
+        "已打包 {bytes}/{total_bytes} {wide_bar} 用时：{elapsed} 剩余：{eta} {msg}";
+    PackProgressPrediction =>
+        "(predicted size: {})",
+        "（预计大小：{}）";
+    PackDone =>
+        "Packed {} files into `{}`, {} bytes.",
+        "已将 {} 个文件打包到 `{}`，共 {} 字节。";
+    PackCompressionStats =>
+        "Compressed {} files, stored {} files that do not compress, {} files set by extension.",
+        "已压缩 {} 个文件，{} 个无法压缩的文件未压缩存储，{} 个文件按扩展名设置。";
+    ExportProgress =>
+        "{pos}/{len} files exported {wide_bar} elapsed: {elapsed} eta: {eta}",
+        "已导出 {pos}/{len} 个文件 {wide_bar} 用时：{elapsed} 剩余：{eta}";
+    ExportDone =>
+        "Exported {} files into `{}`.",
+        "已将 {} 个文件导出到 `{}`。";
+    ExportDoneStdout =>
+        "Exported {} files to stdout.",
+        "已将 {} 个文件导出到标准输出。";
+    VerifyProgress =>
+        "{pos}/{len} files verified {wide_bar} elapsed: {elapsed} eta: {eta}",
+        "已校验 {pos}/{len} 个文件 {wide_bar} 用时：{elapsed} 剩余：{eta}";
+    VerifyDone =>
+        "All {} entries verified.",
+        "全部 {} 个条目已通过校验。";
+    VerifyDecoded =>
+        "Decoded {} of {} entries, {} in {} ({}/s).",
+        "已解码 {} 个条目，共 {} 个，{}，用时 {}（{}/s）。";
+    VerifyFailed =>
+        "{} of {} entries failed verification",
+        "{} 个条目未通过校验，共 {} 个";
+    FlattenToc =>
+        "Wrote the merged TOC into `{}`.",
+        "已将合并后的 TOC 写入 `{}`。";
+    FlattenPak =>
+        "Wrote the flattened pak into `{}`.",
+        "已将合并后的 pak 写入 `{}`。";
+    FlattenDone =>
+        "Merged {} paks into {} entries, {} overridden entries dropped.",
+        "已将 {} 个 pak 合并为 {} 个条目，丢弃 {} 个被覆盖的条目。";
+    SearchProgress =>
+        "{pos}/{len} files searched {wide_bar} elapsed: {elapsed} eta: {eta}",
+        "已搜索 {pos}/{len} 个文件 {wide_bar} 用时：{elapsed} 剩余：{eta}";
+    SearchDone =>
+        "Found matches in {} of {} files.",
+        "在 {} 个文件中找到匹配，共 {} 个。";
+    BundleProgress =>
+        "{pos}/{len} files packed {wide_bar} elapsed: {elapsed} eta: {eta}",
+        "已打包 {pos}/{len} 个文件 {wide_bar} 用时：{elapsed} 剩余：{eta}";
+    BundleDone =>
+        "Wrote {} files into the bundle `{}`.",
+        "已将 {} 个文件写入打包文件 `{}`。";
+    HintInputMissing =>
+        "Hint: check the input path, it must be an existing file or directory.",
+        "提示：请检查输入路径，它必须是已存在的文件或目录。";
+    HintOutputIsDirectory =>
+        "Hint: the output is an existing directory, give the path of a file inside it instead.",
+        "提示：输出路径是已存在的目录，请改为指定该目录中的文件路径。";
+    HintPermissionDenied =>
+        "Hint: check that the path is not read-only or opened by another program such as the game.",
+        "提示：请检查路径是否为只读，或是否被游戏等其他程序占用。";
+    HintUnsupportedPak =>
+        "Hint: the file is not a pak or uses a version this tool cannot read, `info` shows its header.",
+        "提示：该文件不是 pak 文件或使用了本工具无法读取的版本，可用 `info` 查看其文件头。";
+    HintBadList =>
+        "Hint: check the project name and the list files in `assets/filelist`, `which-list` finds a matching list.",
+        "提示：请检查项目名称及 `assets/filelist` 中的列表文件，`which-list` 可以找到匹配的列表。";
+}
+
+/// Fill the `{}` placeholders of the message template in the current language.
+pub(crate) fn format(msg: Msg, args: &[&dyn Display]) -> String {
+    let mut parts = msg.template(lang()).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            text.push_str(&arg.to_string());
+        }
+        text.push_str(part);
+    }
+
+    text
+}
+
+/// Format a [`Msg`] with its arguments, e.g. `tr!(Msg::InputPak, path)`.
+macro_rules! tr {
+    ($msg:expr $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::format($msg, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+pub(crate) use tr;
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use ree_pak_core::{
+    filename::FileNameTable,
+    layout::LayoutMap,
+    pak::{CompressionMethod, EntrySuspicion},
+    read::{io::archive::PakArchiveReader, read_archive_with_options, ReadOptions},
+    stats::CodecSummary,
+};
+
+use serde_json::json;
+
+use crate::{report, unpack::load_filename_table, InfoCommand};
+
+pub fn info(cmd: &InfoCommand) -> anyhow::Result<()> {
+    // load PAK file
+    let file = std::fs::File::open(&cmd.input).context(format!("Input file `{}` not found.", &cmd.input))?;
+    let mut reader = std::io::BufReader::new(file);
+    // inconsistent entries are listed below instead of failing the summary
+    let options = ReadOptions::default()
+        .with_validate_entries(false)
+        .with_magic_scan(cmd.scan_magic.map_or(0, |window| window.0));
+    let archive = read_archive_with_options(&mut reader, options)?;
+    let header = archive.header();
+    let entries = archive.entries();
+
+    let compressed_size: u64 = entries.iter().map(|entry| entry.real_compressed_size()).sum();
+    let uncompressed_size: u64 = entries.iter().map(|entry| entry.uncompressed_size()).sum();
+    let mut compression_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut encryption_counts: BTreeMap<u16, usize> = BTreeMap::new();
+    for entry in entries {
+        *compression_counts
+            .entry(codec_name(entry.compression_method()))
+            .or_default() += 1;
+        if entry.encryption_type() != 0 {
+            *encryption_counts.entry(entry.encryption_type()).or_default() += 1;
+        }
+    }
+
+    // chunk tables are not parsed, entries whose sizes look chunked are listed instead
+    let likely_chunked: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.suspicion() == Some(EntrySuspicion::LikelyChunked))
+        .collect();
+    let chunk_table = if likely_chunked.is_empty() { "none" } else { "unknown" };
+
+    let metadata = PakArchiveReader::new(&mut reader, &archive)
+        .read_metadata()
+        .context("Failed to read pak metadata")?;
+    let layout = match &cmd.layout {
+        Some(layout_path) => {
+            let file_size = std::fs::metadata(&cmd.input)?.len();
+            let layout = LayoutMap::new(&archive, file_size);
+            std::fs::write(layout_path, layout.to_json()?)
+                .context(format!("Failed to write layout file `{}`.", layout_path))?;
+            Some(layout)
+        }
+        None => None,
+    };
+    let summary = match cmd.by_prefix {
+        Some(depth) => {
+            let file_name_table = match &cmd.project {
+                Some(project) => load_filename_table(project)?,
+                None => FileNameTable::default(),
+            };
+            let mut summary = CodecSummary::new(depth);
+            for entry in entries {
+                summary.add(file_name_table.get_file_name(entry.hash()), entry);
+            }
+            Some(summary)
+        }
+        None => None,
+    };
+
+    if report::is_json() {
+        let prefixes = summary.as_ref().map(|summary| {
+            summary
+                .prefixes()
+                .iter()
+                .flat_map(|(prefix, codecs)| {
+                    codecs.iter().map(move |(codec, stats)| {
+                        json!({
+                            "prefix": prefix,
+                            "codec": codec_name(*codec),
+                            "count": stats.count,
+                            "compressed_size": stats.compressed_size,
+                            "uncompressed_size": stats.uncompressed_size,
+                        })
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+        report::emit(json!({
+            "event": "info",
+            "version": format!("{}.{}", header.major_version(), header.minor_version()),
+            "feature": header.feature(),
+            "base_offset": archive.base_offset(),
+            "entries": entries.len(),
+            "compressed_size": compressed_size,
+            "uncompressed_size": uncompressed_size,
+            "compression": compression_counts,
+            "encryption": encryption_counts,
+            "chunk_table": {
+                "status": chunk_table,
+                "likely_chunked_entries": likely_chunked.len(),
+            },
+            "likely_chunked": likely_chunked.iter().map(|entry| json!({
+                "hash": format!("{:016X}", entry.hash()),
+                "offset": entry.offset(),
+                "compressed_size": entry.compressed_size(),
+                "uncompressed_size": entry.uncompressed_size(),
+            })).collect::<Vec<_>>(),
+            "metadata": metadata.map(|metadata| json!({
+                "name": metadata.name,
+                "author": metadata.author,
+                "version": metadata.version,
+                "tool_version": metadata.tool_version,
+            })),
+            "layout": layout.map(|layout| {
+                let (gaps, overlaps) = layout.anomaly_count();
+                json!({ "gaps": gaps, "overlaps": overlaps })
+            }),
+            "prefixes": prefixes,
+        }));
+        return Ok(());
+    }
+
+    println!(
+        "Version:           {}.{}",
+        header.major_version(),
+        header.minor_version()
+    );
+    println!(
+        "Features:          {:#06X} ({})",
+        header.feature(),
+        describe_features(header.feature())
+    );
+    if archive.base_offset() > 0 {
+        println!("Header offset:     {:#X}", archive.base_offset());
+    }
+    println!("Entries:           {}", entries.len());
+    println!("Compressed size:   {}", format_size(compressed_size));
+    println!("Uncompressed size: {}", format_size(uncompressed_size));
+    println!("Compression:       {}", format_counts(&compression_counts));
+    if encryption_counts.is_empty() {
+        println!("Encrypted entries: none");
+    } else {
+        let counts: BTreeMap<String, usize> = encryption_counts
+            .iter()
+            .map(|(ty, count)| (format!("type {}", ty), *count))
+            .collect();
+        println!("Encrypted entries: {}", format_counts(&counts));
+    }
+    if likely_chunked.is_empty() {
+        println!("Chunk table:       none, no entry has chunked sizes");
+    } else {
+        println!(
+            "Chunk table:       unknown, {} entries have chunked sizes",
+            likely_chunked.len()
+        );
+    }
+    if !likely_chunked.is_empty() {
+        println!("Likely chunked:    {} entries", likely_chunked.len());
+        for entry in &likely_chunked {
+            println!(
+                "  {:016X} at {:#X}, {} of {} bytes stored",
+                entry.hash(),
+                entry.offset(),
+                entry.compressed_size(),
+                entry.uncompressed_size()
+            );
+        }
+    }
+
+    if let Some(metadata) = metadata {
+        let fields = [
+            ("Mod name", metadata.name),
+            ("Mod author", metadata.author),
+            ("Mod version", metadata.version),
+            ("Packed by", metadata.tool_version),
+        ];
+        for (title, value) in fields {
+            if let Some(value) = value {
+                println!("{:<19}{}", format!("{}:", title), value);
+            }
+        }
+    }
+
+    if let Some(layout) = &layout {
+        let (gaps, overlaps) = layout.anomaly_count();
+        println!("Layout:            {} gaps, {} overlaps", gaps, overlaps);
+    }
+
+    if let Some(summary) = &summary {
+        println!();
+        println!("prefix\tcodec\tcount\tcompressed_size\tuncompressed_size\tratio");
+        for (prefix, codecs) in summary.prefixes() {
+            for (codec, stats) in codecs {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{:.3}",
+                    prefix,
+                    codec_name(*codec),
+                    stats.count,
+                    stats.compressed_size,
+                    stats.uncompressed_size,
+                    stats.ratio()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn codec_name(compression: CompressionMethod) -> &'static str {
+    match compression {
+        CompressionMethod::None => "none",
+        CompressionMethod::Deflate => "deflate",
+        CompressionMethod::Zstd => "zstd",
+    }
+}
+
+/// Feature flag of paks whose entry table is encrypted.
+const ENCRYPTED_TOC_FEATURE: u16 = 8;
+
+fn describe_features(feature: u16) -> &'static str {
+    match feature {
+        0 => "none",
+        ENCRYPTED_TOC_FEATURE => "encrypted entry table",
+        _ => "unknown",
+    }
+}
+
+fn format_counts<K: std::fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+    counts
+        .iter()
+        .map(|(key, count)| format!("{} {}", key, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+
+    format!("{} B ({:.2} {})", bytes, size, unit)
+}
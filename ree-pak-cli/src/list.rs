@@ -0,0 +1,161 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use ree_pak_core::{
+    filename::FileNameTable,
+    metadata::{PakMetadata, METADATA_PATH},
+    read::io::archive::PakArchiveReader,
+};
+
+use crate::{
+    unpack::{load_filename_table, FLAT_MAPPING_FILE_NAME, SHORTENED_PATHS_FILE_NAME},
+    ListAction, ListCommand, ListFromDirCommand,
+};
+
+pub fn list(cmd: &ListCommand) -> anyhow::Result<()> {
+    let input = match (&cmd.action, &cmd.input) {
+        (Some(ListAction::FromDir(from_dir)), _) => return list_from_dir(from_dir),
+        (None, Some(input)) => input,
+        (None, None) => anyhow::bail!("Input file is required."),
+    };
+
+    // load project file name table
+    let mut file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+    file_name_table.push_str(METADATA_PATH);
+
+    // load PAK file
+    let file = std::fs::File::open(input).context(format!("Input file `{}` not found.", input))?;
+    let mut reader = std::io::BufReader::new(file);
+    let archive = ree_pak_core::read::read_archive(&mut reader)?;
+
+    let (start_index, entries) = match &cmd.index {
+        Some(range) => (
+            range.start_index(),
+            archive
+                .entries_in_range(range.bounds())
+                .context("Index range is out of bounds")?,
+        ),
+        None => (0, archive.entries()),
+    };
+
+    let metadata = PakArchiveReader::new(&mut reader, &archive)
+        .read_metadata()
+        .context("Failed to read pak metadata")?;
+    if let Some(metadata) = metadata {
+        print_metadata(&metadata);
+    }
+
+    println!("index\thash\tcompressed_size\tuncompressed_size\tpath");
+    for (i, entry) in entries.iter().enumerate() {
+        let name = file_name_table.get_file_name(entry.hash()).unwrap_or_default();
+        println!(
+            "{}\t{:016X}\t{}\t{}\t{}",
+            start_index + i,
+            entry.hash(),
+            entry.compressed_size(),
+            entry.uncompressed_size(),
+            name
+        );
+    }
+
+    Ok(())
+}
+
+/// Print metadata as comment lines before the entry table.
+fn print_metadata(metadata: &PakMetadata) {
+    let fields = [
+        ("name", metadata.name.clone()),
+        ("author", metadata.author.clone()),
+        ("version", metadata.version.clone()),
+        ("build_timestamp", metadata.build_timestamp.map(|t| t.to_string())),
+        ("tool_version", metadata.tool_version.clone()),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            println!("# {}: {}", key, value);
+        }
+    }
+}
+
+/// Write the list of file names of an extracted directory, reversing the unpack layout.
+///
+/// Flat output and shortened paths are restored by their mapping files. Files
+/// under `_Unknown` are named by hash only, so they are skipped.
+fn list_from_dir(cmd: &ListFromDirCommand) -> anyhow::Result<()> {
+    let input_dir = Path::new(&cmd.input);
+    if !input_dir.is_dir() {
+        anyhow::bail!("Input directory `{}` not found.", &cmd.input);
+    }
+
+    let mut names = BTreeSet::new();
+    let mut unknown_count = 0;
+    let mapping_path = input_dir.join(FLAT_MAPPING_FILE_NAME);
+    if mapping_path.is_file() {
+        let mapping = std::fs::read_to_string(&mapping_path)?;
+        for line in mapping.lines() {
+            if let Some((_, original)) = line.split_once('\t') {
+                if original.starts_with("_Unknown/") {
+                    unknown_count += 1;
+                } else {
+                    names.insert(original.to_string());
+                }
+            }
+        }
+    } else {
+        let shortened_path = input_dir.join(SHORTENED_PATHS_FILE_NAME);
+        let shortened = if shortened_path.is_file() {
+            std::fs::read_to_string(&shortened_path)?
+        } else {
+            String::new()
+        };
+        let originals: HashMap<&str, &str> = shortened.lines().filter_map(|line| line.split_once('\t')).collect();
+        for dir_entry in walkdir::WalkDir::new(input_dir) {
+            let dir_entry = dir_entry?;
+            if !dir_entry.file_type().is_file() {
+                continue;
+            }
+            let path = dir_entry
+                .path()
+                .strip_prefix(input_dir)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if path == SHORTENED_PATHS_FILE_NAME {
+                continue;
+            }
+            let path = originals
+                .get(path.as_str())
+                .map(|original| original.to_string())
+                .unwrap_or(path);
+            if path.starts_with("_Unknown/") {
+                unknown_count += 1;
+            } else {
+                names.insert(path);
+            }
+        }
+    }
+
+    let mut writer: Box<dyn Write> = match &cmd.output {
+        Some(output) => Box::new(BufWriter::new(
+            File::create(output).context(format!("Failed to create output file `{}`.", output))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    for name in &names {
+        writeln!(writer, "{}", name)?;
+    }
+    writer.flush()?;
+
+    eprintln!("Listed {} files, skipped {} unknown files.", names.len(), unknown_count);
+
+    Ok(())
+}
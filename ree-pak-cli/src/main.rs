@@ -1,18 +1,104 @@
-use clap::{Args, Parser, Subcommand};
+use std::{ops::Bound, process::ExitCode, str::FromStr};
 
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use i18n::Lang;
+use ree_pak_core::concurrency::{self, ConcurrencyConfig};
+
+mod bundle;
+mod convert;
+mod dedup;
+mod diff;
+mod doctor;
+mod error;
+mod export;
+mod extract_one;
+mod flatten;
+mod gen_list;
+mod i18n;
+mod info;
+mod list;
+mod make_patch;
+mod pack;
+mod report;
+mod search;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod unpack;
+#[cfg(feature = "remote-lists")]
+mod update_lists;
+mod verify;
+mod which_list;
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    /// Maximum number of worker threads, defaults to the number of CPUs
+    #[clap(long, global = true)]
+    threads: Option<usize>,
+    /// Run worker threads with a lower priority
+    #[clap(long, global = true, default_value = "false")]
+    low_priority: bool,
+    /// Maximum number of output files open at once, defaults to half of the process limit
+    #[clap(long, global = true)]
+    max_open_files: Option<usize>,
+    /// Maximum bytes written per second when unpacking, with an optional K, M or G suffix, e.g. "50M"
+    #[clap(long, global = true)]
+    io_limit: Option<ByteSize>,
+    /// Maximum decoded bytes held in memory at once when unpacking into a tar archive,
+    /// with an optional K, M or G suffix, defaults to 1G
+    #[clap(long, global = true)]
+    max_memory: Option<ByteSize>,
+    /// Encoding of list files that are not UTF-8 or UTF-16, e.g. "gbk"
+    #[clap(long, global = true)]
+    list_encoding: Option<String>,
+    /// Print progress and reports as JSON lines on stdout, for unpack, pack and info
+    #[clap(long, global = true, default_value = "false")]
+    json: bool,
+    /// Language of messages, detected from `LC_ALL`, `LC_MESSAGES` or `LANG` by default,
+    /// JSON output is always in English
+    #[clap(long, global = true, value_enum)]
+    lang: Option<Lang>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Unpack a PAK file
     Unpack(UnpackCommand),
+    /// List entries of a PAK file
+    List(ListCommand),
+    /// Check the environment and assets, printing a diagnostic report
+    Doctor(DoctorCommand),
+    /// Rewrite a PAK file with another TOC version
+    Convert(ConvertCommand),
+    /// Check that all entries of a PAK file can be decoded
+    Verify(VerifyCommand),
+    /// Pack a directory into a PAK file
+    Pack(PackCommand),
+    /// Extract a single file from a PAK file by path or hash
+    ExtractOne(ExtractOneCommand),
+    /// Generate a file name list for unknown hashes from an extracted directory
+    GenList(GenListCommand),
+    /// Print a summary of a PAK file
+    Info(InfoCommand),
+    /// Compare two PAK files, reporting added, removed and modified entries
+    Diff(DiffCommand),
+    /// Download or refresh file name lists from a manifest
+    #[cfg(feature = "remote-lists")]
+    UpdateLists(UpdateListsCommand),
+    /// Find which project lists contain a file path or hash
+    WhichList(WhichListCommand),
+    /// Pack files of a mod directory that differ from a PAK into a new patch PAK
+    MakePatch(MakePatchCommand),
+    /// Write all files of a PAK into another archive format
+    Export(ExportCommand),
+    /// Merge a base PAK and its patches into a TOC description and/or a single PAK
+    Flatten(FlattenCommand),
+    /// Find entries whose decoded content matches a regular expression
+    Search(SearchCommand),
+    /// Write a mod archive for sharing, a zip with the PAK, a content manifest and metadata
+    Bundle(BundleCommand),
 }
 
 #[derive(Debug, Args)]
@@ -20,7 +106,7 @@ struct UnpackCommand {
     /// Game project name, e.g. "MHRS_PC_Demo"
     #[clap(short, long)]
     project: String,
-    /// Input PAK file path
+    /// Input PAK file path, or a directory to unpack all PAK files in patch order
     #[clap(short, long)]
     input: String,
     /// Output directory path
@@ -32,12 +118,667 @@ struct UnpackCommand {
     /// Override existing files
     #[clap(long, default_value = "false")]
     r#override: bool,
+    /// Write all files into the output directory without subdirectories,
+    /// a mapping file is written to restore the hierarchy later
+    #[clap(long, default_value = "false")]
+    flat: bool,
+    /// Separator replacing `/` in file names when `--flat` is enabled
+    #[clap(long, default_value = "__")]
+    flat_separator: String,
+    /// Only unpack entries in the TOC index range, e.g. "1000..2000"
+    #[clap(long)]
+    index: Option<IndexRange>,
+    /// Ignore file with gitignore syntax excluding paths from unpacking,
+    /// defaults to `.pakignore` in the current directory if present
+    #[clap(long)]
+    ignore_file: Option<String>,
+    /// Only unpack paths matching a glob, e.g. "natives/stm/sound/**", can be repeated
+    #[clap(long)]
+    include: Vec<String>,
+    /// Skip paths matching a glob, can be repeated
+    #[clap(long)]
+    exclude: Vec<String>,
+    /// Only unpack entries that are new or changed since a previous TOC written by `flatten --toc`
+    /// or `unpack --manifest`, compared by hash, sizes and checksum, to extract the delta of a game update
+    #[clap(long)]
+    changed_since: Option<String>,
+    /// Only unpack a random subset of the selected entries, a percentage such as "1%" or a count such as "500"
+    #[clap(long)]
+    sample: Option<SampleSize>,
+    /// Seed of `--sample`, the same seed picks the same entries, random if not provided
+    #[clap(long, requires = "sample")]
+    seed: Option<u64>,
+    /// Write all files into a single tar archive instead of the output directory,
+    /// avoiding per-file overhead such as antivirus scans of each created file
+    #[clap(long, conflicts_with_all = ["output", "flat"])]
+    tar: Option<String>,
+    /// Order of extraction, `smallest-first` finishes most files early and leaves large ones last
+    #[clap(long, value_enum, default_value_t = UnpackOrder::Toc)]
+    order: UnpackOrder,
+    /// Maximum length of output file paths in bytes, longer paths are shortened
+    /// and recorded in `_ShortenedPaths.txt`, defaults to the platform limit
+    #[clap(long)]
+    max_path_len: Option<usize>,
+    /// Replace characters not allowed in Windows file names, such as `:` and `?`, with this
+    /// character, renamed paths are recorded in `_ShortenedPaths.txt`, defaults to `_` on Windows
+    #[clap(long, conflicts_with = "tar")]
+    replace_invalid: Option<char>,
+    /// When the input is a patch PAK, unpack it merged with its base PAK and
+    /// earlier patches found in the same directory
+    #[clap(long, default_value = "false")]
+    with_base: bool,
+    /// Replace files identical to an earlier extracted file with links to it
+    #[clap(long, value_enum, conflicts_with = "tar")]
+    dedup: Option<DedupMode>,
+    /// Write the stored bytes of entries without decompression or decryption as `.raw` files,
+    /// each with a `.raw.json` file of the entry metadata
+    #[clap(long, default_value = "false", conflicts_with_all = ["tar", "dedup"])]
+    raw: bool,
+    /// Print a SHA-256 merkle root over the paths and contents of the extracted files,
+    /// equal roots mean identical output
+    #[clap(long, default_value = "false", conflicts_with = "raw")]
+    content_hash: bool,
+    /// Write a JSON manifest of the extracted files after unpacking, with the hash, path, sizes,
+    /// compression and checksum of each entry and the SHA-256 of the written content
+    #[clap(long, conflicts_with = "raw")]
+    manifest: Option<String>,
+    /// Octal mode of extracted files, e.g. "644", set regardless of the umask (Unix only)
+    #[clap(long)]
+    file_mode: Option<FileMode>,
+    /// Octal mode of created directories, e.g. "755", set regardless of the umask (Unix only)
+    #[clap(long, conflicts_with = "tar")]
+    dir_mode: Option<FileMode>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum UnpackOrder {
+    Toc,
+    SmallestFirst,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DedupMode {
+    Hardlink,
+    Symlink,
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct ListCommand {
+    #[command(subcommand)]
+    action: Option<ListAction>,
+    /// Game project name, file names are resolved if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Input PAK file path
+    #[clap(short, long, required = true)]
+    input: Option<String>,
+    /// Only list entries in the TOC index range, e.g. "1000..2000"
+    #[clap(long)]
+    index: Option<IndexRange>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ListAction {
+    /// Reconstruct a file name list from an extracted directory
+    FromDir(ListFromDirCommand),
+}
+
+#[derive(Debug, Args)]
+struct ListFromDirCommand {
+    /// Extracted directory path
+    input: String,
+    /// Output list file path, prints to stdout if not provided
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct InfoCommand {
+    /// Input PAK file path
+    #[clap(short, long)]
+    input: String,
+    /// Write the byte layout of the data region as JSON, with gaps and overlaps between entries
+    #[clap(long)]
+    layout: Option<String>,
+    /// Print the codec mix per directory prefix of this many path components, e.g. 3 for "natives/stm/sound"
+    #[clap(long, num_args = 0..=1, default_missing_value = "3")]
+    by_prefix: Option<usize>,
+    /// Game project name, file names are resolved for `--by-prefix` if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Search this many bytes for the header of a dump with junk before it, 1M without a value
+    #[clap(long, num_args = 0..=1, default_missing_value = "1M")]
+    scan_magic: Option<ByteSize>,
+}
+
+#[derive(Debug, Args)]
+struct DiffCommand {
+    /// Old PAK file path
+    #[clap(long = "a")]
+    a: String,
+    /// New PAK file path
+    #[clap(long = "b")]
+    b: String,
+    /// Game project name, file names are resolved if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// How far contents are compared when entries have no checksum
+    #[clap(long, value_enum, default_value_t = DiffStrictness::Full)]
+    strictness: DiffStrictness,
+    /// Extract added and modified files of the new PAK into this directory
+    #[clap(long)]
+    extract: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DiffStrictness {
+    Size,
+    Preview,
+    Full,
+}
+
+#[derive(Debug, Args)]
+struct WhichListCommand {
+    /// Path of the file, e.g. "natives/stm/..."
+    #[clap(long, required_unless_present = "hash")]
+    path: Option<String>,
+    /// Hash of the file in hexadecimal, e.g. "0x958EDD0C65B486A1"
+    #[clap(long, conflicts_with = "path")]
+    hash: Option<String>,
+}
+
+#[cfg(feature = "remote-lists")]
+#[derive(Debug, Args)]
+struct UpdateListsCommand {
+    /// URL of the list manifest
+    #[clap(long)]
+    manifest: String,
+    /// Projects to update, all projects in the manifest if omitted
+    projects: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct DoctorCommand {
+    /// Output directory to check for write permission
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ConvertCommand {
+    /// Input PAK file path
+    #[clap(short, long)]
+    input: String,
+    /// Output PAK file path
+    #[clap(short, long)]
+    output: String,
+    /// Target TOC version, e.g. "4.0"
+    #[clap(long)]
+    to_version: String,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct VerifyCommand {
+    /// Game project name, file names are resolved if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Input PAK file path, or a directory of PAK files in patch order
+    #[clap(short, long)]
+    input: String,
+    /// Also compute the CRC-32 of every entry, printing the status, size and
+    /// digest of each entry and the overall throughput, not only the failures
+    #[clap(long, default_value = "false")]
+    deep: bool,
+}
+
+#[derive(Debug, Args)]
+struct SearchCommand {
+    /// Game project name, file names are resolved and `--ext` can match them if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Input PAK file path, or a directory of PAK files in patch order
+    #[clap(short, long)]
+    input: String,
+    /// Regular expression matched against the decoded content, e.g. "(?i)rathalos"
+    #[clap(long)]
+    pattern: String,
+    /// Only search files with these extensions, e.g. "msg,user"
+    #[clap(long, value_delimiter = ',')]
+    ext: Vec<String>,
+    /// Match the content as UTF-16LE text, as strings are stored in most RE Engine files
+    #[clap(long, default_value = "false")]
+    utf16: bool,
+}
+
+#[derive(Debug, Args)]
+struct BundleCommand {
+    /// Input PAK file path, or a mod directory to pack
+    #[clap(short, long)]
+    input: String,
+    /// Output zip path
+    #[clap(short, long)]
+    output: String,
+    /// Game project name, file names in the manifest are resolved if provided, for a PAK input
+    #[clap(short, long, conflicts_with = "list_file")]
+    project: Option<String>,
+    /// File name list, input paths are matched case-insensitively
+    /// and hashed with the listed path, for a directory input
+    #[clap(long)]
+    list_file: Option<String>,
+    /// Compression of entries packed from a directory
+    #[clap(long, value_enum, default_value_t = PackCompression::None)]
+    compression: PackCompression,
+    /// File name of the PAK in the bundle, defaults to the input name
+    #[clap(long)]
+    pak_name: Option<String>,
+    /// Readme file included as `README.md`
+    #[clap(long)]
+    readme: Option<String>,
+    /// Image included as `thumbnail` with its extension
+    #[clap(long)]
+    thumbnail: Option<String>,
+    /// Mod name in the bundle metadata, defaults to the one in the pak metadata entry
+    #[clap(long)]
+    mod_name: Option<String>,
+    /// Author in the bundle metadata, defaults to the one in the pak metadata entry
+    #[clap(long)]
+    author: Option<String>,
+    /// Mod version in the bundle metadata, defaults to the one in the pak metadata entry
+    #[clap(long)]
+    mod_version: Option<String>,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct PackCommand {
+    /// Input directory path, can be repeated, later directories override files of earlier ones
+    #[clap(short, long, required_unless_present = "manifest")]
+    input: Vec<String>,
+    /// JSON manifest of files to pack, mapping pak paths to source files with optional
+    /// per-file compression, it overrides files of the input directories
+    #[clap(long)]
+    manifest: Option<String>,
+    /// Output PAK file path
+    #[clap(short, long)]
+    output: String,
+    /// File name list, input paths are matched case-insensitively
+    /// and hashed with the listed path
+    #[clap(long)]
+    list_file: Option<String>,
+    /// Mod name recorded in the pak metadata entry
+    #[clap(long)]
+    mod_name: Option<String>,
+    /// Author recorded in the pak metadata entry
+    #[clap(long)]
+    author: Option<String>,
+    /// Mod version recorded in the pak metadata entry
+    #[clap(long)]
+    mod_version: Option<String>,
+    /// Encrypt the entry table, as retail patch PAKs do
+    #[clap(long, default_value = "false")]
+    encrypt_toc: bool,
+    /// Write a byte-identical PAK for identical input, entries are ordered by hash
+    /// and the metadata build time is taken from `SOURCE_DATE_EPOCH` if set
+    #[clap(long, default_value = "false")]
+    deterministic: bool,
+    /// Compression of packed entries, files whose first 64 KiB do not shrink below
+    /// 90% are stored uncompressed
+    #[clap(long, value_enum, default_value_t = PackCompression::None)]
+    compression: PackCompression,
+    /// JSON file with the compression ratio limit and compression per extension,
+    /// e.g. `{ "max_ratio": 0.85, "extensions": { "tex": "none" } }`
+    #[clap(long)]
+    compression_config: Option<String>,
+    /// Compress every file with `--compression`, even if it does not shrink
+    #[clap(long, default_value = "false")]
+    compress_all: bool,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExportCommand {
+    /// Game project name, file names are resolved if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Input PAK file path, or a directory to export all PAK files in patch order
+    #[clap(short, long)]
+    input: String,
+    /// Output archive path, `-` writes tar formats to stdout, a directory path for the dir format
+    #[clap(short, long)]
+    output: String,
+    /// Archive format
+    #[clap(long, value_enum, default_value_t = ExportFormat::Zip)]
+    format: ExportFormat,
+    /// Store zip files uncompressed
+    #[clap(long, default_value = "false")]
+    store: bool,
+    /// Only store paths, sizes and content hashes in a SQLite database, without the file data
+    #[clap(long, default_value = "false")]
+    metadata_only: bool,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Zip,
+    Tar,
+    /// Tar compressed with zstd
+    TarZst,
+    /// Files under a directory
+    Dir,
+    /// SQLite database with an `entries` table indexed by path and content hash
+    Sqlite,
+}
+
+#[derive(Debug, Args)]
+struct FlattenCommand {
+    /// Game project name, file names are written into the TOC if provided
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Input PAK file path, or a directory of a base PAK and its patches
+    #[clap(short, long)]
+    input: String,
+    /// Write the merged TOC as JSON, with the source PAK of each entry and the PAKs it overrides
+    #[clap(long)]
+    toc: Option<String>,
+    /// Write the effective entries into a single PAK, copying their data as is
+    #[clap(short, long)]
+    output: Option<String>,
+    /// Encrypt the entry table of the output PAK
+    #[clap(long, default_value = "false")]
+    encrypt_toc: bool,
+    /// Override existing files
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct MakePatchCommand {
+    /// Base PAK file path, its existing patches next to it are applied first
+    #[clap(long)]
+    base: String,
+    /// Directory of modified files, laid out like unpacked files
+    #[clap(long)]
+    mod_dir: String,
+    /// Output PAK file path, defaults to the next patch number after the existing patches
+    #[clap(short, long)]
+    output: Option<String>,
+    /// File name list, input paths are matched case-insensitively
+    /// and hashed with the listed path
+    #[clap(long)]
+    list_file: Option<String>,
+    /// Compression of packed entries
+    #[clap(long, value_enum, default_value_t = PackCompression::None)]
+    compression: PackCompression,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExtractOneCommand {
+    /// Input PAK file path
+    #[clap(short, long)]
+    input: String,
+    /// Path of the file in the PAK, e.g. "natives/stm/..."
+    #[clap(long, required_unless_present = "hash")]
+    path: Option<String>,
+    /// Hash of the file in hexadecimal, e.g. "0x958EDD0C65B486A1"
+    #[clap(long, conflicts_with = "path")]
+    hash: Option<String>,
+    /// Output file path, defaults to the file name in the current directory
+    #[clap(short, long)]
+    output: Option<String>,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Args)]
+struct GenListCommand {
+    /// Extracted directory to collect candidate paths from
+    #[clap(short, long)]
+    input: String,
+    /// PAK file path, or a directory of PAK files, with the hashes to resolve
+    #[clap(long)]
+    pak: String,
+    /// Game project name, hashes resolved by its list are skipped
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Output list file path
+    #[clap(short, long)]
+    output: String,
+    /// Scan .pfb, .scn and .user files for embedded paths
+    #[clap(long, default_value = "false")]
+    scan: bool,
+    /// Override existing file
+    #[clap(long, default_value = "false")]
+    r#override: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PackCompression {
+    None,
+    Deflate,
+    Zstd,
 }
 
-fn main() -> anyhow::Result<()> {
+/// Ordinal index range of entries in TOC order.
+///
+/// Accepts `start..end`, `start..=end`, `start..`, `..end` or a single index.
+#[derive(Debug, Clone, Copy)]
+struct IndexRange {
+    start: Bound<usize>,
+    end: Bound<usize>,
+}
+
+impl IndexRange {
+    fn bounds(&self) -> (Bound<usize>, Bound<usize>) {
+        (self.start, self.end)
+    }
+
+    fn start_index(&self) -> usize {
+        match self.start {
+            Bound::Included(start) => start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        }
+    }
+}
+
+impl FromStr for IndexRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_index = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|e| format!("invalid index `{}`: {}", s, e))
+        };
+
+        let Some((start, end)) = s.split_once("..") else {
+            let index = parse_index(s)?;
+            return Ok(Self {
+                start: Bound::Included(index),
+                end: Bound::Included(index),
+            });
+        };
+        let start = if start.is_empty() {
+            Bound::Unbounded
+        } else {
+            Bound::Included(parse_index(start)?)
+        };
+        let end = if let Some(end) = end.strip_prefix('=') {
+            Bound::Included(parse_index(end)?)
+        } else if end.is_empty() {
+            Bound::Unbounded
+        } else {
+            Bound::Excluded(parse_index(end)?)
+        };
+
+        Ok(Self { start, end })
+    }
+}
+
+/// A byte count with an optional binary K, M or G suffix.
+#[derive(Debug, Clone, Copy)]
+struct ByteSize(u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, shift) = match s.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&s[..i], 10),
+            Some((i, 'm' | 'M')) => (&s[..i], 20),
+            Some((i, 'g' | 'G')) => (&s[..i], 30),
+            _ => (s, 0),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(1 << shift))
+            .map(Self)
+            .ok_or_else(|| format!("invalid size `{}`", s))
+    }
+}
+
+/// Size of a random sample of entries.
+#[derive(Debug, Clone, Copy)]
+enum SampleSize {
+    Percent(f64),
+    Count(usize),
+}
+
+impl SampleSize {
+    /// Number of entries to pick out of `total`, at least one for a non-zero percentage.
+    fn count(self, total: usize) -> usize {
+        match self {
+            SampleSize::Percent(percent) if percent > 0.0 => {
+                ((total as f64 * percent / 100.0).round() as usize).clamp(1, total.max(1))
+            }
+            SampleSize::Percent(_) => 0,
+            SampleSize::Count(count) => count,
+        }
+        .min(total)
+    }
+}
+
+impl FromStr for SampleSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(Self::Percent),
+            None => s.parse::<usize>().ok().map(Self::Count),
+        }
+        .ok_or_else(|| format!("invalid sample size `{}`, expected a percentage or a count", s))
+    }
+}
+
+/// Unix permission bits written in octal, with an optional `0o` prefix.
+#[derive(Debug, Clone, Copy)]
+struct FileMode(u32);
+
+impl FromStr for FileMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.trim().trim_start_matches("0o");
+        u32::from_str_radix(digits, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .map(Self)
+            .ok_or_else(|| format!("invalid octal mode `{}`", s))
+    }
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    concurrency::set_concurrency_config(
+        ConcurrencyConfig::default()
+            .with_max_threads(cli.threads)
+            .with_low_priority(cli.low_priority)
+            .with_max_open_files(cli.max_open_files)
+            .with_max_write_bytes_per_sec(cli.io_limit.map(|size| size.0))
+            .with_max_in_flight_bytes(cli.max_memory.map(|size| size.0)),
+    );
+    report::set_json(cli.json);
+    i18n::set_lang(cli.lang);
+    if let Err(e) = unpack::set_list_encoding(cli.list_encoding.as_deref()) {
+        return error::report_error(&e);
+    }
 
-    match &cli.command {
+    let result = match &cli.command {
         Command::Unpack(cmd) => unpack::unpack_parallel(cmd),
+        Command::List(cmd) => list::list(cmd),
+        Command::Doctor(cmd) => doctor::doctor(cmd),
+        Command::Convert(cmd) => convert::convert(cmd),
+        Command::Verify(cmd) => verify::verify(cmd),
+        Command::Pack(cmd) => pack::pack(cmd),
+        Command::ExtractOne(cmd) => extract_one::extract_one(cmd),
+        Command::GenList(cmd) => gen_list::gen_list(cmd),
+        Command::Info(cmd) => info::info(cmd),
+        Command::Diff(cmd) => diff::diff(cmd),
+        #[cfg(feature = "remote-lists")]
+        Command::UpdateLists(cmd) => update_lists::update_lists(cmd),
+        Command::WhichList(cmd) => which_list::which_list(cmd),
+        Command::MakePatch(cmd) => make_patch::make_patch(cmd),
+        Command::Export(cmd) => export::export(cmd),
+        Command::Flatten(cmd) => flatten::flatten(cmd),
+        Command::Search(cmd) => search::search(cmd),
+        Command::Bundle(cmd) => bundle::bundle(cmd),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error::report_error(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_range() {
+        let range = |s: &str| IndexRange::from_str(s).unwrap().bounds();
+        assert_eq!(range("3"), (Bound::Included(3), Bound::Included(3)));
+        assert_eq!(range("2..5"), (Bound::Included(2), Bound::Excluded(5)));
+        assert_eq!(range("2..=5"), (Bound::Included(2), Bound::Included(5)));
+        assert_eq!(range("7.."), (Bound::Included(7), Bound::Unbounded));
+        assert_eq!(range("..4"), (Bound::Unbounded, Bound::Excluded(4)));
+        assert_eq!(IndexRange::from_str("..4").unwrap().start_index(), 0);
+        for invalid in ["", "a", "1..b", "-1..2"] {
+            assert!(IndexRange::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_byte_size() {
+        let size = |s: &str| ByteSize::from_str(s).map(|size| size.0);
+        assert_eq!(size("512"), Ok(512));
+        assert_eq!(size("64k"), Ok(64 << 10));
+        assert_eq!(size(" 8M "), Ok(8 << 20));
+        assert_eq!(size("2G"), Ok(2 << 30));
+        for invalid in ["", "G", "1.5M", "-1", "99999999999G"] {
+            assert!(size(invalid).is_err(), "{}", invalid);
+        }
     }
 }
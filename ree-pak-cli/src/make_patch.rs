@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use ree_pak_core::{
+    compare,
+    read::multi::{find_patches, MultiPak, MultiPakEntry},
+    write::{FileOptions, PakWriter},
+};
+
+use crate::{
+    pack::{collect_inputs, PackInput},
+    unpack::load_list_file,
+    MakePatchCommand,
+};
+
+pub fn make_patch(cmd: &MakePatchCommand) -> anyhow::Result<()> {
+    let base_path = Path::new(&cmd.base);
+    if !base_path.is_file() {
+        anyhow::bail!("Base file `{}` not found.", &cmd.base);
+    }
+    let mod_dir = Path::new(&cmd.mod_dir);
+    if !mod_dir.is_dir() {
+        anyhow::bail!("Mod directory `{}` not found.", &cmd.mod_dir);
+    }
+    let file_name_table = match &cmd.list_file {
+        Some(list_file) => {
+            Some(load_list_file(list_file).context(format!("Failed to load list file `{}`.", list_file))?)
+        }
+        None => None,
+    };
+
+    // the mod is compared against the base with its existing patches applied
+    let (pak_paths, next_patch) = find_patches(base_path)?;
+    let multi_pak = MultiPak::open(&pak_paths)?;
+    let effective: HashMap<u64, &MultiPakEntry> = multi_pak
+        .entries()
+        .iter()
+        .map(|item| (item.entry.hash(), item))
+        .collect();
+    let mut readers = (0..multi_pak.paks().len())
+        .map(|pak_index| multi_pak.open_reader(pak_index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let inputs = collect_inputs(mod_dir, file_name_table.as_ref())?;
+    let mut changed: Vec<&PackInput> = vec![];
+    for input in &inputs {
+        let status = match effective.get(&input.hash) {
+            Some(item) => {
+                let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.path))?;
+                let size = file.metadata()?.len();
+                let reader = &mut readers[item.pak_index];
+                if compare::eq_data(reader, &item.entry, BufReader::new(file), size)? {
+                    continue;
+                }
+                'M'
+            }
+            None => 'A',
+        };
+        println!("{}\t{}", status, input.path);
+        changed.push(input);
+    }
+    let unchanged = inputs.len() - changed.len();
+    if changed.is_empty() {
+        println!("No file differs from `{}`, no patch written.", &cmd.base);
+        return Ok(());
+    }
+
+    let output_path = match &cmd.output {
+        Some(output) => PathBuf::from(output),
+        None => patch_path(base_path, next_patch),
+    };
+    let output = if cmd.r#override {
+        File::create(&output_path)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&output_path)
+    }
+    .context(format!("Failed to create output file `{}`.", output_path.display()))?;
+    let options = FileOptions::default().with_compression(cmd.compression.into());
+    let mut writer = PakWriter::new(BufWriter::new(output), changed.len() as u32)?;
+    for input in &changed {
+        let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.path))?;
+        writer.start_file_hash(input.hash, options)?;
+        io::copy(&mut BufReader::new(file), &mut writer).context(format!("Failed to pack `{}`.", input.path))?;
+    }
+    writer.finish()?.flush()?;
+
+    println!(
+        "Packed {} changed files into `{}`, skipped {} unchanged files.",
+        changed.len(),
+        output_path.display(),
+        unchanged
+    );
+
+    Ok(())
+}
+
+/// Path of a patch of the base pak, e.g. `re_chunk_000.pak.patch_003.pak`.
+fn patch_path(base_path: &Path, number: u32) -> PathBuf {
+    let mut file_name = base_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".patch_{:03}.pak", number));
+    base_path.with_file_name(file_name)
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use indicatif::{HumanBytes, ProgressStyle};
+use ree_pak_core::{
+    filename::{FileName, FileNameTable},
+    metadata::PakMetadata,
+    pak::CompressionMethod,
+    write::{
+        CompressionHeuristic, CompressionReason, FileOptions, PackManifest, PakOptions, PakWriter, SizeEstimate,
+        WriteEvent, FAT32_MAX_FILE_SIZE,
+    },
+};
+
+use serde_json::json;
+
+use crate::{
+    error::{check_output_file, failure, Failure},
+    i18n::{tr, Msg},
+    report,
+    unpack::load_list_file,
+    PackCommand, PackCompression,
+};
+
+/// A file to pack, collected from an input directory or a manifest.
+pub(crate) struct PackInput {
+    /// Path in the pak, with `/` separators.
+    pub(crate) path: String,
+    pub(crate) hash: u64,
+    /// File holding the content.
+    pub(crate) source: PathBuf,
+    /// Compression overriding the one of the pak.
+    pub(crate) compression: Option<CompressionMethod>,
+}
+
+pub fn pack(cmd: &PackCommand) -> anyhow::Result<()> {
+    for input in &cmd.input {
+        if !Path::new(input).is_dir() {
+            return Err(failure(
+                Failure::InputMissing,
+                format!("Input directory `{}` not found.", input),
+            ));
+        }
+    }
+    let file_name_table = match &cmd.list_file {
+        Some(list_file) => {
+            Some(load_list_file(list_file).context(format!("Failed to load list file `{}`.", list_file))?)
+        }
+        None => None,
+    };
+
+    let mut inputs = collect_pack_inputs(cmd, file_name_table.as_ref())?;
+    if cmd.deterministic {
+        inputs.sort_by_key(|input| input.hash);
+    }
+    let heuristic = load_compression_heuristic(cmd)?;
+    let compression_stats = choose_compression(&mut inputs, &heuristic, cmd.compression.into())?;
+    let options = FileOptions::default().with_compression(cmd.compression.into());
+    let metadata = pack_metadata(cmd);
+    let pak_options = PakOptions::default()
+        .with_encrypted_toc(cmd.encrypt_toc)
+        .with_deterministic(cmd.deterministic);
+
+    let estimate = estimate_size(&inputs, metadata.as_ref(), options, pak_options)?;
+    report::emit(json!({
+        "event": "estimate",
+        "files": estimate.files(),
+        "uncompressed_size": estimate.uncompressed_size(),
+        "estimated_size": estimate.estimated_size(),
+    }));
+    report::message(tr!(Msg::PackPredictedSize, estimate.estimated_size()));
+    if estimate.exceeds(FAT32_MAX_FILE_SIZE) {
+        eprintln!("{}", tr!(Msg::PackFat32Warning));
+    }
+
+    check_output_file(Path::new(&cmd.output))?;
+    let output = if cmd.r#override {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&cmd.output)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(&cmd.output)
+    }
+    .context(format!("Failed to create output file `{}`.", &cmd.output))?;
+    let total_files = inputs.len() + metadata.is_some() as usize;
+    // progress is counted in input bytes, so large files advance while being compressed
+    let bar = report::progress_bar(estimate.uncompressed_size());
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::PackProgress))?);
+    bar.set_message(tr!(Msg::PackProgressPrediction, HumanBytes(estimate.estimated_size())));
+    bar.enable_steady_tick(Duration::from_millis(100));
+    let event_bar = bar.clone();
+    let total_size = Arc::new(AtomicU64::new(0));
+    let event_total_size = total_size.clone();
+    let mut done_files = 0;
+    let mut writer =
+        PakWriter::with_options(BufWriter::new(output), total_files as u32, pak_options)?.on_event(move |event| {
+            match event {
+                WriteEvent::FileDone {
+                    hash,
+                    compressed_size,
+                    uncompressed_size,
+                } => {
+                    done_files += 1;
+                    report::emit(json!({
+                        "event": "packed",
+                        "hash": report::hash_string(*hash),
+                        "compressed_size": compressed_size,
+                        "uncompressed_size": uncompressed_size,
+                        "done": done_files,
+                        "total": total_files,
+                    }));
+                }
+                WriteEvent::Finish { total_size, .. } => {
+                    event_total_size.store(*total_size, Ordering::Relaxed);
+                    event_bar.finish();
+                }
+                _ => {}
+            }
+        });
+    if let Some(metadata) = &metadata {
+        writer.write_metadata(metadata)?;
+        bar.inc(metadata.to_json()?.len() as u64);
+    }
+    for input in &inputs {
+        let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.source.display()))?;
+        writer.start_file_hash(input.hash, input.options(options))?;
+        io::copy(&mut bar.wrap_read(BufReader::new(file)), &mut writer)
+            .context(format!("Failed to pack `{}`.", input.path))?;
+    }
+    writer.finish()?.flush()?;
+
+    let total_size = total_size.load(Ordering::Relaxed);
+    report::emit(json!({
+        "event": "report",
+        "packed": inputs.len(),
+        "output": &cmd.output,
+        "size": total_size,
+        "compression": {
+            "compressed": compression_stats.compressed,
+            "incompressible": compression_stats.incompressible,
+            "by_extension": compression_stats.by_extension,
+        },
+    }));
+    report::message(tr!(Msg::PackDone, inputs.len(), &cmd.output, total_size));
+    if compression_stats.compressed + compression_stats.incompressible + compression_stats.by_extension > 0 {
+        report::message(tr!(
+            Msg::PackCompressionStats,
+            compression_stats.compressed,
+            compression_stats.incompressible,
+            compression_stats.by_extension
+        ));
+    }
+
+    Ok(())
+}
+
+/// Number of inputs by the reason of their compression.
+#[derive(Debug, Default)]
+struct CompressionStats {
+    compressed: usize,
+    incompressible: usize,
+    by_extension: usize,
+}
+
+fn load_compression_heuristic(cmd: &PackCommand) -> anyhow::Result<CompressionHeuristic> {
+    let heuristic = match &cmd.compression_config {
+        Some(path) => {
+            let data = std::fs::read(path).context(format!("Failed to read compression config `{}`.", path))?;
+            CompressionHeuristic::from_json(&data)?
+        }
+        None => CompressionHeuristic::new(),
+    };
+
+    if cmd.compress_all {
+        Ok(heuristic.with_max_ratio(f64::INFINITY))
+    } else {
+        Ok(heuristic)
+    }
+}
+
+/// Set the compression of inputs without one, storing files that do not compress well.
+fn choose_compression(
+    inputs: &mut [PackInput],
+    heuristic: &CompressionHeuristic,
+    compression: CompressionMethod,
+) -> anyhow::Result<CompressionStats> {
+    let mut stats = CompressionStats::default();
+    for input in inputs.iter_mut().filter(|input| input.compression.is_none()) {
+        let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.source.display()))?;
+        let (chosen, reason) = heuristic.choose(&input.path, BufReader::new(file), compression)?;
+        match reason {
+            CompressionReason::Extension => stats.by_extension += 1,
+            CompressionReason::Compressible => stats.compressed += 1,
+            CompressionReason::Incompressible => stats.incompressible += 1,
+            CompressionReason::NotRequested | CompressionReason::Empty => {}
+        }
+        input.compression = Some(chosen);
+    }
+
+    Ok(stats)
+}
+
+/// Predict the size of the pak by compressing a sample of each input.
+fn estimate_size(
+    inputs: &[PackInput],
+    metadata: Option<&PakMetadata>,
+    options: FileOptions,
+    pak_options: PakOptions,
+) -> anyhow::Result<SizeEstimate> {
+    let mut estimate = SizeEstimate::new(pak_options);
+    if let Some(metadata) = metadata {
+        let data = metadata.to_json()?;
+        estimate.add_file(data.as_slice(), data.len() as u64, FileOptions::default())?;
+    }
+    for input in inputs {
+        let file = File::open(&input.source).context(format!("Failed to open `{}`.", input.source.display()))?;
+        let size = file.metadata()?.len();
+        estimate.add_file(BufReader::new(file), size, input.options(options))?;
+    }
+
+    Ok(estimate)
+}
+
+/// Collect the files of all input directories and the manifest.
+///
+/// Later inputs take priority over earlier ones with the same hash, the manifest
+/// over all directories. Replaced files keep the position of the first one.
+fn collect_pack_inputs(cmd: &PackCommand, file_name_table: Option<&FileNameTable>) -> anyhow::Result<Vec<PackInput>> {
+    let mut inputs: Vec<PackInput> = vec![];
+    let mut positions: HashMap<u64, usize> = HashMap::new();
+    let mut add = |input: PackInput| match positions.get(&input.hash) {
+        Some(&pos) => inputs[pos] = input,
+        None => {
+            positions.insert(input.hash, inputs.len());
+            inputs.push(input);
+        }
+    };
+
+    for input_dir in &cmd.input {
+        collect_inputs(Path::new(input_dir), file_name_table)?
+            .into_iter()
+            .for_each(&mut add);
+    }
+    if let Some(manifest_path) = &cmd.manifest {
+        let manifest =
+            PackManifest::load(manifest_path).context(format!("Failed to load manifest `{}`.", manifest_path))?;
+        for file in manifest.files {
+            let path = file.path.replace('\\', "/");
+            add(PackInput {
+                hash: input_hash(&path, file_name_table),
+                path,
+                source: file.source,
+                compression: file.compression,
+            });
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// Collect files of the input directory in a stable order and compute their hashes.
+///
+/// With a list, paths are matched case-insensitively against it and hashed with the
+/// canonical path. Files under `_Unknown` keep the hash in their file name.
+pub(crate) fn collect_inputs(
+    input_dir: &Path,
+    file_name_table: Option<&FileNameTable>,
+) -> anyhow::Result<Vec<PackInput>> {
+    let mut inputs = vec![];
+    // links are followed, e.g. files deduplicated by `unpack --dedup symlink`
+    for dir_entry in walkdir::WalkDir::new(input_dir).follow_links(true).sort_by_file_name() {
+        let dir_entry = dir_entry?;
+        if !dir_entry.file_type().is_file() {
+            continue;
+        }
+        let relative = dir_entry.path().strip_prefix(input_dir)?;
+        let path = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        inputs.push(PackInput {
+            hash: input_hash(&path, file_name_table),
+            path,
+            source: dir_entry.into_path(),
+            compression: None,
+        });
+    }
+
+    Ok(inputs)
+}
+
+/// Hash of a pak path, files under `_Unknown` keep the hash in their file name.
+///
+/// With a list, the path is matched case-insensitively against it and hashed
+/// with the canonical path.
+fn input_hash(path: &str, file_name_table: Option<&FileNameTable>) -> u64 {
+    if let Some(hash) = unknown_hash(path) {
+        return hash;
+    }
+
+    let hash = FileName::new(path).hash_mixed();
+    match file_name_table {
+        Some(table) => match table.get_file_name(hash) {
+            Some(canonical) => FileName::new(canonical).hash_mixed(),
+            None => {
+                eprintln!("Warning: `{}` is not in the list file.", path);
+                hash
+            }
+        },
+        None => hash,
+    }
+}
+
+impl PackInput {
+    /// Options of the entry, the compression of the input overrides the one of `options`.
+    fn options(&self, options: FileOptions) -> FileOptions {
+        match self.compression {
+            Some(compression) => options.with_compression(compression),
+            None => options,
+        }
+    }
+}
+
+/// Metadata entry to embed, only if any mod information is given.
+fn pack_metadata(cmd: &PackCommand) -> Option<PakMetadata> {
+    if cmd.mod_name.is_none() && cmd.author.is_none() && cmd.mod_version.is_none() {
+        return None;
+    }
+
+    let build_timestamp = if cmd.deterministic {
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.parse().ok())
+    } else {
+        Some(ree_pak_core::env::unix_time())
+    };
+    Some(PakMetadata {
+        name: cmd.mod_name.clone(),
+        author: cmd.author.clone(),
+        version: cmd.mod_version.clone(),
+        build_timestamp,
+        tool_version: Some(format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+    })
+}
+
+/// Get the hash of an unpacked unknown entry, e.g. `_Unknown/0123456789ABCDEF.tex`.
+fn unknown_hash(path: &str) -> Option<u64> {
+    let file_name = path.strip_prefix("_Unknown/")?;
+    let stem = file_name.split('.').next()?;
+
+    u64::from_str_radix(stem, 16).ok()
+}
+
+impl From<PackCompression> for CompressionMethod {
+    fn from(value: PackCompression) -> Self {
+        match value {
+            PackCompression::None => CompressionMethod::None,
+            PackCompression::Deflate => CompressionMethod::Deflate,
+            PackCompression::Zstd => CompressionMethod::Zstd,
+        }
+    }
+}
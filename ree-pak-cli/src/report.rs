@@ -0,0 +1,54 @@
+//! Machine-readable output, enabled with the global `--json` flag.
+//!
+//! Each event is one JSON object per line on stdout, with an `event` field naming it.
+//! Progress bars are hidden and human-readable messages go to stderr instead.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use indicatif::ProgressBar;
+use serde_json::Value;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Write an event line, only in JSON mode.
+pub(crate) fn emit(event: Value) {
+    if is_json() {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", event);
+    }
+}
+
+/// Print a message for humans, kept off stdout in JSON mode.
+pub(crate) fn message<T: Display>(message: T) {
+    if is_json() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// A progress bar of `len` steps, hidden in JSON mode.
+pub(crate) fn progress_bar(len: u64) -> ProgressBar {
+    if is_json() {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(len)
+    }
+}
+
+/// Format a hash the same way as other JSON output.
+pub(crate) fn hash_string(hash: u64) -> String {
+    format!("{:016X}", hash)
+}
@@ -0,0 +1,145 @@
+use std::{io::Read, time::Duration};
+
+use anyhow::Context;
+use indicatif::ProgressStyle;
+use rayon::iter::IntoParallelRefIterator;
+use ree_pak_core::{
+    concurrency,
+    filename::FileNameTable,
+    read::{io::entry::PakEntryReader, multi::MultiPakEntry},
+};
+use regex::bytes::Regex;
+use serde_json::json;
+
+use crate::{
+    i18n::{tr, Msg},
+    report,
+    unpack::{extract_entries, load_filename_table, open_multi_pak, resolve_path, PakReaders},
+    SearchCommand,
+};
+
+/// Offsets reported per file, the count of matches is always complete.
+const MAX_REPORTED_OFFSETS: usize = 100;
+
+pub fn search(cmd: &SearchCommand) -> anyhow::Result<()> {
+    let pattern = Regex::new(&cmd.pattern).context("Invalid search pattern")?;
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+    let multi_pak = open_multi_pak(&cmd.input)?;
+    let entries: Vec<&MultiPakEntry> = multi_pak
+        .entries()
+        .iter()
+        .filter(|item| cmd.ext.is_empty() || has_extension(&resolve_path(&item.entry, &file_name_table), &cmd.ext))
+        .collect();
+
+    let bar = report::progress_bar(entries.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::SearchProgress))?);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    let process = |pak_readers: &mut PakReaders, item: &&MultiPakEntry| -> anyhow::Result<bool> {
+        let path = resolve_path(&item.entry, &file_name_table);
+        let result = pak_readers.get(item.pak_index).and_then(|pak_reader| {
+            // matches may span the whole entry, so it is decoded into memory
+            let _permit = concurrency::acquire_memory(item.entry.uncompressed_size());
+            // the size is not trusted, the buffer grows with the decoded data
+            let mut data = vec![];
+            PakEntryReader::new_streaming(pak_reader, &item.entry)?.read_to_end(&mut data)?;
+            Ok(find_matches(&pattern, &data, cmd.utf16))
+        });
+        bar.inc(1);
+        match result {
+            Ok(offsets) if offsets.is_empty() => Ok(false),
+            Ok(offsets) => {
+                let reported = &offsets[..offsets.len().min(MAX_REPORTED_OFFSETS)];
+                report::emit(json!({
+                    "event": "match",
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": path,
+                    "count": offsets.len(),
+                    "offsets": reported,
+                }));
+                if !report::is_json() {
+                    let mut offsets_text = reported
+                        .iter()
+                        .map(|offset| format!("{:#X}", offset))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    if offsets.len() > reported.len() {
+                        offsets_text.push_str(",...");
+                    }
+                    bar.suspend(|| println!("{}\t{}\t{}", path, offsets.len(), offsets_text));
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                report::emit(json!({
+                    "event": "failed",
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": path,
+                    "error": format!("{:#}", e),
+                }));
+                if !report::is_json() {
+                    bar.suspend(|| eprintln!("FAIL\t{}\t{:#}", path, e));
+                }
+                Err(e)
+            }
+        }
+    };
+    let (matched, failed) = concurrency::install(|| extract_entries(entries.par_iter(), &multi_pak, process, true))?;
+    bar.finish();
+
+    let matched = matched.into_iter().filter(|matched| *matched).count();
+    report::emit(json!({
+        "event": "report",
+        "searched": entries.len(),
+        "matched": matched,
+        "failed": failed,
+    }));
+    report::message(tr!(Msg::SearchDone, matched, entries.len()));
+    if failed > 0 {
+        report::message(tr!(Msg::DoneWithErrors, failed));
+    }
+
+    Ok(())
+}
+
+/// Whether a file name has one of the extensions, ignoring case, e.g. `msg` for `text.msg.23`.
+fn has_extension(path: &str, extensions: &[String]) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    file_name
+        .split('.')
+        .skip(1)
+        .any(|part| extensions.iter().any(|ext| part.eq_ignore_ascii_case(ext)))
+}
+
+/// Byte offsets of the matches in the data.
+///
+/// With `utf16` the data is matched as UTF-16LE text aligned to two bytes, unpaired
+/// surrogates are replaced, and offsets still point into the original data.
+fn find_matches(pattern: &Regex, data: &[u8], utf16: bool) -> Vec<u64> {
+    if !utf16 {
+        return pattern.find_iter(data).map(|m| m.start() as u64).collect();
+    }
+
+    let units = data.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    let mut text = String::with_capacity(data.len() / 2);
+    // offset in the data of each char of the text, by char start in the text
+    let mut char_offsets = vec![];
+    let mut unit_index = 0;
+    for c in char::decode_utf16(units) {
+        let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+        char_offsets.push((text.len(), unit_index * 2));
+        // an unpaired surrogate is one unit, like its replacement
+        unit_index += c.len_utf16();
+        text.push(c);
+    }
+
+    pattern
+        .find_iter(text.as_bytes())
+        .map(|m| {
+            let i = char_offsets.partition_point(|(text_offset, _)| *text_offset < m.start());
+            char_offsets.get(i).map_or(data.len(), |(_, offset)| *offset) as u64
+        })
+        .collect()
+}
@@ -0,0 +1,73 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use ree_pak_core::{error::Result, export::ExportSink};
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+const SCHEMA: &str = "
+CREATE TABLE entries (
+    path TEXT NOT NULL PRIMARY KEY,
+    size INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    data BLOB
+);
+CREATE INDEX entries_sha256 ON entries (sha256);
+";
+
+/// Writes exported files as rows of an `entries` table.
+///
+/// Each row has the path, size and SHA-256 of the content, and the content
+/// itself unless only metadata is kept. All rows are inserted in a single
+/// transaction committed by [`SqliteExporter::finish`].
+pub(crate) struct SqliteExporter {
+    conn: Connection,
+    with_data: bool,
+}
+
+impl SqliteExporter {
+    /// Create the database at `path`, which must not exist.
+    pub(crate) fn create(path: &Path, with_data: bool) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute_batch("BEGIN")?;
+
+        Ok(Self { conn, with_data })
+    }
+
+    pub(crate) fn finish(self) -> anyhow::Result<()> {
+        self.conn.execute_batch("COMMIT")?;
+        self.conn.close().map_err(|(_, e)| e)?;
+
+        Ok(())
+    }
+}
+
+impl ExportSink for SqliteExporter {
+    fn add_file(&mut self, name: &str, size: u64, data: &mut dyn Read) -> Result<()> {
+        let mut hasher = Sha256::new();
+        let content = if self.with_data {
+            let mut content = vec![];
+            data.read_to_end(&mut content)?;
+            hasher.update(&content);
+            Some(content)
+        } else {
+            io::copy(data, &mut hasher)?;
+            None
+        };
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        self.conn
+            .prepare_cached("INSERT INTO entries (path, size, sha256, data) VALUES (?1, ?2, ?3, ?4)")
+            .and_then(|mut statement| statement.execute(params![name, size as i64, digest, content]))
+            .map_err(io::Error::other)?;
+
+        Ok(())
+    }
+}
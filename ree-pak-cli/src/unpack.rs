@@ -1,25 +1,64 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{File, OpenOptions},
-    io::BufReader,
+    io::{BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
-    sync::Mutex,
+    sync::{Mutex, OnceLock, RwLock},
     time::Duration,
 };
 
 use anyhow::Context;
-use indicatif::{ProgressBar, ProgressStyle};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use ree_pak_core::{filename::FileNameTable, pak::PakEntry, read::io::archive::PakArchiveReader};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use ree_pak_core::{
+    concurrency::{self, ThrottledWriter},
+    content_hash::{content_digest, to_hex, ContentDigest, ContentManifest, HashingReader},
+    env::{self, splitmix64},
+    filename::{
+        available_projects, is_invalid_name_char, sanitize_path, shorten_path, FileNameTable, ListOptions,
+        LIST_SUFFIXES,
+    },
+    filter::{PakIgnore, PathFilter, PAK_IGNORE_FILE_NAME},
+    pak::PakEntry,
+    permissions::OutputPermissions,
+    progress::ExtractProgress,
+    read::{
+        io::{
+            entry::{DecodeStats, PakEntryReader},
+            part::PartReader,
+        },
+        multi::{find_patch_chain, patch_base_name, MultiPak, MultiPakEntry},
+        order::ExtractOrder,
+        source::SourceReader,
+    },
+};
 
-use crate::UnpackCommand;
+use serde_json::{json, Value};
 
-pub fn unpack_parallel(cmd: &UnpackCommand) -> anyhow::Result<()> {
-    if cmd.ignore_error {
-        unpack_parallel_error_continue(cmd)
-    } else {
-        unpack_parallel_error_terminate(cmd)
-    }
-}
+use crate::{
+    dedup::{Dedup, DigestReader},
+    error::{check_output_file, classify_as, failure, Failure},
+    i18n::{tr, Msg},
+    info::codec_name,
+    report, which_list, DedupMode, UnpackCommand, UnpackOrder,
+};
+
+pub(crate) const FLAT_MAPPING_FILE_NAME: &str = "_FlatMapping.txt";
+pub(crate) const SHORTENED_PATHS_FILE_NAME: &str = "_ShortenedPaths.txt";
+
+/// Default limit of output path lengths, Windows allows long paths through `\\?\` prefixes.
+#[cfg(windows)]
+const DEFAULT_MAX_PATH_LEN: usize = 32767;
+#[cfg(not(windows))]
+const DEFAULT_MAX_PATH_LEN: usize = 4096;
+/// Room left in the path budget for an extension guessed after writing.
+const GUESSED_EXTENSION_LEN: usize = 8;
+/// Entries checked against the project list before extracting.
+const LIST_CHECK_SAMPLE_SIZE: usize = 256;
+/// Entries listed by decode time in the JSON report.
+const SLOWEST_ENTRY_COUNT: usize = 10;
+/// Mode of files in tar output without `--file-mode`.
+const DEFAULT_TAR_MODE: u32 = 0o644;
 
 fn output_path<P: AsRef<Path>>(output: &Option<String>, input: P) -> PathBuf {
     if let Some(output) = &output {
@@ -27,11 +66,14 @@ fn output_path<P: AsRef<Path>>(output: &Option<String>, input: P) -> PathBuf {
         output.into()
     } else if let Some(parent) = input.as_ref().parent() {
         // relative to input directory
-        let dir_name = input
+        let mut dir_name = input
             .as_ref()
             .file_stem()
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or("output".to_string());
+        if input.as_ref().is_dir() {
+            dir_name.push_str("_unpacked");
+        }
         parent.join(dir_name).to_string_lossy().to_string().into()
     } else {
         // current directory
@@ -39,158 +81,1018 @@ fn output_path<P: AsRef<Path>>(output: &Option<String>, input: P) -> PathBuf {
     }
 }
 
-fn load_filename_table(project_name: &str) -> anyhow::Result<FileNameTable> {
-    let path_str_relative = format!("assets/filelist/{}.list", project_name);
-    let path_relative = Path::new(&path_str_relative);
-    let path_abs = std::env::current_exe()?.parent().unwrap().join(path_relative);
-    if !path_abs.exists() || !path_abs.is_file() {
-        anyhow::bail!(
-            "Project file `{}` not found, check your project name.",
-            path_abs.display()
+/// Directory of project file name lists, relative to the executable.
+pub(crate) fn filelist_dir() -> anyhow::Result<PathBuf> {
+    Ok(std::env::current_exe()?.parent().unwrap().join("assets/filelist"))
+}
+
+/// Options for reading list files, set once from the command line.
+static LIST_OPTIONS: OnceLock<ListOptions> = OnceLock::new();
+
+pub(crate) fn set_list_encoding(label: Option<&str>) -> anyhow::Result<()> {
+    let mut options = ListOptions::default();
+    if let Some(label) = label {
+        options = options
+            .with_fallback_encoding_label(label)
+            .context(format!("Unknown list encoding `{}`.", label))?;
+    }
+    LIST_OPTIONS.set(options).ok();
+
+    Ok(())
+}
+
+/// Load a list file, `.zst` lists are decompressed first and not cached.
+pub(crate) fn load_list_file<P: AsRef<Path>>(path: P) -> anyhow::Result<FileNameTable> {
+    let path = path.as_ref();
+    let options = LIST_OPTIONS.get().copied().unwrap_or_default();
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        #[cfg(feature = "zstd")]
+        {
+            let list = zstd::decode_all(File::open(path)?).context("Failed to decompress list file")?;
+            return FileNameTable::from_list_bytes_with_options(list, options)
+                .context("Failed to load file name table");
+        }
+        #[cfg(not(feature = "zstd"))]
+        anyhow::bail!("Compressed list files need a build with the `zstd` feature.");
+    }
+
+    FileNameTable::from_list_file_cached(path, options).context("Failed to load file name table")
+}
+
+pub(crate) fn load_filename_table(project_name: &str) -> anyhow::Result<FileNameTable> {
+    let dir = filelist_dir()?;
+    let path_abs = dir.join(format!("{}.list", project_name));
+    // fall back to a compressed list of the project
+    let path_abs = LIST_SUFFIXES
+        .iter()
+        .map(|suffix| dir.join(format!("{}{}", project_name, suffix)))
+        .find(|path| path.is_file())
+        .unwrap_or(path_abs);
+    if !path_abs.is_file() {
+        let projects = available_projects([&dir]).unwrap_or_default();
+        if projects.is_empty() {
+            return Err(failure(
+                Failure::BadList,
+                tr!(Msg::ProjectListMissing, path_abs.display(), dir.display()),
+            ));
+        }
+        let names: Vec<&str> = projects.iter().map(|list| list.project.as_str()).collect();
+        return Err(failure(
+            Failure::BadList,
+            tr!(Msg::ProjectListNotFound, path_abs.display(), names.join(", ")),
+        ));
+    }
+
+    let table = load_list_file(&path_abs)
+        .map_err(|e| classify_as(e, Failure::BadList, format!("Failed to load `{}`.", path_abs.display())))?;
+    for invalid in table.invalid_names() {
+        eprintln!(
+            "Warning: list line {} is not valid UTF-8, loaded lossily: {:02X?}",
+            invalid.line, invalid.bytes
         );
     }
 
-    FileNameTable::from_list_file(path_abs).context("Failed to load file name table")
+    Ok(table)
+}
+
+fn selected_entries<'a>(multi_pak: &'a MultiPak, cmd: &UnpackCommand) -> anyhow::Result<&'a [MultiPakEntry]> {
+    match &cmd.index {
+        Some(range) => multi_pak
+            .entries_in_range(range.bounds())
+            .context("Index range is out of bounds"),
+        None => Ok(multi_pak.entries()),
+    }
+}
+
+/// Why a selected entry was left out of the output.
+#[derive(Debug, Clone, Copy)]
+enum SkipReason {
+    /// Excluded by the ignore file.
+    IgnoreFile,
+    /// Not matched by `--include` or matched by `--exclude`.
+    Filter,
+    /// Not picked by `--sample`.
+    Sample,
+    /// The same as in the previous TOC of `--changed-since`.
+    Unchanged,
+}
+
+impl SkipReason {
+    fn name(self) -> &'static str {
+        match self {
+            SkipReason::IgnoreFile => "ignore_file",
+            SkipReason::Filter => "filter",
+            SkipReason::Sample => "sample",
+            SkipReason::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// Keep the entries that pass `keep` with their resolved path, emitting a skipped event for the others.
+fn skip_entries<F>(
+    entries: &mut Vec<&MultiPakEntry>,
+    file_name_table: &FileNameTable,
+    reason: SkipReason,
+    skipped: &mut BTreeMap<&'static str, usize>,
+    mut keep: F,
+) where
+    F: FnMut(&MultiPakEntry, &str) -> bool,
+{
+    entries.retain(|item| {
+        let path = resolve_path(&item.entry, file_name_table);
+        if keep(item, &path) {
+            return true;
+        }
+        *skipped.entry(reason.name()).or_default() += 1;
+        report::emit(json!({
+            "event": "skipped",
+            "hash": report::hash_string(item.entry.hash()),
+            "path": path,
+            "reason": reason.name(),
+        }));
+        false
+    });
+}
+
+/// An entry of a TOC written by `flatten --toc`.
+struct PreviousEntry {
+    compressed_size: u64,
+    uncompressed_size: u64,
+    /// Missing in TOCs written before checksums were included.
+    checksum: Option<u64>,
+}
+
+impl PreviousEntry {
+    fn matches(&self, entry: &PakEntry) -> bool {
+        self.compressed_size == entry.compressed_size()
+            && self.uncompressed_size == entry.uncompressed_size()
+            && self.checksum.is_none_or(|checksum| checksum == entry.checksum())
+    }
+}
+
+/// Load the entries of a previous TOC by hash.
+fn load_previous_toc(path: &str) -> anyhow::Result<HashMap<u64, PreviousEntry>> {
+    let toc: Value = serde_json::from_reader(BufReader::new(
+        File::open(path).context(format!("Previous TOC `{}` not found.", path))?,
+    ))
+    .context(format!("Failed to parse previous TOC `{}`", path))?;
+    let entries = toc["entries"]
+        .as_array()
+        .context(format!("Previous TOC `{}` has no entries array", path))?;
+    let hex = |value: &Value| value.as_str().and_then(|s| u64::from_str_radix(s, 16).ok());
+
+    entries
+        .iter()
+        .map(|entry| {
+            let (Some(hash), Some(compressed_size), Some(uncompressed_size)) = (
+                hex(&entry["hash"]),
+                entry["compressed_size"].as_u64(),
+                entry["uncompressed_size"].as_u64(),
+            ) else {
+                anyhow::bail!("Invalid entry in previous TOC `{}`: {}", path, entry);
+            };
+            let previous = PreviousEntry {
+                compressed_size,
+                uncompressed_size,
+                checksum: hex(&entry["checksum"]),
+            };
+            Ok((hash, previous))
+        })
+        .collect()
+}
+
+/// Pick `count` of `total` positions at random, the same seed picks the same positions.
+fn sample_mask(total: usize, count: usize, seed: u64) -> Vec<bool> {
+    // partial Fisher-Yates shuffle driven by splitmix64
+    let mut state = seed;
+    let mut positions: Vec<usize> = (0..total).collect();
+    let mut mask = vec![false; total];
+    for i in 0..count.min(total) {
+        let j = i + (splitmix64(&mut state) % (total - i) as u64) as usize;
+        positions.swap(i, j);
+        mask[positions[i]] = true;
+    }
+
+    mask
+}
+
+fn random_seed() -> u64 {
+    env::env().random_u64()
+}
+
+/// Get the path of an entry relative to the output directory, before extension detection.
+pub(crate) fn resolve_path(entry: &PakEntry, file_name_table: &FileNameTable) -> String {
+    file_name_table
+        .get_file_name(entry.hash())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("_Unknown/{:08X}", entry.hash()))
+}
+
+fn load_path_filter(cmd: &UnpackCommand) -> anyhow::Result<Option<PathFilter>> {
+    let mut builder = PathFilter::builder();
+    for pattern in &cmd.include {
+        builder = builder.include_glob(pattern)?;
+    }
+    for pattern in &cmd.exclude {
+        builder = builder.exclude_glob(pattern)?;
+    }
+    if builder.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(builder.build()?))
+}
+
+fn load_pak_ignore(cmd: &UnpackCommand) -> anyhow::Result<Option<PakIgnore>> {
+    let path = match &cmd.ignore_file {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let path = PathBuf::from(PAK_IGNORE_FILE_NAME);
+            if !path.is_file() {
+                return Ok(None);
+            }
+            path
+        }
+    };
+
+    let pak_ignore = PakIgnore::from_file(&path).context(format!("Failed to load ignore file `{}`", path.display()))?;
+    report::message(tr!(Msg::IgnoreFile, path.display()));
+    Ok(Some(pak_ignore))
+}
+
+/// A written file, `path` is the resolved path and `output_name` the name relative to the output.
+struct ExtractedFile {
+    path: String,
+    output_name: String,
+    /// Decode statistics, `None` for raw entries which are not decoded.
+    decode: Option<DecodeStats>,
+    /// SHA-256 of the content with `--content-hash`.
+    content: Option<ContentDigest>,
 }
 
 fn process_entry(
     entry: &PakEntry,
     file_name_table: &FileNameTable,
-    output_path: &Path,
-    archive_reader: &Mutex<PakArchiveReader<BufReader<File>>>,
+    pak_reader: &mut SourceReader,
+    output: &Output,
     bar: &ProgressBar,
-    r#override: bool,
-) -> anyhow::Result<()> {
-    let mut r = archive_reader.lock().unwrap();
-    let mut entry_reader = (*r).owned_entry_reader(entry.clone())?;
-    drop(r);
+    cmd: &UnpackCommand,
+) -> anyhow::Result<ExtractedFile> {
+    if cmd.raw {
+        let Output::Dir(dir_output) = output else {
+            unreachable!("`--raw` conflicts with `--tar`");
+        };
+        return process_raw_entry(entry, file_name_table, pak_reader, dir_output, bar, cmd);
+    }
+    let mut entry_reader = PakEntryReader::new_streaming(pak_reader, entry)?;
 
     // output file path
-    let file_relative_path: PathBuf = file_name_table
-        .get_file_name(entry.hash())
-        .map(|fname| fname.get_name().to_string())
-        .unwrap_or_else(|| format!("_Unknown/{:08X}", entry.hash()))
-        .into();
-    let filepath = output_path.join(file_relative_path);
-    let filedir = filepath.parent().unwrap();
+    let mut file_relative_path = resolve_path(entry, file_name_table);
+    let dir_output = match output {
+        Output::Dir(dir_output) => dir_output,
+        Output::Tar(tar) => {
+            // the whole entry is buffered for the tar header, so huge entries wait for memory
+            // and entries over the limit fail
+            let _permit = concurrency::try_acquire_memory(entry.uncompressed_size())
+                .context("The entry is too large to buffer for the tar output, raise `--max-memory`.")?;
+            // the size is not trusted, the buffer grows with the decoded data
+            let mut data = vec![];
+            entry_reader.read_to_end(&mut data)?;
+            if !file_relative_path.rsplit('/').next().unwrap().contains('.') {
+                if let Some(ext) = entry_reader.determine_extension() {
+                    file_relative_path = format!("{}.{}", file_relative_path, ext);
+                }
+            }
+            tar.append(&file_relative_path, &data)?;
 
-    if !filedir.exists() {
-        std::fs::create_dir_all(filedir)?;
+            bar.inc(1);
+            return Ok(ExtractedFile {
+                output_name: file_relative_path.clone(),
+                path: file_relative_path,
+                decode: Some(entry_reader.decode_stats()),
+                content: hashes_content(cmd).then(|| content_digest(&data)),
+            });
+        }
+    };
+    let mut output_name = output_file_name(&file_relative_path, cmd);
+    if let Some(shortened) = shorten_path(&output_name, dir_output.path_budget) {
+        output_name = shortened;
     }
+    let filepath = dir_output.path.join(&output_name);
+    dir_output.created_dirs.create_dir_all(filepath.parent().unwrap())?;
 
-    let mut file = if r#override {
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&filepath)?
+    let permit = concurrency::acquire_open_file();
+    if cmd.r#override && dir_output.dedup.is_some() {
+        // the file may be a link from an earlier run, writing through it would change the linked file
+        match std::fs::remove_file(&filepath) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let mut file = ThrottledWriter::new(create_output_file(&filepath, cmd)?);
+    let dedup = dir_output.dedup.is_some();
+    let (content, content_digest) = if hashes_content(cmd) {
+        let mut reader = HashingReader::new(&mut entry_reader);
+        let content = copy_to_file(&mut reader, &mut file, dedup)?;
+        (content, Some(reader.finish()))
     } else {
-        OpenOptions::new().create_new(true).write(true).open(&filepath)?
+        (copy_to_file(&mut entry_reader, &mut file, dedup)?, None)
     };
-    std::io::copy(&mut entry_reader, &mut file)?;
+    drop(file);
+    drop(permit);
 
     // guess unknown file extension
+    let mut filepath = filepath;
     if filepath.extension().is_none() {
         if let Some(ext) = entry_reader.determine_extension() {
             let new_path = filepath.with_extension(ext);
-            std::fs::rename(filepath, new_path)?;
+            std::fs::rename(&filepath, &new_path)?;
+            filepath = new_path;
+            file_relative_path = format!("{}.{}", file_relative_path, ext);
+            output_name = format!("{}.{}", output_name, ext);
         }
     }
+    if let (Some(dedup), Some((size, digest))) = (&dir_output.dedup, content) {
+        dedup.link_duplicate(&filepath, size, digest)?;
+    }
 
     bar.inc(1);
+    Ok(ExtractedFile {
+        path: file_relative_path,
+        output_name,
+        decode: Some(entry_reader.decode_stats()),
+        content: content_digest,
+    })
+}
+
+/// Whether the content of extracted files is hashed, for `--content-hash` or `--manifest`.
+fn hashes_content(cmd: &UnpackCommand) -> bool {
+    cmd.content_hash || cmd.manifest.is_some()
+}
+
+/// Copy a decoded entry into its file, returning its size and digest for `--dedup`.
+fn copy_to_file<R: Read, W: Write>(reader: R, file: &mut W, dedup: bool) -> std::io::Result<Option<(u64, u64)>> {
+    if dedup {
+        let mut reader = DigestReader::new(reader);
+        std::io::copy(&mut reader, file)?;
+        Ok(Some(reader.finish()))
+    } else {
+        let mut reader = reader;
+        std::io::copy(&mut reader, file)?;
+        Ok(None)
+    }
+}
+
+/// Write the stored bytes of an entry to `<path>.raw` and its metadata to `<path>.raw.json`.
+fn process_raw_entry(
+    entry: &PakEntry,
+    file_name_table: &FileNameTable,
+    pak_reader: &mut SourceReader,
+    dir_output: &DirOutput,
+    bar: &ProgressBar,
+    cmd: &UnpackCommand,
+) -> anyhow::Result<ExtractedFile> {
+    let file_relative_path = resolve_path(entry, file_name_table);
+    let mut output_name = format!("{}.raw", output_file_name(&file_relative_path, cmd));
+    // the room reserved for a guessed extension holds the `.json` of the sidecar
+    if let Some(shortened) = shorten_path(&output_name, dir_output.path_budget) {
+        output_name = shortened;
+    }
+    let filepath = dir_output.path.join(&output_name);
+    dir_output.created_dirs.create_dir_all(filepath.parent().unwrap())?;
+
+    let permit = concurrency::acquire_open_file();
+    let mut file = ThrottledWriter::new(create_output_file(&filepath, cmd)?);
+    std::io::copy(&mut PartReader::raw_entry(pak_reader, entry)?, &mut file)?;
+    let metadata = json!({
+        "path": file_relative_path,
+        "hash": report::hash_string(entry.hash()),
+        "offset": entry.offset(),
+        "compressed_size": entry.compressed_size(),
+        "uncompressed_size": entry.uncompressed_size(),
+        "compression": codec_name(entry.compression_method()),
+        "encryption_type": entry.encryption_type(),
+        "checksum": format!("{:016X}", entry.checksum()),
+    });
+    let mut sidecar = create_output_file(&filepath.with_extension("raw.json"), cmd)?;
+    serde_json::to_writer_pretty(&mut sidecar, &metadata)?;
+    writeln!(sidecar)?;
+    drop(permit);
+
+    bar.inc(1);
+    // the raw file stands for the path with the suffix, so mappings only list shortened paths
+    Ok(ExtractedFile {
+        path: format!("{}.raw", file_relative_path),
+        output_name,
+        decode: None,
+        content: None,
+    })
+}
+
+/// Decoded files taking the longest to decode, slowest first, with their decode statistics.
+fn slowest_entries(files: &[ExtractedFile]) -> Vec<serde_json::Value> {
+    let mut decoded: Vec<(&ExtractedFile, &DecodeStats)> = files
+        .iter()
+        .filter_map(|file| file.decode.as_ref().map(|stats| (file, stats)))
+        .collect();
+    decoded.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.decode_time));
+
+    decoded
+        .into_iter()
+        .take(SLOWEST_ENTRY_COUNT)
+        .map(|(file, stats)| json!({ "path": file.path, "decode": decode_json(stats) }))
+        .collect()
+}
+
+fn decode_json(stats: &DecodeStats) -> serde_json::Value {
+    json!({
+        "codec": codec_name(stats.codec),
+        "compressed_bytes": stats.compressed_bytes,
+        "uncompressed_bytes": stats.uncompressed_bytes,
+        "decode_time_us": stats.decode_time.as_micros() as u64,
+    })
+}
+
+/// Create an output file, replacing an existing one only with `--override`.
+fn create_output_file(path: &Path, cmd: &UnpackCommand) -> std::io::Result<File> {
+    let file = if cmd.r#override {
+        OpenOptions::new().create(true).write(true).truncate(true).open(path)
+    } else {
+        OpenOptions::new().create_new(true).write(true).open(path)
+    }?;
+    output_permissions(cmd).apply_to_file(&file)?;
+
+    Ok(file)
+}
+
+/// Modes of extracted files and directories from `--file-mode` and `--dir-mode`.
+fn output_permissions(cmd: &UnpackCommand) -> OutputPermissions {
+    OutputPermissions::new()
+        .with_file_mode(cmd.file_mode.map(|mode| mode.0))
+        .with_dir_mode(cmd.dir_mode.map(|mode| mode.0))
+}
+
+/// Get the output file name relative to the output directory.
+fn output_file_name(file_relative_path: &str, cmd: &UnpackCommand) -> String {
+    let sanitized =
+        invalid_char_replacement(cmd).and_then(|replacement| sanitize_path(file_relative_path, replacement));
+    let path = sanitized.as_deref().unwrap_or(file_relative_path);
+    if cmd.flat {
+        path.replace('/', &cmd.flat_separator)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Replacement of characters not allowed in file names, `_` on Windows unless set by `--replace-invalid`.
+fn invalid_char_replacement(cmd: &UnpackCommand) -> Option<char> {
+    cmd.replace_invalid.or(cfg!(windows).then_some('_'))
+}
+
+/// Write a mapping file, each line is `<output file name>\t<original path>`.
+///
+/// Flat output maps all files, otherwise only shortened paths are written.
+fn write_mapping(output_path: &Path, mut files: Vec<ExtractedFile>, cmd: &UnpackCommand) -> anyhow::Result<()> {
+    let file_name = if cmd.flat {
+        FLAT_MAPPING_FILE_NAME
+    } else {
+        files.retain(|file| file.output_name != file.path);
+        if files.is_empty() {
+            return Ok(());
+        }
+        report::message(tr!(Msg::ShortenedPaths, files.len()));
+        SHORTENED_PATHS_FILE_NAME
+    };
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut writer = BufWriter::new(File::create(output_path.join(file_name))?);
+    for file in files {
+        writeln!(writer, "{}\t{}", file.output_name, file.path)?;
+    }
+    writer.flush()?;
+
     Ok(())
 }
 
-fn unpack_parallel_error_terminate(cmd: &UnpackCommand) -> anyhow::Result<()> {
-    // load project file name table
-    let file_name_table = load_filename_table(&cmd.project)?;
+/// Manifest entry of an extracted file, keyed like the TOC of `flatten --toc` so it works with `--changed-since`.
+fn manifest_entry(entry: &PakEntry, file: &ExtractedFile) -> Value {
+    json!({
+        "hash": report::hash_string(entry.hash()),
+        "path": file.path,
+        "output_name": file.output_name,
+        "compressed_size": entry.compressed_size(),
+        "uncompressed_size": entry.uncompressed_size(),
+        "compression": codec_name(entry.compression_method()),
+        "checksum": format!("{:016X}", entry.checksum()),
+        "sha256": file.content.as_ref().map(to_hex),
+    })
+}
 
-    // load PAK file
-    let file = std::fs::File::open(&cmd.input).context(format!("Input file `{}` not found.", &cmd.input))?;
-    let mut reader = std::io::BufReader::new(file);
-    let archive = ree_pak_core::read::read_archive(&mut reader)?;
-    let archive_reader = Mutex::new(PakArchiveReader::new(reader, &archive));
+/// Write the manifest of extracted files sorted by path, with the content root over them.
+fn write_manifest(path: &Path, files: &[ExtractedFile], mut entries: Vec<Value>) -> anyhow::Result<()> {
+    let mut content = ContentManifest::new();
+    for file in files {
+        if let Some(digest) = file.content {
+            content.add(file.path.clone(), digest);
+        }
+    }
+    entries.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+    let manifest = json!({
+        "content_root": to_hex(&content.root()),
+        "entries": entries,
+    });
 
-    // output path
-    let output_path = output_path(&cmd.output, &cmd.input);
+    let mut writer =
+        BufWriter::new(File::create(path).context(format!("Failed to create manifest `{}`.", path.display()))?);
+    serde_json::to_writer_pretty(&mut writer, &manifest)?;
+    writeln!(writer)?;
+    writer.flush()?;
 
-    // extract files
-    let bar = ProgressBar::new(archive.entries().len() as u64);
-    bar.set_style(
-        ProgressStyle::default_bar().template("{pos}/{len} files written {wide_bar} elapsed: {elapsed} eta: {eta}")?,
-    );
-    bar.enable_steady_tick(Duration::from_millis(100));
-    bar.println(format!("Output directory: `{}`", output_path.display()));
-    archive
-        .entries()
-        .par_iter()
-        .try_for_each(|entry| -> anyhow::Result<()> {
-            let result = process_entry(
-                entry,
-                &file_name_table,
-                &output_path,
-                &archive_reader,
-                &bar,
-                cmd.r#override,
+    Ok(())
+}
+
+/// Destination of unpacked files.
+enum Output {
+    Dir(DirOutput),
+    Tar(TarOutput),
+}
+
+/// An output directory, file paths are kept within `path_budget` bytes relative to it.
+struct DirOutput {
+    path: PathBuf,
+    path_budget: usize,
+    created_dirs: CreatedDirs,
+    dedup: Option<Dedup>,
+}
+
+impl DirOutput {
+    fn new(
+        path: PathBuf,
+        max_path_len: usize,
+        dedup: Option<DedupMode>,
+        permissions: OutputPermissions,
+    ) -> anyhow::Result<Self> {
+        let base_len = std::path::absolute(&path)?.as_os_str().len() + 1;
+        Ok(Self {
+            path_budget: max_path_len.saturating_sub(base_len + GUESSED_EXTENSION_LEN),
+            dedup: dedup.map(|mode| Dedup::new(mode, path.clone())),
+            path,
+            created_dirs: CreatedDirs::new(permissions),
+        })
+    }
+}
+
+/// A tar archive written by all workers, one entry at a time.
+struct TarOutput {
+    builder: Mutex<tar::Builder<BufWriter<ThrottledWriter<File>>>>,
+    mtime: u64,
+    mode: u32,
+}
+
+impl TarOutput {
+    fn create(path: &Path, r#override: bool, mode: u32) -> anyhow::Result<Self> {
+        check_output_file(path)?;
+        let file = if r#override {
+            File::create(path)
+        } else {
+            OpenOptions::new().create_new(true).write(true).open(path)
+        }
+        .context(format!("Failed to create output file `{}`.", path.display()))?;
+        let mtime = ree_pak_core::env::unix_time();
+
+        Ok(Self {
+            builder: Mutex::new(tar::Builder::new(BufWriter::new(ThrottledWriter::new(file)))),
+            mtime,
+            mode,
+        })
+    }
+
+    fn append(&self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(self.mode);
+        header.set_mtime(self.mtime);
+        let mut builder = self.builder.lock().unwrap();
+        builder.append_data(&mut header, path, data)?;
+
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        let mut writer = self.builder.into_inner().unwrap().into_inner()?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Directories known to exist, shared between workers to skip redundant filesystem calls.
+struct CreatedDirs {
+    dirs: RwLock<HashSet<PathBuf>>,
+    permissions: OutputPermissions,
+}
+
+impl CreatedDirs {
+    fn new(permissions: OutputPermissions) -> Self {
+        Self {
+            dirs: RwLock::default(),
+            permissions,
+        }
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> std::io::Result<()> {
+        if self.dirs.read().unwrap().contains(dir) {
+            return Ok(());
+        }
+        self.permissions.create_dir_all(dir)?;
+        self.dirs.write().unwrap().insert(dir.to_path_buf());
+
+        Ok(())
+    }
+}
+
+/// Pak file readers opened on demand, one set per worker.
+pub(crate) struct PakReaders<'a> {
+    multi_pak: &'a MultiPak,
+    readers: Vec<Option<SourceReader>>,
+}
+
+impl<'a> PakReaders<'a> {
+    fn new(multi_pak: &'a MultiPak) -> Self {
+        Self {
+            multi_pak,
+            readers: (0..multi_pak.paks().len()).map(|_| None).collect(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, pak_index: usize) -> anyhow::Result<&mut SourceReader> {
+        let reader = &mut self.readers[pak_index];
+        if reader.is_none() {
+            let source = &self.multi_pak.paks()[pak_index].source;
+            *reader = Some(
+                self.multi_pak
+                    .open_reader(pak_index)
+                    .context(format!("Failed to open input file `{}`", source))?,
             );
-            if let Err(e) = &result {
-                println!("Error processing entry: {}\nEntry: {:?}", e, entry);
-            };
-            result
-        })?;
+        }
 
-    bar.finish();
-    println!("Done.");
+        Ok(reader.as_mut().unwrap())
+    }
+}
+
+pub(crate) fn open_multi_pak(input: &str) -> anyhow::Result<MultiPak> {
+    let input_path = Path::new(input);
+    if input_path.is_dir() {
+        let multi_pak = MultiPak::open_dir(input_path)?;
+        if multi_pak.paks().is_empty() {
+            anyhow::bail!(tr!(Msg::NoPakFiles, input));
+        }
+        Ok(multi_pak)
+    } else {
+        if !input_path.exists() {
+            return Err(failure(Failure::InputMissing, tr!(Msg::InputNotFound, input)));
+        }
+        File::open(input_path).context(tr!(Msg::InputNotFound, input))?;
+        Ok(MultiPak::open(&[input_path])?)
+    }
+}
+
+/// Warn if the project list resolves few entries, suggesting a better matching list.
+///
+/// Using the list of another game is a common mistake that only shows after
+/// extracting everything into `_Unknown`, so a sample of hashes is checked first.
+fn check_list_match(multi_pak: &MultiPak, file_name_table: &FileNameTable, project: &str) -> anyhow::Result<()> {
+    let entries = multi_pak.entries();
+    let step = (entries.len() / LIST_CHECK_SAMPLE_SIZE).max(1);
+    let sample: Vec<u64> = entries.iter().step_by(step).map(|item| item.entry.hash()).collect();
+    let hits = sample
+        .iter()
+        .filter(|&&hash| file_name_table.get_file_name(hash).is_some())
+        .count();
+    if sample.is_empty() || hits * 2 >= sample.len() {
+        return Ok(());
+    }
+
+    eprintln!("{}", tr!(Msg::ListMismatch, project, hits, sample.len()));
+    if let Some((best, best_hits)) = which_list::best_matching_list(&sample, project)? {
+        if best_hits > hits {
+            eprintln!("{}", tr!(Msg::ListSuggestion, best, best_hits, best));
+        }
+    }
 
     Ok(())
 }
 
-fn unpack_parallel_error_continue(cmd: &UnpackCommand) -> anyhow::Result<()> {
+/// Open the input, merging a patch PAK with its base if requested.
+///
+/// A patch only holds the files changed since its base, so extracting it
+/// alone gives a sparse tree, which is easily mistaken for a broken unpack.
+fn open_unpack_input(cmd: &UnpackCommand) -> anyhow::Result<MultiPak> {
+    let input_path = Path::new(&cmd.input);
+    let file_name = input_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let Some(base_name) = input_path.is_file().then(|| patch_base_name(&file_name)).flatten() else {
+        return open_multi_pak(&cmd.input);
+    };
+
+    let chain = find_patch_chain(input_path)?;
+    match (chain, cmd.with_base) {
+        (Some(paths), true) => Ok(MultiPak::open(&paths)?),
+        (None, true) => anyhow::bail!(
+            "Base PAK `{}` of patch `{}` not found in the same directory.",
+            base_name,
+            &cmd.input
+        ),
+        (chain, false) => {
+            eprintln!("{}", tr!(Msg::PatchOnly, file_name, base_name));
+            if chain.is_some() {
+                eprintln!("{}", tr!(Msg::UseWithBase));
+            }
+            open_multi_pak(&cmd.input)
+        }
+    }
+}
+
+/// Process entries on the worker threads with a reader of each pak per worker,
+/// returning the results and the number of errors.
+pub(crate) fn extract_entries<'a, I, F, T>(
+    entries: I,
+    multi_pak: &MultiPak,
+    process: F,
+    ignore_error: bool,
+) -> anyhow::Result<(Vec<T>, usize)>
+where
+    I: ParallelIterator<Item = &'a &'a MultiPakEntry>,
+    F: Fn(&mut PakReaders, &&MultiPakEntry) -> anyhow::Result<T> + Sync + Send,
+    T: Send,
+{
+    let entries = entries.map_init(|| PakReaders::new(multi_pak), process);
+    if ignore_error {
+        let results: Vec<anyhow::Result<T>> = entries.collect();
+        let error_count = results.iter().filter(|result| result.is_err()).count();
+        Ok((
+            results.into_iter().filter_map(|result| result.ok()).collect(),
+            error_count,
+        ))
+    } else {
+        Ok((entries.collect::<anyhow::Result<_>>()?, 0))
+    }
+}
+
+pub fn unpack_parallel(cmd: &UnpackCommand) -> anyhow::Result<()> {
     // load project file name table
     let file_name_table = load_filename_table(&cmd.project)?;
 
-    // load PAK file
-    let file = std::fs::File::open(&cmd.input).context(format!("Input file `{}` not found.", &cmd.input))?;
-    let mut reader = std::io::BufReader::new(file);
-    let archive = ree_pak_core::read::read_archive(&mut reader)?;
-    let archive_reader = Mutex::new(PakArchiveReader::new(reader, &archive));
+    // load PAK files
+    let multi_pak = open_unpack_input(cmd)?;
+    check_list_match(&multi_pak, &file_name_table, &cmd.project)?;
 
     // output path
     let output_path = output_path(&cmd.output, &cmd.input);
+    if let Some(manifest_path) = &cmd.manifest {
+        check_output_file(Path::new(manifest_path))?;
+    }
+    if let Some(replacement) = cmd.replace_invalid {
+        if replacement == '/' || is_invalid_name_char(replacement) {
+            anyhow::bail!(
+                "`{}` cannot replace invalid characters, it is not allowed in file names.",
+                replacement
+            );
+        }
+    }
 
-    // extract files
-    let bar = ProgressBar::new(archive.entries().len() as u64);
-    bar.set_style(
-        ProgressStyle::default_bar().template("{pos}/{len} files written {wide_bar} elapsed: {elapsed} eta: {eta}")?,
-    );
-    bar.enable_steady_tick(Duration::from_millis(100));
-    bar.println(format!("Output directory: `{}`", output_path.display()));
-    let results: Vec<anyhow::Result<()>> = archive
-        .entries()
-        .par_iter()
-        .map(|entry| -> anyhow::Result<()> {
-            let result = process_entry(
-                entry,
+    // filter files
+    let mut entries: Vec<&MultiPakEntry> = selected_entries(&multi_pak, cmd)?.iter().collect();
+    let selected_count = entries.len();
+    let mut skipped = BTreeMap::new();
+    if let Some(pak_ignore) = load_pak_ignore(cmd)? {
+        let total = entries.len();
+        skip_entries(
+            &mut entries,
+            &file_name_table,
+            SkipReason::IgnoreFile,
+            &mut skipped,
+            |_, path| !pak_ignore.is_ignored(path),
+        );
+        report::message(tr!(Msg::IgnoredFiles, total - entries.len(), total));
+    }
+    if let Some(path_filter) = load_path_filter(cmd)? {
+        let total = entries.len();
+        skip_entries(
+            &mut entries,
+            &file_name_table,
+            SkipReason::Filter,
+            &mut skipped,
+            |_, path| path_filter.is_match(path),
+        );
+        report::message(tr!(Msg::SelectedFiles, entries.len(), total));
+    }
+    let removed_count = match &cmd.changed_since {
+        Some(toc_path) => {
+            let previous = load_previous_toc(toc_path)?;
+            let total = entries.len();
+            skip_entries(
+                &mut entries,
                 &file_name_table,
-                &output_path,
-                &archive_reader,
-                &bar,
-                cmd.r#override,
+                SkipReason::Unchanged,
+                &mut skipped,
+                |item, _| {
+                    previous
+                        .get(&item.entry.hash())
+                        .is_none_or(|old| !old.matches(&item.entry))
+                },
             );
-            if let Err(e) = &result {
-                bar.println(format!("Error processing entry: {}\nEntry: {:?}", e, entry));
-            };
-            result
-        })
-        .collect();
+            let current: HashSet<u64> = multi_pak.entries().iter().map(|item| item.entry.hash()).collect();
+            let removed = previous.keys().filter(|hash| !current.contains(hash)).count();
+            report::message(tr!(Msg::ChangedFiles, entries.len(), total, removed));
+            Some(removed)
+        }
+        None => None,
+    };
+    let sample_seed = match cmd.sample {
+        Some(sample) => {
+            let seed = cmd.seed.unwrap_or_else(random_seed);
+            let total = entries.len();
+            let mut picked = sample_mask(total, sample.count(total), seed).into_iter();
+            skip_entries(
+                &mut entries,
+                &file_name_table,
+                SkipReason::Sample,
+                &mut skipped,
+                // entries are visited in order, one mask value each
+                |_, _| picked.next().unwrap_or(false),
+            );
+            report::message(tr!(Msg::SampledFiles, entries.len(), total, seed));
+            Some(seed)
+        }
+        None => None,
+    };
+    let skipped_count = selected_count - entries.len();
+    let order = ExtractOrder::from(cmd.order);
+    order.sort(&mut entries, |item| &item.entry);
+
+    // extract files
+    let bar = report::progress_bar(entries.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::UnpackProgress))?);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    if multi_pak.paks().len() > 1 {
+        for pak in multi_pak.paks() {
+            bar.println(tr!(Msg::InputPak, pak.source));
+        }
+    }
+    let output = match &cmd.tar {
+        Some(tar_path) => {
+            bar.println(tr!(Msg::OutputArchive, tar_path));
+            let mode = cmd.file_mode.map_or(DEFAULT_TAR_MODE, |mode| mode.0);
+            Output::Tar(TarOutput::create(Path::new(tar_path), cmd.r#override, mode)?)
+        }
+        None => {
+            bar.println(tr!(Msg::OutputDirectory, output_path.display()));
+            let max_path_len = cmd.max_path_len.unwrap_or(DEFAULT_MAX_PATH_LEN);
+            Output::Dir(DirOutput::new(
+                output_path.clone(),
+                max_path_len,
+                cmd.dedup,
+                output_permissions(cmd),
+            )?)
+        }
+    };
+    let total_count = entries.len();
+    let total_bytes = entries.iter().map(|item| item.entry.uncompressed_size()).sum();
+    let progress = ExtractProgress::new(total_count as u64, total_bytes);
+    let errors = Mutex::new(vec![]);
+    let manifest_entries = Mutex::new(vec![]);
+    let process = |pak_readers: &mut PakReaders, item: &&MultiPakEntry| -> anyhow::Result<ExtractedFile> {
+        let file_progress = progress.start_file();
+        let result = pak_readers
+            .get(item.pak_index)
+            .and_then(|pak_reader| process_entry(&item.entry, &file_name_table, pak_reader, &output, &bar, cmd));
+        match &result {
+            Ok(file) => {
+                file_progress.add_bytes(item.entry.uncompressed_size());
+                file_progress.finish();
+                let snapshot = progress.snapshot();
+                report::emit(json!({
+                    "event": "extracted",
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": file.path,
+                    "output_name": file.output_name,
+                    "done": snapshot.files_done,
+                    "total": snapshot.total_files,
+                    "bytes_done": snapshot.bytes_done,
+                    "total_bytes": snapshot.total_bytes,
+                    "decode": file.decode.as_ref().map(decode_json),
+                }));
+                if cmd.manifest.is_some() {
+                    manifest_entries.lock().unwrap().push(manifest_entry(&item.entry, file));
+                }
+            }
+            Err(e) => {
+                bar.println(tr!(Msg::EntryError, e, format!("{:?}", item.entry)));
+                let error = json!({
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": resolve_path(&item.entry, &file_name_table),
+                    "error": format!("{:#}", e),
+                });
+                report::emit(json!({ "event": "failed", "error": error }));
+                errors.lock().unwrap().push(error);
+            }
+        }
+        result
+    };
+
+    // splitting the slice would hand the large tail to other threads early,
+    // bridging takes entries one by one in order instead
+    let (files, error_count) = concurrency::install(|| match order {
+        ExtractOrder::Toc => extract_entries(entries.par_iter(), &multi_pak, process, cmd.ignore_error),
+        ExtractOrder::SmallestFirst => {
+            extract_entries(entries.iter().par_bridge(), &multi_pak, process, cmd.ignore_error)
+        }
+    })?;
 
     bar.finish();
+    let extracted_count = files.len();
+    let (deduplicated, saved_bytes) = match output {
+        Output::Tar(tar) => {
+            tar.finish()?;
+            (0, 0)
+        }
+        Output::Dir(DirOutput { dedup: Some(dedup), .. }) => {
+            report::message(tr!(
+                Msg::LinkedDuplicates,
+                dedup.linked(),
+                HumanBytes(dedup.saved_bytes())
+            ));
+            (dedup.linked(), dedup.saved_bytes())
+        }
+        Output::Dir(_) => (0, 0),
+    };
+    let slowest = slowest_entries(&files);
+    let content_root = cmd.content_hash.then(|| {
+        let mut manifest = ContentManifest::new();
+        for file in &files {
+            if let Some(digest) = file.content {
+                manifest.add(file.path.clone(), digest);
+            }
+        }
+        let root = to_hex(&manifest.root());
+        report::message(tr!(Msg::ContentRoot, manifest.len(), &root));
+        root
+    });
+    if let Some(manifest_path) = &cmd.manifest {
+        write_manifest(Path::new(manifest_path), &files, manifest_entries.into_inner().unwrap())?;
+        report::message(tr!(Msg::ManifestWritten, manifest_path));
+    }
+    if cmd.tar.is_none() {
+        write_mapping(&output_path, files, cmd)?;
+    }
 
-    if !results.is_empty() {
-        println!("Done with {} errors", results.len());
+    report::emit(json!({
+        "event": "report",
+        "extracted": extracted_count,
+        "skipped": skipped_count,
+        "skipped_reasons": skipped,
+        "failed": error_count,
+        "deduplicated": deduplicated,
+        "saved_bytes": saved_bytes,
+        "slowest": slowest,
+        "content_root": content_root,
+        "sample_seed": sample_seed,
+        "removed": removed_count,
+        "errors": errors.into_inner().unwrap(),
+    }));
+    if error_count > 0 {
+        report::message(tr!(Msg::DoneWithErrors, error_count));
     } else {
-        println!("Done.");
+        report::message(tr!(Msg::Done));
     }
 
     Ok(())
 }
+
+impl From<UnpackOrder> for ExtractOrder {
+    fn from(value: UnpackOrder) -> Self {
+        match value {
+            UnpackOrder::Toc => ExtractOrder::Toc,
+            UnpackOrder::SmallestFirst => ExtractOrder::SmallestFirst,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_mask() {
+        let mask = sample_mask(100, 10, 42);
+        assert_eq!(mask.iter().filter(|picked| **picked).count(), 10);
+        // the same seed picks the same entries
+        assert_eq!(sample_mask(100, 10, 42), mask);
+        assert_ne!(sample_mask(100, 10, 43), mask);
+        assert_eq!(sample_mask(5, 10, 42), [true; 5]);
+        assert_eq!(sample_mask(0, 3, 42), [] as [bool; 0]);
+    }
+}
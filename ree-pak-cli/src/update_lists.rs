@@ -0,0 +1,25 @@
+use anyhow::Context;
+use ree_pak_core::filename::remote::RemoteLists;
+
+use crate::{unpack::filelist_dir, UpdateListsCommand};
+
+pub fn update_lists(cmd: &UpdateListsCommand) -> anyhow::Result<()> {
+    let remote = RemoteLists::new(&cmd.manifest, filelist_dir()?);
+    let manifest = remote
+        .fetch_manifest()
+        .context(format!("Failed to fetch manifest `{}`.", &cmd.manifest))?;
+
+    let projects: Vec<&String> = if cmd.projects.is_empty() {
+        manifest.lists.keys().collect()
+    } else {
+        cmd.projects.iter().collect()
+    };
+    for project in projects {
+        let path = remote
+            .update(&manifest, project)
+            .context(format!("Failed to update list of `{}`.", project))?;
+        println!("Updated `{}`: {}", project, path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::Read,
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanBytes, ProgressStyle};
+use rayon::iter::IntoParallelRefIterator;
+use ree_pak_core::{
+    concurrency,
+    error::PakError,
+    filename::FileNameTable,
+    pak::PakEntry,
+    read::{io::entry::PakEntryReader, multi::MultiPakEntry, source::SourceReader},
+};
+use serde_json::json;
+
+use crate::{
+    i18n::{tr, Msg},
+    report,
+    unpack::{extract_entries, load_filename_table, open_multi_pak, resolve_path, PakReaders},
+    VerifyCommand,
+};
+
+/// Decode every entry with the scheduling of unpack, discarding the data.
+///
+/// Failed entries are always reported. With `--deep`, the CRC-32 of the content
+/// is computed too, and every entry is reported with it, its size and path,
+/// followed by the overall throughput.
+pub fn verify(cmd: &VerifyCommand) -> anyhow::Result<()> {
+    // load project file name table
+    let file_name_table = match &cmd.project {
+        Some(project) => load_filename_table(project)?,
+        None => FileNameTable::default(),
+    };
+
+    let multi_pak = open_multi_pak(&cmd.input)?;
+    let entries: Vec<&MultiPakEntry> = multi_pak.entries().iter().collect();
+
+    let bar = report::progress_bar(entries.len() as u64);
+    bar.set_style(ProgressStyle::default_bar().template(&tr!(Msg::VerifyProgress))?);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    let start = Instant::now();
+    let process = |pak_readers: &mut PakReaders, item: &&MultiPakEntry| -> anyhow::Result<u64> {
+        let path = resolve_path(&item.entry, &file_name_table);
+        let result = pak_readers
+            .get(item.pak_index)
+            .and_then(|pak_reader| decode_entry(pak_reader, &item.entry, cmd.deep));
+        match &result {
+            Ok((size, Some(crc))) => {
+                report::emit(json!({
+                    "event": "verified",
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": path,
+                    "size": size,
+                    "crc32": format!("{:08X}", crc),
+                }));
+                if !report::is_json() {
+                    bar.suspend(|| println!("OK\t{:08X}\t{}\t{}", crc, size, path));
+                }
+            }
+            Ok((_, None)) => {}
+            Err(e) => {
+                report::emit(json!({
+                    "event": "failed",
+                    "hash": report::hash_string(item.entry.hash()),
+                    "path": path,
+                    "error": format!("{:#}", e),
+                }));
+                if !report::is_json() {
+                    bar.suspend(|| println!("FAIL\t{}\t{:#}", path, e));
+                }
+            }
+        }
+        bar.inc(1);
+        result.map(|(size, _)| size)
+    };
+    let (sizes, failed) = concurrency::install(|| extract_entries(entries.par_iter(), &multi_pak, process, true))?;
+    bar.finish();
+
+    let elapsed = start.elapsed();
+    let decoded_bytes: u64 = sizes.iter().sum();
+    let throughput = (decoded_bytes as f64 / elapsed.as_secs_f64().max(f64::EPSILON)) as u64;
+    report::emit(json!({
+        "event": "report",
+        "verified": sizes.len(),
+        "failed": failed,
+        "decoded_bytes": decoded_bytes,
+        "elapsed_secs": elapsed.as_secs_f64(),
+    }));
+    if failed > 0 {
+        anyhow::bail!(tr!(Msg::VerifyFailed, failed, entries.len()));
+    }
+    if cmd.deep {
+        report::message(tr!(
+            Msg::VerifyDecoded,
+            sizes.len(),
+            entries.len(),
+            HumanBytes(decoded_bytes),
+            format!("{:.2?}", elapsed),
+            HumanBytes(throughput)
+        ));
+    } else {
+        report::message(tr!(Msg::VerifyDone, entries.len()));
+    }
+
+    Ok(())
+}
+
+/// Decode an entry into nothing, returning its size and the CRC-32 if `digest` is set.
+fn decode_entry(pak_reader: &mut SourceReader, entry: &PakEntry, digest: bool) -> anyhow::Result<(u64, Option<u32>)> {
+    let mut entry_reader = PakEntryReader::new_streaming(pak_reader, entry)?;
+    let mut crc = digest.then(crc32fast::Hasher::new);
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = entry_reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        if let Some(crc) = &mut crc {
+            crc.update(&buf[..n]);
+        }
+        size += n as u64;
+    }
+    if size != entry.uncompressed_size() {
+        return Err(PakError::EntrySizeMismatch {
+            hash: entry.hash(),
+            expected: entry.uncompressed_size(),
+            found: size,
+        }
+        .into());
+    }
+
+    Ok((size, crc.map(|crc| crc.finalize())))
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use ree_pak_core::filename::{project_name, FileName};
+
+use crate::{
+    extract_one::parse_hash,
+    unpack::{filelist_dir, load_list_file},
+    WhichListCommand,
+};
+
+pub fn which_list(cmd: &WhichListCommand) -> anyhow::Result<()> {
+    let hash = match (&cmd.path, &cmd.hash) {
+        (_, Some(hash)) => parse_hash(hash)?,
+        (Some(path), None) => FileName::new(path).hash_mixed(),
+        (None, None) => anyhow::bail!("Either `--path` or `--hash` is required."),
+    };
+
+    let dir = filelist_dir()?;
+    let lists = find_lists(&dir)?;
+    if lists.is_empty() {
+        anyhow::bail!("No list files found in `{}`.", dir.display());
+    }
+
+    let mut found = 0;
+    for (project, path) in &lists {
+        let table = match load_list_file(path) {
+            Ok(table) => table,
+            Err(e) => {
+                eprintln!("Warning: skipped `{}`: {:#}", path.display(), e);
+                continue;
+            }
+        };
+        if let Some(name) = table.get_file_name(hash) {
+            println!("{}\t{}", project, name);
+            found += 1;
+        }
+    }
+
+    if found == 0 {
+        println!("{:016X} is not in any of {} lists.", hash, lists.len());
+    } else {
+        println!("{:016X} found in {} of {} lists.", hash, found, lists.len());
+    }
+
+    Ok(())
+}
+
+/// Find the project list resolving most of `hashes`, other than `current`.
+///
+/// Returns the project and the number of resolved hashes, `None` if no list resolves any.
+pub(crate) fn best_matching_list(hashes: &[u64], current: &str) -> anyhow::Result<Option<(String, usize)>> {
+    let mut best: Option<(String, usize)> = None;
+    for (project, path) in find_lists(&filelist_dir()?)? {
+        if project == current {
+            continue;
+        }
+        let Ok(table) = load_list_file(&path) else {
+            continue;
+        };
+        let hits = hashes
+            .iter()
+            .filter(|&&hash| table.get_file_name(hash).is_some())
+            .count();
+        if hits > best.as_ref().map_or(0, |(_, best_hits)| *best_hits) {
+            best = Some((project, hits));
+        }
+    }
+
+    Ok(best)
+}
+
+/// Find the list files of a directory with their project names, sorted by project.
+fn find_lists(dir: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut lists = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(project) = project_name(&path) {
+            lists.push((project.to_string(), path.clone()));
+        }
+    }
+    lists.sort();
+
+    Ok(lists)
+}
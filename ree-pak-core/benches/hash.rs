@@ -0,0 +1,65 @@
+//! Benchmark of file name hashing.
+//!
+//! Run with `cargo bench -p ree-pak-core --bench hash`, add `--features simd-hash`
+//! to measure the SIMD batch hashing.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ree_pak_core::filename::{hash_mixed_batch, FileName};
+
+const NAME_COUNT: usize = 200_000;
+
+fn names() -> Vec<String> {
+    (0..NAME_COUNT)
+        .map(|i| {
+            format!(
+                "natives/stm/environment/props/set{:03}/mesh/prop_{:06}.mesh.2109148288",
+                i % 500,
+                i
+            )
+        })
+        .collect()
+}
+
+fn bench_hash(c: &mut Criterion) {
+    let names = names();
+    let file_names: Vec<FileName> = names.iter().map(|name| FileName::new(name)).collect();
+    let name_refs: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+
+    let mut group = c.benchmark_group("hash");
+    group.throughput(Throughput::Elements(NAME_COUNT as u64));
+    // the reader-based murmur3 over UTF-16 buffers of each case
+    group.bench_function("utf16 reader", |b| {
+        b.iter(|| {
+            file_names
+                .iter()
+                .map(|name| FileName::mix_hash(name.hash_lower_case(), name.hash_upper_case()))
+                .fold(0, |acc, hash| acc ^ black_box(hash))
+        })
+    });
+    group.bench_function("ascii scalar", |b| {
+        b.iter(|| {
+            file_names
+                .iter()
+                .map(|name| name.hash_mixed())
+                .fold(0, |acc, hash| acc ^ black_box(hash))
+        })
+    });
+    let batch_title = if cfg!(feature = "simd-hash") {
+        "batch (simd-hash)"
+    } else {
+        "batch (scalar)"
+    };
+    group.bench_function(batch_title, |b| {
+        b.iter(|| {
+            hash_mixed_batch(black_box(&name_refs))
+                .into_iter()
+                .fold(0, |acc, hash| acc ^ hash)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_hash);
+criterion_main!(benches);
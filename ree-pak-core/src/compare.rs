@@ -0,0 +1,175 @@
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+use crate::error::Result;
+use crate::pak::PakEntry;
+use crate::read::io::entry::PakEntryReader;
+
+/// Default number of bytes hashed at each end of an entry for previews.
+pub const DEFAULT_PREVIEW_SIZE: u64 = 64 * 1024;
+
+/// How far entry contents are compared before they are considered equal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Only compare uncompressed sizes.
+    Size,
+    /// Compare sizes, then hashes of the first and last bytes.
+    Preview,
+    /// Compare sizes and previews, then hashes of the full contents.
+    #[default]
+    Full,
+}
+
+/// Tiered entry content comparison: size, then preview hash, then full hash.
+///
+/// Each tier only runs if the previous one is tied, so most differing entries
+/// are told apart without decoding them fully.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentComparer {
+    strictness: Strictness,
+    preview_size: u64,
+}
+
+impl Default for ContentComparer {
+    fn default() -> Self {
+        Self::new(Strictness::default())
+    }
+}
+
+impl ContentComparer {
+    pub fn new(strictness: Strictness) -> Self {
+        Self {
+            strictness,
+            preview_size: DEFAULT_PREVIEW_SIZE,
+        }
+    }
+
+    /// Set the number of bytes hashed at each end of an entry.
+    pub fn with_preview_size(mut self, preview_size: u64) -> Self {
+        self.preview_size = preview_size;
+        self
+    }
+
+    #[inline]
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
+    /// Compare contents of two entries, each read from its own pak reader.
+    pub fn eq_entries<R1, R2>(&self, a: (&mut R1, &PakEntry), b: (&mut R2, &PakEntry)) -> Result<bool>
+    where
+        R1: BufRead + Seek,
+        R2: BufRead + Seek,
+    {
+        let (reader_a, entry_a) = a;
+        let (reader_b, entry_b) = b;
+        if entry_a.uncompressed_size() != entry_b.uncompressed_size() {
+            return Ok(false);
+        }
+        if self.strictness == Strictness::Size {
+            return Ok(true);
+        }
+
+        if self.preview_digest(reader_a, entry_a)? != self.preview_digest(reader_b, entry_b)? {
+            return Ok(false);
+        }
+        if self.strictness == Strictness::Preview {
+            return Ok(true);
+        }
+
+        Ok(full_digest(reader_a, entry_a)? == full_digest(reader_b, entry_b)?)
+    }
+
+    /// Hash of the first and last preview bytes of an entry, or of all bytes if it is small.
+    ///
+    /// The tail of a compressed entry can only be reached by decoding the entry,
+    /// but it is not hashed.
+    pub fn preview_digest<R>(&self, reader: &mut R, entry: &PakEntry) -> Result<u128>
+    where
+        R: BufRead + Seek,
+    {
+        let mut entry_reader = PakEntryReader::new_streaming(reader, entry)?;
+        let size = entry.uncompressed_size();
+        if size <= self.preview_size * 2 {
+            return Ok(murmur3::murmur3_x64_128(&mut entry_reader, 0)?);
+        }
+
+        let mut preview = vec![0; self.preview_size as usize * 2];
+        let (head, tail) = preview.split_at_mut(self.preview_size as usize);
+        entry_reader.read_exact(head)?;
+        entry_reader.seek(SeekFrom::End(-(self.preview_size as i64)))?;
+        entry_reader.read_exact(tail)?;
+
+        Ok(murmur3::murmur3_x64_128(&mut &preview[..], 0)?)
+    }
+}
+
+/// Hash of the full contents of an entry.
+pub fn full_digest<R>(reader: &mut R, entry: &PakEntry) -> Result<u128>
+where
+    R: BufRead + Seek,
+{
+    let mut entry_reader = PakEntryReader::new_streaming(reader, entry)?;
+    Ok(murmur3::murmur3_x64_128(&mut entry_reader, 0)?)
+}
+
+/// Whether an entry holds exactly `data` of `data_size` bytes, e.g. a file meant to replace it.
+pub fn eq_data<R, D>(reader: &mut R, entry: &PakEntry, mut data: D, data_size: u64) -> Result<bool>
+where
+    R: BufRead + Seek,
+    D: Read,
+{
+    if entry.uncompressed_size() != data_size {
+        return Ok(false);
+    }
+
+    Ok(full_digest(reader, entry)? == murmur3::murmur3_x64_128(&mut data, 0)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::spec;
+
+    fn stored_entry(offset: u64, size: u64) -> PakEntry {
+        PakEntry::from(spec::EntryV2 {
+            hash_name_lower: 0,
+            hash_name_upper: 0,
+            offset,
+            compressed_size: size,
+            uncompressed_size: size,
+            compression_method: 0,
+            checksum: 0,
+        })
+    }
+
+    #[test]
+    fn test_tiered_compare() {
+        let a = vec![1u8; 64];
+        let mut middle = a.clone();
+        middle[32] = 2;
+        let mut tail = a.clone();
+        tail[63] = 2;
+        let data = Cursor::new([a.clone(), middle, tail].concat());
+        let entries = [0, 64, 128].map(|offset| stored_entry(offset, 64));
+
+        let compare = |strictness, i: usize, j: usize| {
+            let (mut data, mut other) = (data.clone(), data.clone());
+            ContentComparer::new(strictness)
+                .with_preview_size(16)
+                .eq_entries((&mut data, &entries[i]), (&mut other, &entries[j]))
+                .unwrap()
+        };
+        assert!(compare(Strictness::Size, 0, 2));
+        assert!(!compare(Strictness::Preview, 0, 2));
+        assert!(compare(Strictness::Preview, 0, 1));
+        assert!(!compare(Strictness::Full, 0, 1));
+        assert!(compare(Strictness::Full, 0, 0));
+
+        let mut reader = data.clone();
+        assert!(eq_data(&mut reader, &entries[0], &a[..], 64).unwrap());
+        assert!(!eq_data(&mut reader, &entries[1], &a[..], 64).unwrap());
+        assert!(!eq_data(&mut reader, &entries[0], &a[..32], 32).unwrap());
+    }
+}
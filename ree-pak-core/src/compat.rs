@@ -0,0 +1,160 @@
+//! Compatibility tests over synthetic paks in each supported layout.
+//!
+//! Fixtures are generated in memory, so no game data is stored in the repository.
+//! Each profile is one header configuration the reader supports.
+
+use std::io::{Cursor, Read, Write};
+
+use crate::filename::FileName;
+use crate::pak::{self, CompressionMethod, PakEntry, PakHeader};
+use crate::read::io::entry::PakEntryReader;
+use crate::read::read_archive;
+use crate::spec;
+use crate::write::{convert_archive, write_encrypted_toc, write_toc};
+
+struct Profile {
+    name: &'static str,
+    major_version: u8,
+    minor_version: u8,
+    encrypted_toc: bool,
+}
+
+const PROFILES: [Profile; 4] = [
+    Profile {
+        name: "v2.0, uncompressed entries",
+        major_version: 2,
+        minor_version: 0,
+        encrypted_toc: false,
+    },
+    Profile {
+        name: "v4.0",
+        major_version: 4,
+        minor_version: 0,
+        encrypted_toc: false,
+    },
+    Profile {
+        name: "v4.1",
+        major_version: 4,
+        minor_version: 1,
+        encrypted_toc: false,
+    },
+    Profile {
+        name: "v4.1, encrypted entry table",
+        major_version: 4,
+        minor_version: 1,
+        encrypted_toc: true,
+    },
+];
+
+const FILES: [(&str, &[u8], CompressionMethod); 3] = [
+    ("natives/stm/a.txt", b"stored data", CompressionMethod::None),
+    (
+        "natives/stm/b.user.2",
+        b"deflate data deflate data",
+        CompressionMethod::Deflate,
+    ),
+    (
+        "natives/stm/c.mesh.1",
+        b"zstd data zstd data zstd data",
+        CompressionMethod::Zstd,
+    ),
+];
+
+/// Build a pak of `FILES` in the layout of a profile.
+fn generate(profile: &Profile) -> Vec<u8> {
+    let feature = if profile.encrypted_toc { 8 } else { 0 };
+    let header = PakHeader::new(
+        profile.major_version,
+        profile.minor_version,
+        feature,
+        FILES.len() as u32,
+        0,
+    )
+    .unwrap();
+    // v2.0 entries cannot describe compression
+    let compressible = header.entry_size() as usize != spec::EntryV1::SIZE;
+
+    let mut offset = spec::Header::SIZE as u64 + header.entry_size() as u64 * FILES.len() as u64;
+    if profile.encrypted_toc {
+        offset += 128;
+    }
+    let mut entries = vec![];
+    let mut data = vec![];
+    for (path, content, compression) in FILES {
+        let compression = if compressible {
+            compression
+        } else {
+            CompressionMethod::None
+        };
+        let stored = match compression {
+            CompressionMethod::None => content.to_vec(),
+            CompressionMethod::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap()
+            }
+            CompressionMethod::Zstd => zstd::encode_all(content, 0).unwrap(),
+        };
+        entries.push(PakEntry::new(
+            FileName::new(path).hash_mixed(),
+            offset,
+            stored.len() as u64,
+            content.len() as u64,
+            compression,
+        ));
+        offset += stored.len() as u64;
+        data.extend_from_slice(&stored);
+    }
+
+    let mut pak = vec![];
+    if profile.encrypted_toc {
+        write_encrypted_toc(&mut pak, &header, &entries, &pak::generate_key()).unwrap();
+    } else {
+        write_toc(&mut pak, &header, &entries).unwrap();
+    }
+    pak.extend_from_slice(&data);
+
+    pak
+}
+
+/// Extract all entries, asserting the archive holds `FILES`.
+fn assert_extracts(pak: Vec<u8>, profile: &Profile) {
+    let mut reader = Cursor::new(pak);
+    let archive = read_archive(&mut reader).unwrap_or_else(|e| panic!("{}: {}", profile.name, e));
+    assert_eq!(archive.entries().len(), FILES.len(), "{}", profile.name);
+    for ((path, content, _), entry) in FILES.iter().zip(archive.entries()) {
+        assert_eq!(entry.hash(), FileName::new(path).hash_mixed(), "{}", profile.name);
+        let mut data = vec![];
+        PakEntryReader::new_streaming(&mut reader, entry)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, *content, "{}: {}", profile.name, path);
+    }
+}
+
+#[test]
+fn test_profiles_extract() {
+    for profile in &PROFILES {
+        assert_extracts(generate(profile), profile);
+    }
+}
+
+#[test]
+fn test_profiles_repack_round_trip() {
+    for profile in &PROFILES {
+        let mut reader = Cursor::new(generate(profile));
+        let archive = read_archive(&mut reader).unwrap();
+        let mut repacked = vec![];
+        convert_archive(
+            &mut reader,
+            &archive,
+            &mut repacked,
+            profile.major_version,
+            profile.minor_version,
+        )
+        .unwrap();
+
+        assert_extracts(repacked, profile);
+    }
+}
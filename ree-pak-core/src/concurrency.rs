@@ -0,0 +1,449 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::{PakError, Result};
+
+/// Limits on the worker threads used by parallel operations of the crate.
+///
+/// Applications embedding the crate set it once with [`set_concurrency_config`],
+/// parallel work then runs in a dedicated pool instead of the global rayon pool.
+/// Decompression itself runs on the worker threads and spawns no threads of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    max_threads: Option<usize>,
+    low_priority: bool,
+    max_open_files: Option<usize>,
+    max_write_bytes_per_sec: Option<u64>,
+    max_in_flight_bytes: Option<u64>,
+}
+
+/// Default limit of decoded bytes held in memory at once, see [`acquire_memory`].
+pub const DEFAULT_MAX_IN_FLIGHT_BYTES: u64 = 1 << 30;
+
+impl ConcurrencyConfig {
+    /// Limit the number of worker threads, `None` uses the number of CPUs.
+    pub fn with_max_threads(mut self, max_threads: Option<usize>) -> Self {
+        self.max_threads = max_threads;
+        self
+    }
+
+    /// Lower the scheduling priority of worker threads, where supported.
+    pub fn with_low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    /// Limit the number of output files open at once, `None` uses [`default_max_open_files`].
+    pub fn with_max_open_files(mut self, max_open_files: Option<usize>) -> Self {
+        self.max_open_files = max_open_files.map(|n| n.max(1));
+        self
+    }
+
+    /// Limit the bytes written per second by all [`ThrottledWriter`]s together, `None` is unlimited.
+    pub fn with_max_write_bytes_per_sec(mut self, max_write_bytes_per_sec: Option<u64>) -> Self {
+        self.max_write_bytes_per_sec = max_write_bytes_per_sec.map(|n| n.max(1));
+        self
+    }
+
+    /// Limit the decoded bytes held in memory at once, `None` uses [`DEFAULT_MAX_IN_FLIGHT_BYTES`].
+    pub fn with_max_in_flight_bytes(mut self, max_in_flight_bytes: Option<u64>) -> Self {
+        self.max_in_flight_bytes = max_in_flight_bytes.map(|n| n.max(1));
+        self
+    }
+
+    #[inline]
+    pub fn max_threads(&self) -> Option<usize> {
+        self.max_threads
+    }
+
+    #[inline]
+    pub fn low_priority(&self) -> bool {
+        self.low_priority
+    }
+
+    #[inline]
+    pub fn max_open_files(&self) -> Option<usize> {
+        self.max_open_files
+    }
+
+    #[inline]
+    pub fn max_write_bytes_per_sec(&self) -> Option<u64> {
+        self.max_write_bytes_per_sec
+    }
+
+    #[inline]
+    pub fn max_in_flight_bytes(&self) -> Option<u64> {
+        self.max_in_flight_bytes
+    }
+
+    fn build_pool(&self) -> Option<Arc<ThreadPool>> {
+        if self.max_threads.is_none() && !self.low_priority {
+            return None;
+        }
+
+        let mut builder = ThreadPoolBuilder::new().thread_name(|i| format!("ree-pak-worker-{}", i));
+        if let Some(max_threads) = self.max_threads {
+            builder = builder.num_threads(max_threads);
+        }
+        if self.low_priority {
+            builder = builder.start_handler(|_| lower_thread_priority());
+        }
+        // fall back to the global pool if threads cannot be spawned
+        builder.build().ok().map(Arc::new)
+    }
+}
+
+struct ConcurrencyState {
+    config: ConcurrencyConfig,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+static STATE: RwLock<ConcurrencyState> = RwLock::new(ConcurrencyState {
+    config: ConcurrencyConfig {
+        max_threads: None,
+        low_priority: false,
+        max_open_files: None,
+        max_write_bytes_per_sec: None,
+        max_in_flight_bytes: None,
+    },
+    pool: None,
+});
+
+/// Number of output files currently open, see [`acquire_open_file`].
+static OPEN_FILES: Mutex<usize> = Mutex::new(0);
+static OPEN_FILE_RELEASED: Condvar = Condvar::new();
+
+/// Set the config consulted by all parallel operations.
+pub fn set_concurrency_config(config: ConcurrencyConfig) {
+    let mut state = STATE.write().unwrap();
+    if state.config != config {
+        if (state.config.max_threads, state.config.low_priority) != (config.max_threads, config.low_priority) {
+            state.pool = config.build_pool();
+        }
+        state.config = config;
+    }
+    OPEN_FILE_RELEASED.notify_all();
+    MEMORY_RELEASED.notify_all();
+}
+
+pub fn concurrency_config() -> ConcurrencyConfig {
+    STATE.read().unwrap().config
+}
+
+/// Run parallel work under the current [`ConcurrencyConfig`].
+pub fn install<OP, R>(op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    let pool = STATE.read().unwrap().pool.clone();
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+/// A slot for one open output file, released when dropped.
+#[derive(Debug)]
+pub struct OpenFilePermit(());
+
+impl Drop for OpenFilePermit {
+    fn drop(&mut self) {
+        *OPEN_FILES.lock().unwrap() -= 1;
+        OPEN_FILE_RELEASED.notify_one();
+    }
+}
+
+/// Wait until an output file may be opened under the configured limit.
+///
+/// Hold the permit while the file is open, so parallel extraction cannot
+/// exhaust the handle limit of the process.
+pub fn acquire_open_file() -> OpenFilePermit {
+    let mut open = OPEN_FILES.lock().unwrap();
+    loop {
+        let limit = concurrency_config()
+            .max_open_files
+            .unwrap_or_else(default_max_open_files);
+        if *open < limit {
+            break;
+        }
+        open = OPEN_FILE_RELEASED.wait(open).unwrap();
+    }
+    *open += 1;
+
+    OpenFilePermit(())
+}
+
+/// Decoded bytes held by [`MemoryPermit`]s, and the size of the entry first in line for memory.
+struct MemoryBudget {
+    in_flight: u64,
+    waiting: Option<u64>,
+}
+
+static MEMORY: Mutex<MemoryBudget> = Mutex::new(MemoryBudget {
+    in_flight: 0,
+    waiting: None,
+});
+static MEMORY_RELEASED: Condvar = Condvar::new();
+
+/// Decoded bytes of an entry held in memory, released when dropped.
+#[derive(Debug)]
+pub struct MemoryPermit {
+    bytes: u64,
+}
+
+impl Drop for MemoryPermit {
+    fn drop(&mut self) {
+        MEMORY.lock().unwrap().in_flight -= self.bytes;
+        MEMORY_RELEASED.notify_all();
+    }
+}
+
+/// Wait until `bytes` of decoded data may be held in memory under the configured limit.
+///
+/// Small entries fan out as long as they fit. An entry that does not fit is
+/// first in line: later entries only start if they leave room for it, so it
+/// starts once enough memory is released instead of waiting behind a stream of
+/// small entries. An entry larger than the whole limit runs alone.
+pub fn acquire_memory(bytes: u64) -> MemoryPermit {
+    let mut budget = MEMORY.lock().unwrap();
+    let mut first_in_line = false;
+    loop {
+        let limit = memory_limit();
+        let reserved = if first_in_line { 0 } else { budget.waiting.unwrap_or(0) };
+        let fits = budget.in_flight.saturating_add(bytes).saturating_add(reserved) <= limit;
+        if fits || (budget.in_flight == 0 && reserved == 0) {
+            break;
+        }
+        if budget.waiting.is_none() {
+            budget.waiting = Some(bytes);
+            first_in_line = true;
+        }
+        budget = MEMORY_RELEASED.wait(budget).unwrap();
+    }
+    if first_in_line {
+        budget.waiting = None;
+        MEMORY_RELEASED.notify_all();
+    }
+    budget.in_flight += bytes;
+
+    MemoryPermit { bytes }
+}
+
+/// Like [`acquire_memory`], but fails with [`PakError::MemoryLimit`] instead of
+/// running a request larger than the whole limit, for sizes from an untrusted entry table.
+pub fn try_acquire_memory(bytes: u64) -> Result<MemoryPermit> {
+    let limit = memory_limit();
+    if bytes > limit {
+        return Err(PakError::MemoryLimit { needed: bytes, limit });
+    }
+
+    Ok(acquire_memory(bytes))
+}
+
+fn memory_limit() -> u64 {
+    concurrency_config()
+        .max_in_flight_bytes
+        .unwrap_or(DEFAULT_MAX_IN_FLIGHT_BYTES)
+}
+
+/// Bytes that may be written without waiting, negative while writers owe time.
+struct WriteBudget {
+    bytes: f64,
+    updated: Option<Instant>,
+}
+
+static WRITE_BUDGET: Mutex<WriteBudget> = Mutex::new(WriteBudget {
+    bytes: 0.0,
+    updated: None,
+});
+
+/// Wait until `len` written bytes fit under the configured write rate.
+///
+/// The budget is shared by all threads and refills at the configured rate,
+/// idle time is saved up to one second of writes.
+pub fn throttle_write(len: usize) {
+    let Some(rate) = concurrency_config().max_write_bytes_per_sec else {
+        return;
+    };
+    let rate = rate as f64;
+    let wait = {
+        let mut budget = WRITE_BUDGET.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = budget.updated.map_or(0.0, |updated| (now - updated).as_secs_f64());
+        budget.bytes = (budget.bytes + elapsed * rate).min(rate) - len as f64;
+        budget.updated = Some(now);
+        // sleep outside the lock, later writers queue behind the reserved bytes
+        (budget.bytes < 0.0).then(|| Duration::from_secs_f64(-budget.bytes / rate))
+    };
+    if let Some(wait) = wait {
+        std::thread::sleep(wait);
+    }
+}
+
+/// Writer keeping its writes under the configured write rate, see [`throttle_write`].
+#[derive(Debug)]
+pub struct ThrottledWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> ThrottledWriter<W> {
+    /// Largest write passed through at once, so waits stay short.
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ThrottledWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(Self::CHUNK_SIZE);
+        let written = self.inner.write(&buf[..len])?;
+        throttle_write(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Default limit of open output files, half of the handle limit of the process
+/// where it can be queried, leaving room for pak readers and other handles.
+pub fn default_max_open_files() -> usize {
+    static DEFAULT: OnceLock<usize> = OnceLock::new();
+    *DEFAULT.get_or_init(|| (process_file_limit() / 2).clamp(16, 4096))
+}
+
+#[cfg(unix)]
+fn process_file_limit() -> usize {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return 1024;
+    }
+    usize::try_from(limit.rlim_cur).unwrap_or(usize::MAX)
+}
+
+// the C runtime limits open files to 512 by default
+#[cfg(not(unix))]
+fn process_file_limit() -> usize {
+    512
+}
+
+#[cfg(unix)]
+fn lower_thread_priority() {
+    // on Linux the nice value applies to the calling thread only
+    unsafe {
+        libc::nice(10);
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_thread_priority() {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Held by tests changing the global config, so they do not run at the same time.
+    static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_open_file_limit() {
+        let _lock = CONFIG_LOCK.lock().unwrap();
+        assert!(default_max_open_files() >= 16);
+
+        set_concurrency_config(ConcurrencyConfig::default().with_max_open_files(Some(1)));
+        let permit = acquire_open_file();
+        let acquired = Arc::new(AtomicBool::new(false));
+        let waiter = {
+            let acquired = acquired.clone();
+            std::thread::spawn(move || {
+                let _permit = acquire_open_file();
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!acquired.load(Ordering::SeqCst));
+
+        drop(permit);
+        waiter.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+        set_concurrency_config(ConcurrencyConfig::default());
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let _lock = CONFIG_LOCK.lock().unwrap();
+        set_concurrency_config(ConcurrencyConfig::default().with_max_in_flight_bytes(Some(100)));
+        let small = acquire_memory(40);
+        let started = Arc::new(AtomicBool::new(false));
+        let large = {
+            let started = started.clone();
+            std::thread::spawn(move || {
+                let _permit = acquire_memory(80);
+                started.store(true, Ordering::SeqCst);
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!started.load(Ordering::SeqCst));
+        // fits the limit, but would leave no room for the entry in line
+        let blocked = Arc::new(AtomicBool::new(false));
+        let second_small = {
+            let blocked = blocked.clone();
+            std::thread::spawn(move || {
+                let _permit = acquire_memory(30);
+                blocked.store(true, Ordering::SeqCst);
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!blocked.load(Ordering::SeqCst));
+
+        drop(small);
+        large.join().unwrap();
+        second_small.join().unwrap();
+        // larger than the limit, runs alone or fails
+        drop(acquire_memory(500));
+        assert!(matches!(
+            try_acquire_memory(500),
+            Err(PakError::MemoryLimit {
+                needed: 500,
+                limit: 100
+            })
+        ));
+        drop(try_acquire_memory(100).unwrap());
+        assert_eq!(MEMORY.lock().unwrap().in_flight, 0);
+        set_concurrency_config(ConcurrencyConfig::default());
+    }
+
+    #[test]
+    fn test_throttled_writer() {
+        let _lock = CONFIG_LOCK.lock().unwrap();
+        let mut writer = ThrottledWriter::new(vec![]);
+        let start = Instant::now();
+        writer.write_all(&[0; 1024 * 1024]).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // the budget starts empty, a second of writes takes a second
+        set_concurrency_config(ConcurrencyConfig::default().with_max_write_bytes_per_sec(Some(1024 * 1024)));
+        let start = Instant::now();
+        writer.write_all(&[0; 1024 * 1024]).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+        set_concurrency_config(ConcurrencyConfig::default());
+        assert_eq!(writer.into_inner().len(), 2 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,162 @@
+//! Root hash over the paths and contents of extracted files.
+//!
+//! Two extractions with the same root wrote the same files with the same
+//! contents, so it can be compared instead of exchanging full manifests. The
+//! root is a SHA-256 merkle root over the files sorted by path, so it does not
+//! depend on the order files were extracted in.
+
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 digest of a file or node.
+pub type ContentDigest = [u8; 32];
+
+// domain prefixes keep leaves and inner nodes from being mistaken for each other
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Collects `(path, content digest)` pairs of extracted files.
+#[derive(Debug, Clone, Default)]
+pub struct ContentManifest {
+    files: Vec<(String, ContentDigest)>,
+}
+
+impl ContentManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl Into<String>, digest: ContentDigest) {
+        self.files.push((path.into(), digest));
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Merkle root over the files sorted by path.
+    ///
+    /// A leaf hashes the path length, path and content digest. A node without a
+    /// sibling moves up a level unchanged. The root of no files is the digest of nothing.
+    pub fn root(&self) -> ContentDigest {
+        let mut files: Vec<&(String, ContentDigest)> = self.files.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut level: Vec<ContentDigest> = files
+            .iter()
+            .map(|(path, digest)| {
+                let mut hasher = Sha256::new();
+                hasher.update([LEAF_PREFIX]);
+                hasher.update((path.len() as u64).to_le_bytes());
+                hasher.update(path.as_bytes());
+                hasher.update(digest);
+                hasher.finalize().into()
+            })
+            .collect();
+        if level.is_empty() {
+            return Sha256::digest([]).into();
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => {
+                        let mut hasher = Sha256::new();
+                        hasher.update([NODE_PREFIX]);
+                        hasher.update(left);
+                        hasher.update(right);
+                        hasher.finalize().into()
+                    }
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        level[0]
+    }
+}
+
+/// Reader computing the SHA-256 digest of the bytes read through it.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Digest of the bytes read so far.
+    pub fn finish(self) -> ContentDigest {
+        self.hasher.finalize().into()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Digest of data held in memory.
+pub fn content_digest(data: &[u8]) -> ContentDigest {
+    Sha256::digest(data).into()
+}
+
+/// Lowercase hex of a digest.
+pub fn to_hex(digest: &ContentDigest) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(data: &[u8]) -> ContentDigest {
+        let mut reader = HashingReader::new(data);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        reader.finish()
+    }
+
+    #[test]
+    fn test_root() {
+        assert_eq!(
+            to_hex(&digest(b"hello")),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        let files = [("a.txt", "one"), ("b/c.txt", "two"), ("d.txt", "three")];
+        let mut forward = ContentManifest::new();
+        let mut backward = ContentManifest::new();
+        for (path, data) in files {
+            forward.add(path, digest(data.as_bytes()));
+        }
+        for (path, data) in files.iter().rev() {
+            backward.add(*path, digest(data.as_bytes()));
+        }
+        assert_eq!(forward.root(), backward.root());
+
+        // the same contents under other paths
+        let mut renamed = ContentManifest::new();
+        for (path, data) in files {
+            renamed.add(format!("x/{}", path), digest(data.as_bytes()));
+        }
+        assert_ne!(renamed.root(), forward.root());
+
+        let mut changed = forward.clone();
+        changed.add("e.txt", digest(b""));
+        assert_ne!(changed.root(), forward.root());
+        assert_eq!(ContentManifest::new().root(), digest(b""));
+    }
+}
@@ -0,0 +1,131 @@
+//! Clock and randomness used by the crate, replaceable for reproducible output.
+//!
+//! Generated keys and build times come from the current [`Env`]. Tests and
+//! fixture generators set a [`FixedEnv`] with [`set_env`], so the same run
+//! always writes the same bytes. File access is not covered, the crate reads and
+//! writes through the readers and writers passed to it.
+
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time and random numbers.
+pub trait Env: Send + Sync {
+    /// Current wall clock time.
+    fn now(&self) -> SystemTime;
+
+    fn random_u64(&self) -> u64;
+
+    fn fill_random(&self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.random_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// The system clock and randomly keyed hashes, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemEnv;
+
+impl Env for SystemEnv {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn random_u64(&self) -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+}
+
+/// A clock standing still and a seeded generator, for reproducible output.
+#[derive(Debug)]
+pub struct FixedEnv {
+    now: SystemTime,
+    state: Mutex<u64>,
+}
+
+impl FixedEnv {
+    /// Clock at `unix_secs` seconds since the Unix epoch, random numbers from `seed`.
+    pub fn new(unix_secs: u64, seed: u64) -> Self {
+        Self {
+            now: UNIX_EPOCH + Duration::from_secs(unix_secs),
+            state: Mutex::new(seed),
+        }
+    }
+}
+
+impl Env for FixedEnv {
+    fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    fn random_u64(&self) -> u64 {
+        splitmix64(&mut self.state.lock().unwrap())
+    }
+}
+
+/// Next number of the splitmix64 generator, advancing `state`.
+///
+/// Fast and fully determined by the seed, for reproducible choices, not for keys
+/// that must be unpredictable.
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+static ENV: RwLock<Option<Arc<dyn Env>>> = RwLock::new(None);
+
+/// Set the env used by the whole crate, `None` restores [`SystemEnv`].
+pub fn set_env(env: Option<Arc<dyn Env>>) {
+    *ENV.write().unwrap() = env;
+}
+
+/// The current env.
+pub fn env() -> Arc<dyn Env> {
+    match &*ENV.read().unwrap() {
+        Some(env) => env.clone(),
+        None => Arc::new(SystemEnv),
+    }
+}
+
+/// Seconds since the Unix epoch by the clock of the current env.
+pub fn unix_time() -> u64 {
+    env()
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_env() {
+        let a = FixedEnv::new(1_700_000_000, 42);
+        let b = FixedEnv::new(1_700_000_000, 42);
+        let (mut key_a, mut key_b) = ([0; 20], [0; 20]);
+        a.fill_random(&mut key_a);
+        b.fill_random(&mut key_b);
+        assert_eq!(key_a, key_b);
+        assert_ne!(a.random_u64(), FixedEnv::new(0, 43).random_u64());
+        assert_eq!(a.now(), b.now());
+
+        set_env(Some(Arc::new(FixedEnv::new(1_700_000_000, 42))));
+        let first = crate::pak::generate_key();
+        assert_eq!(unix_time(), 1_700_000_000);
+        set_env(Some(Arc::new(FixedEnv::new(1_700_000_000, 42))));
+        assert_eq!(crate::pak::generate_key(), first);
+        set_env(None);
+        assert_ne!(crate::pak::generate_key(), first);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::pak::{CompressionMethod, EntrySuspicion};
+
 pub type Result<T> = std::result::Result<T, PakError>;
 
 #[derive(Debug, thiserror::Error)]
@@ -11,7 +13,63 @@ pub enum PakError {
     UnsupportedVersion { major: u8, minor: u8 },
     #[error("Unsupported algorithm: {0:X}")]
     UnsupportedAlgorithm(u16),
+    #[error("Header rejected: {0}")]
+    HeaderRejected(String),
+
+    #[error("Invalid file name at line {line} of the list, not valid UTF-8")]
+    InvalidFileName { line: usize },
+    #[error("File name `{0}` would be written outside the output directory")]
+    UnsafeFileName(String),
+    #[error("Invalid include of list `{path}`: {reason}")]
+    InvalidListInclude { path: String, reason: String },
+    #[error("Invalid pak metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("Invalid pack manifest: {0}")]
+    InvalidManifest(String),
+    #[error("Invalid compression config: {0}")]
+    InvalidCompressionConfig(String),
+    #[error("Unknown encoding: {0}")]
+    UnknownEncoding(String),
+    #[error("Invalid ignore pattern: {0}")]
+    InvalidIgnorePattern(String),
+    #[error("Invalid glob pattern: {0}")]
+    InvalidGlob(String),
+    #[error("Remote list error: {0}")]
+    RemoteList(String),
+    #[error("List file hash mismatch for `{project}`: expected {expected}, found {found}")]
+    ListHashMismatch {
+        project: String,
+        expected: String,
+        found: String,
+    },
 
+    #[error("Entry {hash:016X} has resource encryption type {encryption_type}, its data cannot be decoded")]
+    EncryptedEntry { hash: u64, encryption_type: u16 },
     #[error("Entry index out of bounds")]
     EntryIndexOutOfBounds,
+    #[error("Entry {hash:016X} data size mismatch: expected {expected}, found {found}")]
+    EntrySizeMismatch { hash: u64, expected: u64, found: u64 },
+    #[error("Entry {hash:016X} data does not match its declaration: {reason}")]
+    InvalidEntryPayload { hash: u64, reason: String },
+    #[error(
+        "Entry {hash:016X} is inconsistent, {suspicion}: {method:?} compression, \
+        {compressed_size} bytes stored for {uncompressed_size} bytes"
+    )]
+    SuspiciousEntry {
+        hash: u64,
+        suspicion: EntrySuspicion,
+        method: CompressionMethod,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    },
+    #[error("Header declares {found} entries, more than the limit of {limit}")]
+    EntryCountLimit { found: u32, limit: u32 },
+    #[error("Too many entries, only {0} were reserved")]
+    TooManyEntries(u32),
+    #[error("Only {written} of {reserved} reserved entries were written")]
+    UnusedEntries { reserved: u32, written: u32 },
+    #[error("{needed} bytes would be held in memory, more than the limit of {limit} bytes")]
+    MemoryLimit { needed: u64, limit: u64 },
+    #[error("{0:?} compression is not supported by this build, enable the codec feature")]
+    CodecDisabled(CompressionMethod),
 }
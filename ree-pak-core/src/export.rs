@@ -0,0 +1,640 @@
+//! Export of pak entries into other archive formats.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{WriteBytesExt, LE};
+
+use crate::error::{PakError, Result};
+use crate::filename::is_relative_path;
+use crate::pak::{CompressionMethod, PakEntry};
+use crate::permissions::OutputPermissions;
+use crate::read::io::entry::PakEntryReader;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034B50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014B50;
+const END_SIGNATURE: u32 = 0x06054B50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064B50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064B50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// Version 4.5 of the zip specification, the first with ZIP64.
+const VERSION_ZIP64: u16 = 45;
+const VERSION_DEFAULT: u16 = 20;
+/// Names are UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+/// 1980-01-01, the earliest DOS date, so exports are reproducible.
+const DOS_DATE: u16 = (1 << 5) | 1;
+/// Entries this large use ZIP64 sizes, the margin covers deflate expansion.
+const ZIP64_THRESHOLD: u64 = 0x8000_0000;
+/// Decoded bytes read before the header is written, enough to detect the extension.
+const HEAD_SIZE: usize = 64 * 1024;
+
+/// Target of exported files.
+///
+/// Sinks only store named files, decoding entries and naming them is shared
+/// through [`ExportSink::add_entry`]. Implement it to extract into other
+/// stores such as a database, [`DirExporter`] writes into a directory.
+pub trait ExportSink {
+    /// Add a file of `size` bytes read from `data`.
+    fn add_file(&mut self, name: &str, size: u64, data: &mut dyn Read) -> Result<()>;
+
+    /// Called by [`ExportSink::add_entry`] after the file of an entry is added, with the entry metadata.
+    fn finish_entry(&mut self, name: &str, entry: &PakEntry) -> Result<()> {
+        let _ = (name, entry);
+        Ok(())
+    }
+
+    /// Decode an entry into the sink as `path`, returning the name written.
+    ///
+    /// A path whose file name has no extension gets one guessed from the content.
+    fn add_entry<R>(&mut self, reader: &mut R, entry: &PakEntry, path: &str) -> Result<String>
+    where
+        R: BufRead + Seek,
+        Self: Sized,
+    {
+        let mut entry_reader = PakEntryReader::new_streaming(reader, entry)?;
+        let mut head = vec![];
+        (&mut entry_reader).take(HEAD_SIZE as u64).read_to_end(&mut head)?;
+        let mut name = path.to_string();
+        if !name.rsplit('/').next().unwrap_or_default().contains('.') {
+            if let Some(ext) = entry_reader.determine_extension() {
+                name = format!("{}.{}", name, ext);
+            }
+        }
+
+        self.add_file(
+            &name,
+            entry.uncompressed_size(),
+            &mut head.as_slice().chain(entry_reader),
+        )?;
+        self.finish_entry(&name, entry)?;
+        Ok(name)
+    }
+}
+
+/// Compression of files in an exported zip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZipCompression {
+    Store,
+    #[default]
+    Deflate,
+}
+
+impl ZipCompression {
+    fn method(self) -> u16 {
+        match self {
+            ZipCompression::Store => 0,
+            ZipCompression::Deflate => 8,
+        }
+    }
+}
+
+/// Writes decoded pak entries into a zip archive, one entry at a time.
+///
+/// Headers are completed by seeking back after each file, so no data is
+/// buffered beyond the first bytes of an entry. ZIP64 records are written
+/// when sizes, offsets or the file count need them.
+pub struct ZipExporter<W> {
+    writer: W,
+    compression: ZipCompression,
+    files: Vec<ZipFile>,
+}
+
+/// Central directory record of a written file.
+struct ZipFile {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    offset: u64,
+    zip64: bool,
+}
+
+impl<W> ZipExporter<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            compression: ZipCompression::default(),
+            files: vec![],
+        }
+    }
+
+    pub fn with_compression(mut self, compression: ZipCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Number of files added so far.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Write the central directory and return the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        let directory_start = self.writer.stream_position()?;
+        for file in &self.files {
+            write_central_header(&mut self.writer, file)?;
+        }
+        let directory_end = self.writer.stream_position()?;
+        let directory_size = directory_end - directory_start;
+        let count = self.files.len() as u64;
+
+        let zip64 = count >= 0xFFFF || directory_start >= u32::MAX as u64 || directory_size >= u32::MAX as u64;
+        let w = &mut self.writer;
+        if zip64 {
+            w.write_u32::<LE>(ZIP64_END_SIGNATURE)?;
+            w.write_u64::<LE>(44)?;
+            w.write_u16::<LE>(VERSION_ZIP64)?;
+            w.write_u16::<LE>(VERSION_ZIP64)?;
+            w.write_u32::<LE>(0)?;
+            w.write_u32::<LE>(0)?;
+            w.write_u64::<LE>(count)?;
+            w.write_u64::<LE>(count)?;
+            w.write_u64::<LE>(directory_size)?;
+            w.write_u64::<LE>(directory_start)?;
+
+            w.write_u32::<LE>(ZIP64_LOCATOR_SIGNATURE)?;
+            w.write_u32::<LE>(0)?;
+            w.write_u64::<LE>(directory_end)?;
+            w.write_u32::<LE>(1)?;
+        }
+
+        w.write_u32::<LE>(END_SIGNATURE)?;
+        w.write_u16::<LE>(0)?;
+        w.write_u16::<LE>(0)?;
+        let short_count = if zip64 { 0xFFFF } else { count as u16 };
+        w.write_u16::<LE>(short_count)?;
+        w.write_u16::<LE>(short_count)?;
+        w.write_u32::<LE>(if zip64 { u32::MAX } else { directory_size as u32 })?;
+        w.write_u32::<LE>(if zip64 { u32::MAX } else { directory_start as u32 })?;
+        w.write_u16::<LE>(0)?;
+        w.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// Write a local header with the CRC and sizes left zero, they are completed after the data.
+    fn write_local_header(&mut self, name: &str, zip64: bool) -> Result<()> {
+        let w = &mut self.writer;
+        w.write_u32::<LE>(LOCAL_HEADER_SIGNATURE)?;
+        w.write_u16::<LE>(if zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT })?;
+        w.write_u16::<LE>(FLAG_UTF8)?;
+        w.write_u16::<LE>(self.compression.method())?;
+        w.write_u16::<LE>(0)?;
+        w.write_u16::<LE>(DOS_DATE)?;
+        w.write_u32::<LE>(0)?;
+        let size = if zip64 { u32::MAX } else { 0 };
+        w.write_u32::<LE>(size)?;
+        w.write_u32::<LE>(size)?;
+        w.write_u16::<LE>(name.len() as u16)?;
+        w.write_u16::<LE>(if zip64 { 20 } else { 0 })?;
+        w.write_all(name.as_bytes())?;
+        if zip64 {
+            w.write_u16::<LE>(ZIP64_EXTRA_ID)?;
+            w.write_u16::<LE>(16)?;
+            w.write_u64::<LE>(0)?;
+            w.write_u64::<LE>(0)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W> ExportSink for ZipExporter<W>
+where
+    W: Write + Seek,
+{
+    fn add_file(&mut self, name: &str, size: u64, data: &mut dyn Read) -> Result<()> {
+        if self.compression == ZipCompression::Deflate && !CompressionMethod::Deflate.is_supported() {
+            return Err(PakError::CodecDisabled(CompressionMethod::Deflate));
+        }
+        let zip64 = size >= ZIP64_THRESHOLD;
+        let offset = self.writer.stream_position()?;
+        self.write_local_header(name, zip64)?;
+        let data_start = self.writer.stream_position()?;
+
+        let mut data = CrcReader::new(data);
+        match self.compression {
+            ZipCompression::Store => {
+                io::copy(&mut data, &mut self.writer)?;
+            }
+            #[cfg(feature = "deflate")]
+            ZipCompression::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(&mut self.writer, flate2::Compression::default());
+                io::copy(&mut data, &mut encoder)?;
+                encoder.finish()?;
+            }
+            #[cfg(not(feature = "deflate"))]
+            ZipCompression::Deflate => unreachable!("deflate is rejected above"),
+        }
+        let end = self.writer.stream_position()?;
+        let file = ZipFile {
+            name: name.to_string(),
+            method: self.compression.method(),
+            crc: data.crc.finalize(),
+            compressed_size: end - data_start,
+            uncompressed_size: data.len,
+            offset,
+            zip64,
+        };
+        if !zip64 && file.compressed_size.max(file.uncompressed_size) > u32::MAX as u64 {
+            return Err(io::Error::other(format!("zip entry `{}` is larger than its declared size", file.name)).into());
+        }
+
+        // complete the local header
+        self.writer.seek(SeekFrom::Start(offset + 14))?;
+        self.writer.write_u32::<LE>(file.crc)?;
+        if zip64 {
+            self.writer
+                .seek(SeekFrom::Start(offset + 30 + file.name.len() as u64 + 4))?;
+            self.writer.write_u64::<LE>(file.uncompressed_size)?;
+            self.writer.write_u64::<LE>(file.compressed_size)?;
+        } else {
+            self.writer.write_u32::<LE>(file.compressed_size as u32)?;
+            self.writer.write_u32::<LE>(file.uncompressed_size as u32)?;
+        }
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        self.files.push(file);
+        Ok(())
+    }
+}
+
+/// Writes decoded pak entries into a tar archive, streaming to any writer.
+///
+/// Headers use the GNU format with fixed modes and times, so exports are reproducible.
+pub struct TarExporter<W: Write> {
+    builder: tar::Builder<W>,
+    count: usize,
+}
+
+impl<W> TarExporter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            builder: tar::Builder::new(writer),
+            count: 0,
+        }
+    }
+
+    /// Number of files added so far.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Write the end of archive and return the inner writer.
+    pub fn finish(self) -> Result<W> {
+        let mut writer = self.builder.into_inner()?;
+        writer.flush()?;
+
+        Ok(writer)
+    }
+}
+
+impl<W> ExportSink for TarExporter<W>
+where
+    W: Write,
+{
+    fn add_file(&mut self, name: &str, size: u64, data: &mut dyn Read) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        // the header is written first, a size mismatch would corrupt the following entries
+        let mut data = CrcReader::new(data.take(size));
+        self.builder.append_data(&mut header, name, &mut data)?;
+        if data.len != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("tar entry `{}` is shorter than its declared size", name),
+            )
+            .into());
+        }
+        self.count += 1;
+
+        Ok(())
+    }
+}
+
+/// Writes decoded pak entries as files under a directory.
+pub struct DirExporter {
+    root: PathBuf,
+    permissions: OutputPermissions,
+    count: usize,
+}
+
+impl DirExporter {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            permissions: OutputPermissions::default(),
+            count: 0,
+        }
+    }
+
+    /// Modes of the created files and directories.
+    pub fn with_permissions(mut self, permissions: OutputPermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of files added so far.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+impl ExportSink for DirExporter {
+    fn add_file(&mut self, name: &str, _size: u64, data: &mut dyn Read) -> Result<()> {
+        // names come from lists, which may be fetched from anywhere
+        if !is_relative_path(name) {
+            return Err(PakError::UnsafeFileName(name.to_string()));
+        }
+        let path = self.root.join(name);
+        if let Some(parent) = path.parent() {
+            self.permissions.create_dir_all(parent)?;
+        }
+        let file = File::create(&path)?;
+        self.permissions.apply_to_file(&file)?;
+        let mut writer = BufWriter::new(file);
+        io::copy(data, &mut writer)?;
+        writer.flush()?;
+        self.count += 1;
+
+        Ok(())
+    }
+}
+
+fn write_central_header<W: Write>(w: &mut W, file: &ZipFile) -> Result<()> {
+    let zip64 = file.zip64 || file.offset >= u32::MAX as u64;
+    w.write_u32::<LE>(CENTRAL_HEADER_SIGNATURE)?;
+    w.write_u16::<LE>(VERSION_ZIP64)?;
+    w.write_u16::<LE>(if zip64 { VERSION_ZIP64 } else { VERSION_DEFAULT })?;
+    w.write_u16::<LE>(FLAG_UTF8)?;
+    w.write_u16::<LE>(file.method)?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(DOS_DATE)?;
+    w.write_u32::<LE>(file.crc)?;
+    if zip64 {
+        w.write_u32::<LE>(u32::MAX)?;
+        w.write_u32::<LE>(u32::MAX)?;
+    } else {
+        w.write_u32::<LE>(file.compressed_size as u32)?;
+        w.write_u32::<LE>(file.uncompressed_size as u32)?;
+    }
+    w.write_u16::<LE>(file.name.len() as u16)?;
+    w.write_u16::<LE>(if zip64 { 28 } else { 0 })?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(0)?;
+    w.write_u32::<LE>(0)?;
+    w.write_u32::<LE>(if zip64 { u32::MAX } else { file.offset as u32 })?;
+    w.write_all(file.name.as_bytes())?;
+    if zip64 {
+        w.write_u16::<LE>(ZIP64_EXTRA_ID)?;
+        w.write_u16::<LE>(24)?;
+        w.write_u64::<LE>(file.uncompressed_size)?;
+        w.write_u64::<LE>(file.compressed_size)?;
+        w.write_u64::<LE>(file.offset)?;
+    }
+
+    Ok(())
+}
+
+/// Computes the CRC-32 and length of the bytes read through it.
+struct CrcReader<R> {
+    inner: R,
+    crc: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<R: Read> CrcReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            crc: crc32fast::Hasher::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{ReadBytesExt, LE};
+
+    use super::*;
+    use crate::pak::PakArchive;
+    use crate::read::read_archive;
+    use crate::write::{FileOptions, PakWriter};
+
+    /// Read the files of a zip through its central directory, inflating deflated data.
+    fn read_zip(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = zip.len() - 22;
+        let mut eocd = &zip[end..];
+        assert_eq!(eocd.read_u32::<LE>().unwrap(), END_SIGNATURE);
+        let count = (&zip[end + 10..]).read_u16::<LE>().unwrap();
+        let mut directory = &zip[(&zip[end + 16..]).read_u32::<LE>().unwrap() as usize..];
+
+        let mut files = vec![];
+        for _ in 0..count {
+            let mut header = [0; 46];
+            directory.read_exact(&mut header).unwrap();
+            let field = |pos: usize| (&header[pos..]).read_u32::<LE>().unwrap();
+            let name_len = (&header[28..]).read_u16::<LE>().unwrap() as usize;
+            let name = String::from_utf8(directory[..name_len].to_vec()).unwrap();
+            directory = &directory[name_len..];
+
+            let (method, crc, compressed_size, offset) = (header[10], field(16), field(20), field(42));
+            let local_name_len = (&zip[offset as usize + 26..]).read_u16::<LE>().unwrap() as usize;
+            let data_start = offset as usize + 30 + local_name_len;
+            let stored = &zip[data_start..data_start + compressed_size as usize];
+            let data = match method {
+                0 => stored.to_vec(),
+                8 => {
+                    let mut data = vec![];
+                    flate2::read::DeflateDecoder::new(stored)
+                        .read_to_end(&mut data)
+                        .unwrap();
+                    data
+                }
+                _ => panic!("unknown method {}", method),
+            };
+            assert_eq!(crc, crc32fast::hash(&data), "{}", name);
+            assert_eq!(field(24) as usize, data.len());
+            files.push((name, data));
+        }
+
+        files
+    }
+
+    /// A pak with a named text file and a texture without extension.
+    fn sample_pak() -> (Cursor<Vec<u8>>, PakArchive) {
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 2).unwrap();
+        writer
+            .start_file(
+                "a.txt",
+                FileOptions::default().with_compression(CompressionMethod::Zstd),
+            )
+            .unwrap();
+        writer.write_all(b"text text text").unwrap();
+        writer.start_file("b", FileOptions::default()).unwrap();
+        writer.write_all(b"TEX\0texture data").unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+        let archive = read_archive(&mut reader).unwrap();
+
+        (reader, archive)
+    }
+
+    /// Add both sample entries to a sink, checking the names written.
+    fn add_sample_entries<S: ExportSink>(sink: &mut S, reader: &mut Cursor<Vec<u8>>, archive: &PakArchive) {
+        assert_eq!(
+            sink.add_entry(reader, &archive.entries()[0], "natives/a.txt").unwrap(),
+            "natives/a.txt"
+        );
+        assert_eq!(
+            sink.add_entry(reader, &archive.entries()[1], "_Unknown/B").unwrap(),
+            "_Unknown/B.tex"
+        );
+    }
+
+    #[test]
+    fn test_zip_export() {
+        let (mut reader, archive) = sample_pak();
+        for compression in [ZipCompression::Store, ZipCompression::Deflate] {
+            let mut exporter = ZipExporter::new(Cursor::new(vec![])).with_compression(compression);
+            add_sample_entries(&mut exporter, &mut reader, &archive);
+            let zip = exporter.finish().unwrap().into_inner();
+
+            let files = read_zip(&zip);
+            assert_eq!(files[0], ("natives/a.txt".to_string(), b"text text text".to_vec()));
+            assert_eq!(files[1], ("_Unknown/B.tex".to_string(), b"TEX\0texture data".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_tar_export() {
+        let (mut reader, archive) = sample_pak();
+        let mut exporter = TarExporter::new(vec![]);
+        add_sample_entries(&mut exporter, &mut reader, &archive);
+        assert_eq!(exporter.len(), 2);
+        let tar = exporter.finish().unwrap();
+
+        let mut files = vec![];
+        for file in tar::Archive::new(tar.as_slice()).entries().unwrap() {
+            let mut file = file.unwrap();
+            let name = file.path().unwrap().to_string_lossy().into_owned();
+            let mut data = vec![];
+            file.read_to_end(&mut data).unwrap();
+            files.push((name, data));
+        }
+        assert_eq!(
+            files,
+            [
+                ("natives/a.txt".to_string(), b"text text text".to_vec()),
+                ("_Unknown/B.tex".to_string(), b"TEX\0texture data".to_vec()),
+            ]
+        );
+
+        // a short file is an error instead of a corrupt archive
+        let mut exporter = TarExporter::new(vec![]);
+        assert!(exporter.add_file("short", 10, &mut &b"12345"[..]).is_err());
+    }
+
+    /// Files and entry hashes kept in memory.
+    #[derive(Default)]
+    struct MemorySink {
+        files: Vec<(String, Vec<u8>)>,
+        hashes: Vec<(String, u64)>,
+    }
+
+    impl ExportSink for MemorySink {
+        fn add_file(&mut self, name: &str, _size: u64, data: &mut dyn Read) -> Result<()> {
+            let mut content = vec![];
+            data.read_to_end(&mut content)?;
+            self.files.push((name.to_string(), content));
+            Ok(())
+        }
+
+        fn finish_entry(&mut self, name: &str, entry: &PakEntry) -> Result<()> {
+            self.hashes.push((name.to_string(), entry.hash()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_sink() {
+        let (mut reader, archive) = sample_pak();
+        let mut sink = MemorySink::default();
+        add_sample_entries(&mut sink, &mut reader, &archive);
+
+        assert_eq!(
+            sink.files[1],
+            ("_Unknown/B.tex".to_string(), b"TEX\0texture data".to_vec())
+        );
+        assert_eq!(
+            sink.hashes,
+            [
+                ("natives/a.txt".to_string(), archive.entries()[0].hash()),
+                ("_Unknown/B.tex".to_string(), archive.entries()[1].hash()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dir_export() {
+        let dir = std::env::temp_dir().join(format!("ree-pak-dir-export-{}", std::process::id()));
+        let (mut reader, archive) = sample_pak();
+        let mut exporter = DirExporter::new(&dir);
+        add_sample_entries(&mut exporter, &mut reader, &archive);
+        assert_eq!(exporter.len(), 2);
+
+        assert_eq!(std::fs::read(dir.join("natives/a.txt")).unwrap(), b"text text text");
+        assert_eq!(std::fs::read(dir.join("_Unknown/B.tex")).unwrap(), b"TEX\0texture data");
+
+        for name in ["../escaped.txt", "natives/../../escaped.txt", "/tmp/escaped.txt"] {
+            assert!(matches!(
+                exporter.add_file(name, 1, &mut &b"x"[..]),
+                Err(PakError::UnsafeFileName(_))
+            ));
+        }
+        assert_eq!(exporter.len(), 2);
+        assert!(!dir.parent().unwrap().join("escaped.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
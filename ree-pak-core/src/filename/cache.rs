@@ -0,0 +1,248 @@
+//! Binary cache of hashed list files, stored in the user cache directory.
+//!
+//! The cache holds the name buffer and the hash spans, so loading it skips
+//! decoding and hashing. It is keyed by the size and modification time of the
+//! list and by the list options, and rebuilt when any of them changes. Each list
+//! gets its own cache file, named after the list and a digest of its full path.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+
+use crate::content_hash::{content_digest, to_hex};
+use crate::error::Result;
+
+use super::{FileNameTable, ListOptions, NameSpan};
+
+const CACHE_MAGIC: [u8; 4] = *b"RPLC";
+const CACHE_VERSION: u32 = 1;
+/// Size of a hash and its span in the cache.
+const SPAN_RECORD_SIZE: u64 = 16;
+/// Directory of the caches within the user cache directory.
+const CACHE_DIR: &str = "ree-pak/lists";
+
+/// What a cache was built from, a cache is only used if all fields match.
+#[derive(Debug, PartialEq, Eq)]
+struct CacheKey {
+    list_size: u64,
+    list_mtime: u64,
+    options: String,
+}
+
+impl CacheKey {
+    fn of_list(path: &Path, options: ListOptions) -> Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let list_mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Ok(Self {
+            list_size: metadata.len(),
+            list_mtime,
+            options: format!(
+                "{:?}/{}",
+                options.invalid_name_policy,
+                options.fallback_encoding.map(|e| e.name()).unwrap_or_default()
+            ),
+        })
+    }
+}
+
+impl FileNameTable {
+    /// Path of the binary cache of a list file, `None` if the user has no cache directory.
+    pub fn cache_path<P: AsRef<Path>>(list_path: P) -> Option<PathBuf> {
+        let cache_dir = dirs::cache_dir()?.join(CACHE_DIR);
+        Some(cache_file_path(&cache_dir, list_path.as_ref()))
+    }
+
+    /// Load a list file through its binary cache, building the cache if it is missing or stale.
+    ///
+    /// Lists with invalid names are not cached, so they are reported on every load.
+    /// Lists with includes are not cached either, the cache key does not cover the included lists.
+    /// Failing to write the cache is not an error.
+    pub fn from_list_file_cached<P>(path: P, options: ListOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        match Self::cache_path(path) {
+            Some(cache_path) => Self::from_list_file_cached_at(path, options, &cache_path),
+            None => Self::from_list_file_with_options(path, options),
+        }
+    }
+
+    fn from_list_file_cached_at(path: &Path, options: ListOptions, cache_path: &Path) -> Result<Self> {
+        let key = CacheKey::of_list(path, options)?;
+        if let Ok(Some(table)) = Self::read_cache(cache_path, &key) {
+            return Ok(table);
+        }
+
+        let table = Self::from_list_file_with_options(path, options)?;
+        if table.invalid_names.is_empty() && table.includes.is_empty() {
+            let _ = table.replace_cache(cache_path, &key);
+        }
+
+        Ok(table)
+    }
+
+    /// Read a cache, `None` if it was built from another list or options.
+    fn read_cache(cache_path: &Path, key: &CacheKey) -> Result<Option<Self>> {
+        let file = File::open(cache_path)?;
+        // lengths are checked against the file before allocating, a damaged cache is rebuilt
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CACHE_MAGIC || reader.read_u32::<LE>()? != CACHE_VERSION {
+            return Ok(None);
+        }
+        let list_size = reader.read_u64::<LE>()?;
+        let list_mtime = reader.read_u64::<LE>()?;
+        let options_len = reader.read_u32::<LE>()? as u64;
+        if options_len > file_size {
+            return Ok(None);
+        }
+        let mut options = vec![0; options_len as usize];
+        reader.read_exact(&mut options)?;
+        let cached_key = CacheKey {
+            list_size,
+            list_mtime,
+            options: String::from_utf8_lossy(&options).into_owned(),
+        };
+        if &cached_key != key {
+            return Ok(None);
+        }
+
+        let arena_len = reader.read_u64::<LE>()?;
+        if arena_len > file_size {
+            return Ok(None);
+        }
+        let mut arena = vec![0; arena_len as usize];
+        reader.read_exact(&mut arena)?;
+        let Ok(arena) = String::from_utf8(arena) else {
+            return Ok(None);
+        };
+        let count = reader.read_u64::<LE>()?;
+        if count > file_size / SPAN_RECORD_SIZE {
+            return Ok(None);
+        }
+        let count = count as usize;
+        let mut table = Self {
+            arena,
+            ..Default::default()
+        };
+        table.spans.reserve(count);
+        for _ in 0..count {
+            let hash = reader.read_u64::<LE>()?;
+            let span = NameSpan {
+                offset: reader.read_u32::<LE>()?,
+                len: reader.read_u32::<LE>()?,
+            };
+            let (start, end) = (span.offset as usize, span.offset as usize + span.len as usize);
+            if table.arena.get(start..end).is_none() {
+                return Ok(None);
+            }
+            table.spans.insert(hash, span);
+        }
+
+        Ok(Some(table))
+    }
+
+    /// Write the cache to a temporary file and rename it over the old cache, so
+    /// an interrupted write or a concurrent reader never sees a partial cache.
+    fn replace_cache(&self, cache_path: &Path, key: &CacheKey) -> Result<()> {
+        if let Some(dir) = cache_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = cache_path.with_extension(format!("bin.{}.tmp", std::process::id()));
+        let result = self
+            .write_cache(&tmp_path, key)
+            .and_then(|()| Ok(std::fs::rename(&tmp_path, cache_path)?));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+
+        result
+    }
+
+    fn write_cache(&self, cache_path: &Path, key: &CacheKey) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(cache_path)?);
+        writer.write_all(&CACHE_MAGIC)?;
+        writer.write_u32::<LE>(CACHE_VERSION)?;
+        writer.write_u64::<LE>(key.list_size)?;
+        writer.write_u64::<LE>(key.list_mtime)?;
+        writer.write_u32::<LE>(key.options.len() as u32)?;
+        writer.write_all(key.options.as_bytes())?;
+
+        writer.write_u64::<LE>(self.arena.len() as u64)?;
+        writer.write_all(self.arena.as_bytes())?;
+        writer.write_u64::<LE>(self.spans.len() as u64)?;
+        for (hash, span) in &self.spans {
+            writer.write_u64::<LE>(*hash)?;
+            writer.write_u32::<LE>(span.offset)?;
+            writer.write_u32::<LE>(span.len)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Cache file of a list in `cache_dir`, lists of the same name in different
+/// directories are told apart by a digest of their absolute path.
+fn cache_file_path(cache_dir: &Path, list_path: &Path) -> PathBuf {
+    let full_path = std::path::absolute(list_path).unwrap_or_else(|_| list_path.to_path_buf());
+    let digest = to_hex(&content_digest(full_path.as_os_str().as_encoded_bytes()));
+    let name = list_path.file_name().unwrap_or_default().to_string_lossy();
+    cache_dir.join(format!("{}-{}.bin", name, &digest[..16]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filename::FileName;
+
+    #[test]
+    fn test_list_cache() {
+        let dir = std::env::temp_dir().join(format!("ree-pak-list-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list_path = dir.join("T.list");
+        std::fs::write(&list_path, "natives/stm/a.user.2\nnatives/stm/b.user.2\n").unwrap();
+
+        let cache_path = cache_file_path(&dir.join("cache"), &list_path);
+        assert_ne!(
+            cache_path,
+            cache_file_path(&dir.join("cache"), &dir.join("other/T.list"))
+        );
+        let built = FileNameTable::from_list_file_cached_at(&list_path, ListOptions::default(), &cache_path).unwrap();
+        assert!(cache_path.is_file());
+        assert_eq!(std::fs::read_dir(dir.join("cache")).unwrap().count(), 1);
+        let key = CacheKey::of_list(&list_path, ListOptions::default()).unwrap();
+        let cached = FileNameTable::read_cache(&cache_path, &key).unwrap().unwrap();
+        assert_eq!(cached.len(), built.len());
+        let hash = FileName::new("natives/stm/b.user.2").hash_mixed();
+        assert_eq!(cached.get_file_name(hash), Some("natives/stm/b.user.2"));
+
+        // a cache of other options is not used
+        let gbk = ListOptions::default().with_fallback_encoding(Some(encoding_rs::GBK));
+        let other_key = CacheKey::of_list(&list_path, gbk).unwrap();
+        assert!(FileNameTable::read_cache(&cache_path, &other_key).unwrap().is_none());
+
+        // a damaged arena length is not allocated, the cache is rebuilt
+        let mut cache = std::fs::read(&cache_path).unwrap();
+        let arena_len_offset = 28 + key.options.len();
+        cache[arena_len_offset..arena_len_offset + 8].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        std::fs::write(&cache_path, &cache).unwrap();
+        assert!(FileNameTable::read_cache(&cache_path, &key).unwrap().is_none());
+        let rebuilt = FileNameTable::from_list_file_cached_at(&list_path, ListOptions::default(), &cache_path).unwrap();
+        assert_eq!(rebuilt.len(), built.len());
+        assert!(FileNameTable::read_cache(&cache_path, &key).unwrap().is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
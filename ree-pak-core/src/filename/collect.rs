@@ -0,0 +1,207 @@
+//! Collect candidate file names to resolve unknown hashes.
+//!
+//! Candidates come from paths of an extracted tree and from path strings
+//! embedded in resource files. Resources reference other files without the
+//! `natives/<platform>/` prefix and the version suffix, so such references are
+//! expanded with the prefixes and the versions learned from known paths.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::error::Result;
+
+use super::hash_mixed_str;
+
+/// Path prefixes tried for resource references.
+pub const NATIVES_PREFIXES: [&str; 3] = ["natives/stm/", "natives/x64/", "natives/nsw/"];
+
+/// Resource types that embed paths of other resources.
+const SCANNED_EXTENSIONS: [&str; 3] = ["pfb", "scn", "user"];
+
+/// Minimum length of a scanned string to be considered a path.
+const MIN_PATH_LEN: usize = 5;
+
+/// Candidate file names, expanded to full paths when resolved.
+#[derive(Debug, Clone, Default)]
+pub struct Candidates {
+    paths: HashSet<String>,
+    references: HashSet<String>,
+    /// Version suffixes by lowercase extension, e.g. `mesh` -> `2109148288`.
+    versions: HashMap<String, BTreeSet<String>>,
+}
+
+impl Candidates {
+    /// Add a full path, its version suffix is learned for expanding references.
+    pub fn add_path(&mut self, path: &str) {
+        if let Some((ext, version)) = split_version(path) {
+            self.versions
+                .entry(ext.to_ascii_lowercase())
+                .or_default()
+                .insert(version.to_string());
+        }
+        self.paths.insert(path.to_string());
+    }
+
+    /// Add a path referenced by a resource, without prefix and version, e.g. `Art/Model/a.mesh`.
+    pub fn add_reference(&mut self, reference: &str) {
+        let reference = reference.trim_start_matches('@').trim_start_matches('/');
+        if !reference.is_empty() {
+            self.references.insert(reference.to_string());
+        }
+    }
+
+    /// Scan resource data for embedded UTF-16LE path strings and add them as references.
+    pub fn scan_resource(&mut self, data: &[u8]) {
+        for s in scan_utf16_strings(data) {
+            if looks_like_path(&s) {
+                self.add_reference(&s);
+            }
+        }
+    }
+
+    /// Add paths of all files of an extracted tree, skipping `_Unknown`.
+    ///
+    /// With `scan`, resources that embed paths are scanned for references as well.
+    pub fn add_dir<P>(&mut self, dir: P, scan: bool) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        for dir_entry in walkdir::WalkDir::new(dir) {
+            let dir_entry = dir_entry.map_err(std::io::Error::from)?;
+            if !dir_entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative) = dir_entry.path().strip_prefix(dir) else {
+                continue;
+            };
+            let path = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if !path.starts_with("_Unknown/") {
+                self.add_path(&path);
+            }
+            if scan && is_scanned_resource(&path) {
+                let data = std::fs::read(dir_entry.path())?;
+                self.scan_resource(&data);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// All full path candidates, including expanded references.
+    pub fn expand(&self) -> BTreeSet<String> {
+        let mut expanded: BTreeSet<String> = self.paths.iter().cloned().collect();
+        for reference in &self.references {
+            let versions = reference
+                .rsplit_once('.')
+                .and_then(|(_, ext)| self.versions.get(&ext.to_ascii_lowercase()));
+            for prefix in NATIVES_PREFIXES {
+                let base = if reference.to_ascii_lowercase().starts_with("natives/") {
+                    reference.to_string()
+                } else {
+                    format!("{}{}", prefix, reference)
+                };
+                for version in versions.into_iter().flatten() {
+                    expanded.insert(format!("{}.{}", base, version));
+                }
+                expanded.insert(base);
+            }
+        }
+
+        expanded
+    }
+
+    /// Candidates whose hash is one of `hashes`, sorted.
+    pub fn resolve(&self, hashes: &HashSet<u64>) -> Vec<String> {
+        let expanded: Vec<String> = self.expand().into_iter().collect();
+        let mut resolved: Vec<String> = crate::concurrency::install(|| {
+            expanded
+                .into_par_iter()
+                .filter(|candidate| hashes.contains(&hash_mixed_str(candidate)))
+                .collect()
+        });
+        resolved.sort();
+
+        resolved
+    }
+}
+
+/// Split the numeric version suffix of a path, e.g. `a.mesh.2109148288` -> (`mesh`, `2109148288`).
+fn split_version(path: &str) -> Option<(&str, &str)> {
+    let (rest, version) = path.rsplit_once('.')?;
+    if version.is_empty() || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (_, ext) = rest.rsplit_once('.')?;
+
+    Some((ext, version))
+}
+
+fn is_scanned_resource(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    file_name
+        .split('.')
+        .skip(1)
+        .any(|part| SCANNED_EXTENSIONS.iter().any(|ext| part.eq_ignore_ascii_case(ext)))
+}
+
+fn looks_like_path(s: &str) -> bool {
+    s.len() >= MIN_PATH_LEN
+        && s.contains('/')
+        && s.rsplit('/').next().is_some_and(|name| name.contains('.'))
+        && s.chars().all(|c| c.is_ascii_graphic() || c == ' ')
+}
+
+/// Find printable null-terminated UTF-16LE strings in binary data.
+fn scan_utf16_strings(data: &[u8]) -> Vec<String> {
+    let mut strings = vec![];
+    // strings may start at odd offsets
+    for align in 0..2 {
+        let mut current = String::new();
+        for unit in data[align..].chunks_exact(2) {
+            let c = u16::from_le_bytes([unit[0], unit[1]]);
+            match char::from_u32(c as u32) {
+                Some(c) if c.is_ascii_graphic() || c == ' ' => current.push(c),
+                _ => {
+                    if current.len() >= MIN_PATH_LEN {
+                        strings.push(std::mem::take(&mut current));
+                    } else {
+                        current.clear();
+                    }
+                }
+            }
+        }
+        if current.len() >= MIN_PATH_LEN {
+            strings.push(current);
+        }
+    }
+
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filename::FileName;
+
+    #[test]
+    fn test_resolve_references() {
+        let mut candidates = Candidates::default();
+        candidates.add_path("natives/stm/art/model/known.mesh.2109148288");
+        let reference: Vec<u8> = "Art/Model/other.mesh\0"
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        candidates.scan_resource(&[&[0xFF, 0x00, 0x01][..], &reference].concat());
+
+        let target = "natives/stm/Art/Model/other.mesh.2109148288";
+        let hashes = HashSet::from([FileName::new(target).hash_mixed()]);
+        assert_eq!(candidates.resolve(&hashes), [target]);
+    }
+}
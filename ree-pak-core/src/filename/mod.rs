@@ -0,0 +1,599 @@
+mod cache;
+pub mod collect;
+mod projects;
+#[cfg(feature = "remote-lists")]
+pub mod remote;
+mod sanitize;
+mod shorten;
+#[cfg(feature = "simd-hash")]
+mod simd;
+
+pub use projects::{available_projects, project_name, ProjectList, LIST_SUFFIXES};
+pub use sanitize::{is_invalid_name_char, is_relative_path, sanitize_path, INVALID_NAME_CHARS};
+pub use shorten::{shorten_path, LONG_PATH_DIR, MAX_COMPONENT_LEN};
+
+use std::{
+    collections::HashMap,
+    hash::BuildHasherDefault,
+    path::{Path, PathBuf},
+};
+
+use encoding_rs::{Encoding, UTF_16LE, UTF_8};
+use nohash::NoHashHasher;
+use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+
+use crate::error::{PakError, Result};
+
+/// Directive including another list, followed by its path relative to the including list.
+pub const INCLUDE_DIRECTIVE: &str = "@include";
+
+/// File name lookup table by mixed hash.
+///
+/// Names are interned in a single contiguous buffer, the map only stores spans into it.
+///
+/// List lines starting with `#` are comments. List files may include other
+/// lists with an `@include other.list` line, see [`FileNameTable::from_list_file`].
+#[derive(Debug, Clone, Default)]
+pub struct FileNameTable {
+    arena: String,
+    spans: HashMap<u64, NameSpan, BuildHasherDefault<NoHashHasher<u64>>>,
+    invalid_names: Vec<InvalidName>,
+    includes: Vec<PathBuf>,
+}
+
+/// How list lines that are not valid UTF-8 are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidNamePolicy {
+    /// Replace invalid sequences with U+FFFD.
+    #[default]
+    Lossy,
+    /// Leave the line out of the table.
+    Skip,
+    /// Fail loading the list.
+    Error,
+}
+
+/// Options for reading list files.
+///
+/// Lists starting with a UTF-8 or UTF-16 BOM are decoded accordingly, UTF-16LE
+/// without BOM is detected by its zero bytes. Other lists are read as UTF-8, or
+/// with the fallback encoding if they are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListOptions {
+    invalid_name_policy: InvalidNamePolicy,
+    fallback_encoding: Option<&'static Encoding>,
+}
+
+impl ListOptions {
+    pub fn with_invalid_name_policy(mut self, policy: InvalidNamePolicy) -> Self {
+        self.invalid_name_policy = policy;
+        self
+    }
+
+    /// Set the encoding of lists that are not valid UTF-8, e.g. `encoding_rs::GBK`.
+    pub fn with_fallback_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.fallback_encoding = encoding;
+        self
+    }
+
+    /// Set the fallback encoding by its WHATWG label, e.g. `gbk` or `shift_jis`.
+    pub fn with_fallback_encoding_label(self, label: &str) -> Result<Self> {
+        let encoding =
+            Encoding::for_label(label.as_bytes()).ok_or_else(|| PakError::UnknownEncoding(label.to_string()))?;
+        Ok(self.with_fallback_encoding(Some(encoding)))
+    }
+}
+
+/// A list line that is not valid UTF-8, recorded with its original bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidName {
+    /// 1-based line number in the list.
+    pub line: usize,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NameSpan {
+    offset: u32,
+    len: u32,
+}
+
+impl FileNameTable {
+    /// Load a list file and the lists it includes.
+    ///
+    /// Included lists are resolved relative to the directory of the including list,
+    /// recursively. Names of the including list take precedence over included ones
+    /// with the same hash. Including a list that is already being loaded is an error.
+    pub fn from_list_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_list_file_with_policy(path, InvalidNamePolicy::default())
+    }
+
+    pub fn from_list_file_with_policy<P>(path: P, policy: InvalidNamePolicy) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_list_file_with_options(path, ListOptions::default().with_invalid_name_policy(policy))
+    }
+
+    pub fn from_list_file_with_options<P>(path: P, options: ListOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_list_file(path.as_ref(), options, &mut vec![])
+    }
+
+    /// Load a list file with its includes, `loading` holds the lists being loaded to detect cycles.
+    fn load_list_file(path: &Path, options: ListOptions, loading: &mut Vec<PathBuf>) -> Result<Self> {
+        let list = std::fs::read(path)?;
+        let mut table = Self::from_list_bytes_with_options(list, options)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let includes: Vec<PathBuf> = table
+            .arena
+            .lines()
+            .filter_map(include_target)
+            .map(|target| dir.join(target))
+            .collect();
+        if includes.is_empty() {
+            return Ok(table);
+        }
+
+        loading.push(path.canonicalize()?);
+        for include in includes {
+            let invalid = |reason: &str| PakError::InvalidListInclude {
+                path: include.display().to_string(),
+                reason: reason.to_string(),
+            };
+            let canonical = include.canonicalize().map_err(|e| invalid(&e.to_string()))?;
+            if loading.contains(&canonical) {
+                return Err(invalid("the list includes itself"));
+            }
+            let included = Self::load_list_file(&include, options, loading)?;
+            table.merge(included);
+            table.includes.push(include);
+        }
+        loading.pop();
+
+        Ok(table)
+    }
+
+    /// Add the names of another table that are not in this one.
+    fn merge(&mut self, other: Self) {
+        let base = self.arena.len() as u32;
+        self.arena.push_str(&other.arena);
+        for (hash, span) in other.spans {
+            self.spans.entry(hash).or_insert(NameSpan {
+                offset: base + span.offset,
+                len: span.len,
+            });
+        }
+        self.invalid_names.extend(other.invalid_names);
+        self.includes.extend(other.includes);
+    }
+
+    /// Create a table from raw list content, detecting its encoding.
+    pub fn from_list_bytes_with_options(list: Vec<u8>, options: ListOptions) -> Result<Self> {
+        let (encoding, bom_len) = match Encoding::for_bom(&list) {
+            Some((encoding, bom_len)) => (Some(encoding), bom_len),
+            None if looks_like_utf16le(&list) => (Some(UTF_16LE), 0),
+            None => (None, 0),
+        };
+        let encoding = match encoding {
+            Some(encoding) if encoding != UTF_8 => encoding,
+            _ => {
+                let mut list = list;
+                list.drain(..bom_len);
+                match (String::from_utf8(list), options.fallback_encoding) {
+                    (Ok(list), _) => return Ok(Self::from_list(list)),
+                    (Err(e), None) => return Self::from_list_bytes(e.into_bytes(), options.invalid_name_policy),
+                    (Err(e), Some(fallback)) => return Ok(Self::from_list(decode(fallback, e.as_bytes()))),
+                }
+            }
+        };
+
+        Ok(Self::from_list(decode(encoding, &list[bom_len..])))
+    }
+
+    /// Create a table from raw list content, handling lines that are not valid UTF-8 by `policy`.
+    ///
+    /// Invalid lines are recorded and available from [`FileNameTable::invalid_names`].
+    pub fn from_list_bytes(list: Vec<u8>, policy: InvalidNamePolicy) -> Result<Self> {
+        let list = match String::from_utf8(list) {
+            Ok(list) => return Ok(Self::from_list(list)),
+            Err(e) => e.into_bytes(),
+        };
+
+        let mut valid = String::with_capacity(list.len());
+        let mut invalid_names = vec![];
+        for (i, line) in list.split(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            match std::str::from_utf8(line) {
+                Ok(line) => valid.push_str(line),
+                Err(_) => {
+                    if policy == InvalidNamePolicy::Error {
+                        return Err(PakError::InvalidFileName { line: i + 1 });
+                    }
+                    if policy == InvalidNamePolicy::Lossy {
+                        valid.push_str(&String::from_utf8_lossy(line));
+                    }
+                    invalid_names.push(InvalidName {
+                        line: i + 1,
+                        bytes: line.to_vec(),
+                    });
+                }
+            }
+            valid.push('\n');
+        }
+
+        let mut table = Self::from_list(valid);
+        table.invalid_names = invalid_names;
+        Ok(table)
+    }
+
+    /// Create a table from list content, one file name per line.
+    ///
+    /// The content is kept as the name buffer, so no copy is made per line.
+    pub fn from_list(list: String) -> Self {
+        let base = list.as_ptr() as usize;
+        let lines: Vec<(&str, NameSpan)> = list
+            .lines()
+            .filter(|line| !line.is_empty() && !is_list_directive(line))
+            .map(|line| {
+                let span = NameSpan {
+                    offset: (line.as_ptr() as usize - base) as u32,
+                    len: line.len() as u32,
+                };
+                (line, span)
+            })
+            .collect();
+        let spans = crate::concurrency::install(|| {
+            lines
+                .par_chunks(HASH_BATCH_SIZE)
+                .flat_map_iter(|chunk| {
+                    let names: Vec<&str> = chunk.iter().map(|(line, _)| *line).collect();
+                    hash_mixed_batch(&names)
+                        .into_iter()
+                        .zip(chunk.iter().map(|(_, span)| *span))
+                })
+                .collect()
+        });
+
+        Self {
+            arena: list,
+            spans,
+            invalid_names: vec![],
+            includes: vec![],
+        }
+    }
+
+    pub fn push_str(&mut self, file_name: &str) {
+        let span = NameSpan {
+            offset: self.arena.len() as u32,
+            len: file_name.len() as u32,
+        };
+        self.arena.push_str(file_name);
+        self.spans.insert(hash_mixed_str(file_name), span);
+    }
+
+    pub fn get_file_name(&self, hash: u64) -> Option<&str> {
+        self.spans
+            .get(&hash)
+            .map(|span| &self.arena[span.offset as usize..(span.offset + span.len) as usize])
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Lines of the loaded list that were not valid UTF-8.
+    #[inline]
+    pub fn invalid_names(&self) -> &[InvalidName] {
+        &self.invalid_names
+    }
+
+    /// Lists included by the loaded list file, directly or through other includes.
+    #[inline]
+    pub fn includes(&self) -> &[PathBuf] {
+        &self.includes
+    }
+}
+
+/// Whether a list line is a comment or an include rather than a file name.
+fn is_list_directive(line: &str) -> bool {
+    line.starts_with('#') || include_target(line).is_some()
+}
+
+/// Path of an `@include` line.
+fn include_target(line: &str) -> Option<&str> {
+    line.strip_prefix(INCLUDE_DIRECTIVE)
+        .filter(|rest| rest.starts_with([' ', '\t']))
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+}
+
+/// Decode without BOM handling, unmappable sequences are replaced.
+fn decode(encoding: &'static Encoding, bytes: &[u8]) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Paths are mostly ASCII, so UTF-16LE text has zeros at every odd byte.
+fn looks_like_utf16le(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(512) & !1];
+    !sample.is_empty() && sample.chunks_exact(2).all(|unit| unit[1] == 0 && unit[0] != 0)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileName {
+    name: String,
+}
+
+impl FileName {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn hash_lower_case(&self) -> u32 {
+        hash_utf16(&self.name.to_lowercase())
+    }
+
+    pub fn hash_upper_case(&self) -> u32 {
+        hash_utf16(&self.name.to_uppercase())
+    }
+
+    pub fn hash_mixed(&self) -> u64 {
+        hash_mixed_str(&self.name)
+    }
+
+    pub fn mix_hash(lower: u32, upper: u32) -> u64 {
+        let upper = upper as u64;
+        let lower = lower as u64;
+
+        upper << 32 | lower
+    }
+}
+
+/// Mixed hashes of many names, in order.
+///
+/// With the `simd-hash` feature, ASCII names are hashed two at a time with SIMD.
+pub fn hash_mixed_batch(names: &[&str]) -> Vec<u64> {
+    #[cfg(feature = "simd-hash")]
+    return simd::hash_mixed_batch(names);
+    #[cfg(not(feature = "simd-hash"))]
+    names.iter().map(|name| hash_mixed_str(name)).collect()
+}
+
+fn hash_mixed_str(name: &str) -> u64 {
+    if name.is_ascii() {
+        return hash_mixed_ascii(name.as_bytes());
+    }
+    FileName::mix_hash(hash_utf16(&name.to_lowercase()), hash_utf16(&name.to_uppercase()))
+}
+
+const MURMUR3_SEED: u32 = 0xFFFFFFFF;
+/// Names hashed per batch when loading lists.
+const HASH_BATCH_SIZE: usize = 256;
+
+/// Case folding tables of ASCII bytes.
+const ASCII_LOWER: [u8; 128] = ascii_case_table(b'A', b'Z', 0x20u8);
+const ASCII_UPPER: [u8; 128] = ascii_case_table(b'a', b'z', 0xE0u8);
+
+const fn ascii_case_table(first: u8, last: u8, delta: u8) -> [u8; 128] {
+    let mut table = [0; 128];
+    let mut i = 0;
+    while i < 128 {
+        let b = i as u8;
+        table[i] = if b >= first && b <= last {
+            b.wrapping_add(delta)
+        } else {
+            b
+        };
+        i += 1;
+    }
+    table
+}
+
+/// Hash both cases of an ASCII name in one pass, without building UTF-16 strings.
+///
+/// Each byte is one UTF-16LE code unit with a zero high byte, so a murmur3 block
+/// holds two characters.
+fn hash_mixed_ascii(name: &[u8]) -> u64 {
+    hash_mixed_ascii_from(
+        name,
+        Murmur3::new(MURMUR3_SEED),
+        Murmur3::new(MURMUR3_SEED),
+        name.len() as u32 * 2,
+    )
+}
+
+/// Continue hashing both cases with the remaining bytes of a name, `byte_len` is the UTF-16 length of the full name.
+fn hash_mixed_ascii_from(rest: &[u8], mut lower: Murmur3, mut upper: Murmur3, byte_len: u32) -> u64 {
+    let mut pairs = rest.chunks_exact(2);
+    for pair in &mut pairs {
+        let (a, b) = (pair[0] as usize, pair[1] as usize);
+        lower.block(ASCII_LOWER[a] as u32 | (ASCII_LOWER[b] as u32) << 16);
+        upper.block(ASCII_UPPER[a] as u32 | (ASCII_UPPER[b] as u32) << 16);
+    }
+    let (lower, upper) = match pairs.remainder() {
+        [c] => (
+            lower.finish(Some(ASCII_LOWER[*c as usize] as u32), byte_len),
+            upper.finish(Some(ASCII_UPPER[*c as usize] as u32), byte_len),
+        ),
+        _ => (lower.finish(None, byte_len), upper.finish(None, byte_len)),
+    };
+
+    FileName::mix_hash(lower, upper)
+}
+
+/// Incremental murmur3 x86 32-bit over 4-byte blocks.
+struct Murmur3 {
+    h: u32,
+}
+
+impl Murmur3 {
+    const C1: u32 = 0xCC9E2D51;
+    const C2: u32 = 0x1B873593;
+    const N: u32 = 0xE6546B64;
+
+    fn new(seed: u32) -> Self {
+        Self { h: seed }
+    }
+
+    #[inline]
+    fn mix_k(k: u32) -> u32 {
+        k.wrapping_mul(Self::C1).rotate_left(15).wrapping_mul(Self::C2)
+    }
+
+    #[inline]
+    fn block(&mut self, k: u32) {
+        self.h ^= Self::mix_k(k);
+        self.h = self.h.rotate_left(13).wrapping_mul(5).wrapping_add(Self::N);
+    }
+
+    /// Finish with the trailing bytes of the input as a little-endian value, `len` is the input length in bytes.
+    fn finish(mut self, tail: Option<u32>, len: u32) -> u32 {
+        if let Some(tail) = tail {
+            self.h ^= Self::mix_k(tail);
+        }
+        let mut h = self.h ^ len;
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x85EBCA6B);
+        h ^= h >> 13;
+        h = h.wrapping_mul(0xC2B2AE35);
+        h ^ (h >> 16)
+    }
+}
+
+fn hash_utf16(name: &str) -> u32 {
+    let bytes: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+
+    murmur3_hash(&bytes[..]).unwrap()
+}
+
+pub fn murmur3_hash<R: std::io::Read>(mut reader: R) -> Result<u32> {
+    Ok(murmur3::murmur3_32(&mut reader, 0xFFFFFFFF)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file_name() {
+        let filename = FileName::new("natives/stm/camera/collisionfilter/defaultcamera.cfil.7");
+        assert_eq!(filename.hash_lower_case(), 0x65B486A1);
+        assert_eq!(filename.hash_upper_case(), 0x958EDD0C);
+        assert_eq!(filename.hash_mixed(), 0x958EDD0C65B486A1);
+    }
+
+    #[test]
+    fn test_hash_ascii_fast_path() {
+        // the ASCII fast path matches hashing UTF-16 strings, for even and odd lengths
+        for name in ["natives/STM/a.user.2", "natives/STM/ab.user.2", "", "a"] {
+            let expected = FileName::mix_hash(hash_utf16(&name.to_lowercase()), hash_utf16(&name.to_uppercase()));
+            assert_eq!(hash_mixed_ascii(name.as_bytes()), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_table_from_list() {
+        let list = "natives/stm/camera/collisionfilter/defaultcamera.cfil.7\r\n\nnatives/stm/other.user.2\n";
+        let mut table = FileNameTable::from_list(list.to_string());
+        table.push_str("natives/stm/pushed.user.2");
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.get_file_name(0x958EDD0C65B486A1),
+            Some("natives/stm/camera/collisionfilter/defaultcamera.cfil.7")
+        );
+        let pushed = FileName::new("natives/stm/pushed.user.2");
+        assert_eq!(
+            table.get_file_name(pushed.hash_mixed()),
+            Some("natives/stm/pushed.user.2")
+        );
+    }
+
+    #[test]
+    fn test_table_includes() {
+        let dir = std::env::temp_dir().join(format!("ree-pak-list-includes-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("base")).unwrap();
+        std::fs::write(
+            dir.join("base/engine.list"),
+            "# shared engine paths\nnatives/stm/engine.user.2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("game.list"),
+            "@include base/engine.list\r\n# game paths\r\nnatives/stm/game.user.2\r\n",
+        )
+        .unwrap();
+
+        let table = FileNameTable::from_list_file(dir.join("game.list")).unwrap();
+        assert_eq!(table.len(), 2);
+        for name in ["natives/stm/engine.user.2", "natives/stm/game.user.2"] {
+            assert_eq!(table.get_file_name(FileName::new(name).hash_mixed()), Some(name));
+        }
+        assert_eq!(table.includes(), [dir.join("base/engine.list")]);
+
+        std::fs::write(dir.join("base/engine.list"), "@include ../game.list\n").unwrap();
+        assert!(matches!(
+            FileNameTable::from_list_file(dir.join("game.list")),
+            Err(PakError::InvalidListInclude { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_table_invalid_names() {
+        let list = b"natives/stm/a\xFF.user.2\r\nnatives/stm/other.user.2\n".to_vec();
+
+        let lossy = FileNameTable::from_list_bytes(list.clone(), InvalidNamePolicy::Lossy).unwrap();
+        assert_eq!(lossy.len(), 2);
+        let replaced = FileName::new("natives/stm/a\u{FFFD}.user.2");
+        assert!(lossy.get_file_name(replaced.hash_mixed()).is_some());
+        assert_eq!(
+            lossy.invalid_names(),
+            [InvalidName {
+                line: 1,
+                bytes: b"natives/stm/a\xFF.user.2".to_vec()
+            }]
+        );
+
+        let skip = FileNameTable::from_list_bytes(list.clone(), InvalidNamePolicy::Skip).unwrap();
+        assert_eq!(skip.len(), 1);
+        assert!(FileNameTable::from_list_bytes(list, InvalidNamePolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_table_encodings() {
+        let name = "natives/stm/other.user.2";
+        let utf16le = |bom: &[u8]| -> Vec<u8> {
+            let units = name.encode_utf16().flat_map(|c| c.to_le_bytes());
+            bom.iter().copied().chain(units).collect()
+        };
+        let hash = FileName::new(name).hash_mixed();
+        for list in [
+            utf16le(&[0xFF, 0xFE]),
+            utf16le(&[]),
+            [&[0xEF, 0xBB, 0xBF], name.as_bytes()].concat(),
+        ] {
+            let table = FileNameTable::from_list_bytes_with_options(list, ListOptions::default()).unwrap();
+            assert_eq!(table.get_file_name(hash), Some(name));
+        }
+
+        let (gbk, _, _) = encoding_rs::GBK.encode("natives/stm/\u{4E2D}.user.2");
+        let options = ListOptions::default().with_fallback_encoding_label("gbk").unwrap();
+        let table = FileNameTable::from_list_bytes_with_options(gbk.into_owned(), options).unwrap();
+        let hash = FileName::new("natives/stm/\u{4E2D}.user.2").hash_mixed();
+        assert!(table.get_file_name(hash).is_some());
+    }
+}
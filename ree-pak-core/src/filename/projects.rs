@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// File name suffixes of project lists, the project name is the part before it.
+pub const LIST_SUFFIXES: [&str; 2] = [".list", ".list.zst"];
+
+/// A project list file found by [`available_projects`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectList {
+    pub project: String,
+    pub path: PathBuf,
+    /// Size of the list file on disk.
+    pub file_size: u64,
+    /// Number of non-empty lines, `None` for compressed lists in builds without zstd.
+    pub entry_count: Option<usize>,
+}
+
+/// Find the project lists in `dirs`, sorted by project name.
+///
+/// Directories that do not exist are skipped. A project found in several
+/// directories is taken from the first one, so earlier directories override
+/// later ones.
+pub fn available_projects<I, P>(dirs: I) -> Result<Vec<ProjectList>>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut lists: Vec<ProjectList> = vec![];
+    for dir in dirs {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            continue;
+        }
+        let mut found = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(project) = project_name(&path) else {
+                continue;
+            };
+            if lists.iter().chain(&found).any(|list| list.project == project) {
+                continue;
+            }
+            found.push(ProjectList {
+                project: project.to_string(),
+                file_size: entry.metadata()?.len(),
+                entry_count: count_entries(&path)?,
+                path,
+            });
+        }
+        lists.append(&mut found);
+    }
+    lists.sort_by(|a, b| a.project.cmp(&b.project));
+
+    Ok(lists)
+}
+
+/// Project name of a list file path, `None` if it is not a list.
+pub fn project_name(path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?.to_str()?;
+    LIST_SUFFIXES
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .filter(|project| !project.is_empty())
+}
+
+fn count_entries(path: &Path) -> Result<Option<usize>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        #[cfg(feature = "zstd")]
+        return Ok(Some(count_lines(BufReader::new(zstd::Decoder::new(file)?))?));
+        #[cfg(not(feature = "zstd"))]
+        return Ok(None);
+    }
+
+    Ok(Some(count_lines(BufReader::new(file))?))
+}
+
+/// Count lines with content, zero bytes next to the newlines of UTF-16 lists are ignored.
+fn count_lines<R: Read>(mut reader: BufReader<R>) -> Result<usize> {
+    let mut count = 0;
+    let mut line_has_content = false;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        for &byte in buf {
+            match byte {
+                b'\n' => {
+                    count += line_has_content as usize;
+                    line_has_content = false;
+                }
+                b'\r' | 0 => {}
+                _ => line_has_content = true,
+            }
+        }
+        let len = buf.len();
+        reader.consume(len);
+    }
+
+    Ok(count + line_has_content as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available_projects() {
+        let root = std::env::temp_dir().join(format!("ree-pak-projects-{}", std::process::id()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(first.join("RE4.list"), "natives/a.txt\r\nnatives/b.txt\r\n\r\n").unwrap();
+        std::fs::write(first.join("notes.txt"), "not a list").unwrap();
+        std::fs::write(second.join("RE4.list"), "natives/a.txt\n").unwrap();
+        std::fs::write(second.join("MHWs.list"), "natives/a.txt").unwrap();
+
+        let lists = available_projects([&first, &second, &root.join("missing")]).unwrap();
+        let summary: Vec<_> = lists
+            .iter()
+            .map(|list| (list.project.as_str(), list.entry_count, list.file_size))
+            .collect();
+        assert_eq!(summary, [("MHWs", Some(1), 13), ("RE4", Some(2), 32)]);
+        assert_eq!(lists[1].path, first.join("RE4.list"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
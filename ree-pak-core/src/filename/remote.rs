@@ -0,0 +1,174 @@
+//! Download list files described by a manifest and cache them locally.
+//!
+//! The manifest is a JSON document mapping project names to list files:
+//!
+//! ```json
+//! {
+//!   "lists": {
+//!     "MHRS_PC_Demo": { "url": "https://example.com/MHRS_PC_Demo.list", "sha256": "..." }
+//!   }
+//! }
+//! ```
+//!
+//! Cached lists are stored as `<cache dir>/<project>.list`. Project names may only
+//! contain ASCII letters, digits, `_` and `-`, so a manifest cannot name files
+//! outside the cache directory.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{PakError, Result};
+
+use super::FileNameTable;
+
+/// List files available for download.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListManifest {
+    pub lists: BTreeMap<String, RemoteList>,
+}
+
+/// A downloadable list file of a project.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteList {
+    pub url: String,
+    /// Hex SHA-256 of the list file.
+    pub sha256: String,
+}
+
+/// Source of remote list files and the directory they are cached in.
+#[derive(Debug, Clone)]
+pub struct RemoteLists {
+    manifest_url: String,
+    cache_dir: PathBuf,
+}
+
+impl ListManifest {
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| PakError::RemoteList(format!("invalid manifest: {}", e)))
+    }
+}
+
+impl RemoteLists {
+    pub fn new<S, P>(manifest_url: S, cache_dir: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        Self {
+            manifest_url: manifest_url.into(),
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    pub fn fetch_manifest(&self) -> Result<ListManifest> {
+        ListManifest::from_json(&download(&self.manifest_url)?)
+    }
+
+    /// Path of the cached list of a project, which may not exist yet.
+    ///
+    /// Fails for project names that are not valid file names, see the module docs.
+    pub fn cached_path(&self, project: &str) -> Result<PathBuf> {
+        if !is_valid_project_name(project) {
+            return Err(PakError::RemoteList(format!("invalid project name `{}`", project)));
+        }
+
+        Ok(self.cache_dir.join(format!("{}.list", project)))
+    }
+
+    /// Download the list of a project, verify its hash and replace the cached copy.
+    pub fn update(&self, manifest: &ListManifest, project: &str) -> Result<PathBuf> {
+        let path = self.cached_path(project)?;
+        let remote = manifest
+            .lists
+            .get(project)
+            .ok_or_else(|| PakError::RemoteList(format!("project `{}` is not in the manifest", project)))?;
+        let data = download(&remote.url)?;
+        verify_sha256(project, &data, &remote.sha256)?;
+
+        std::fs::create_dir_all(&self.cache_dir)?;
+        // replace atomically, an interrupted download keeps the previous list
+        let tmp_path = path.with_extension("list.tmp");
+        std::fs::write(&tmp_path, &data)?;
+        std::fs::rename(&tmp_path, &path)?;
+
+        Ok(path)
+    }
+}
+
+impl FileNameTable {
+    /// Load the list of a project from the cache, downloading it first if not cached.
+    pub fn fetch(project: &str, remote: &RemoteLists) -> Result<Self> {
+        let path = remote.cached_path(project)?;
+        if !path.is_file() {
+            let manifest = remote.fetch_manifest()?;
+            remote.update(&manifest, project)?;
+        }
+
+        Self::from_list_file(path)
+    }
+}
+
+/// Whether a project name is non-empty and only has ASCII letters, digits, `_` and `-`.
+fn is_valid_project_name(project: &str) -> bool {
+    !project.is_empty()
+        && project
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| PakError::RemoteList(format!("failed to download `{}`: {}", url, e)))?;
+    let mut data = vec![];
+    response.into_reader().read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+fn verify_sha256(project: &str, data: &[u8], expected: &str) -> Result<()> {
+    let found: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    if !found.eq_ignore_ascii_case(expected.trim()) {
+        return Err(PakError::ListHashMismatch {
+            project: project.to_string(),
+            expected: expected.to_string(),
+            found,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_and_hash() {
+        let manifest = ListManifest::from_json(
+            br#"{"lists": {"T": {"url": "https://example.com/T.list", "sha256": "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824"}}}"#,
+        )
+        .unwrap();
+        let remote = &manifest.lists["T"];
+        assert_eq!(remote.url, "https://example.com/T.list");
+
+        assert!(verify_sha256("T", b"hello", &remote.sha256).is_ok());
+        assert!(verify_sha256("T", b"hello!", &remote.sha256).is_err());
+    }
+
+    #[test]
+    fn test_cached_path() {
+        let remote = RemoteLists::new("https://example.com/lists.json", "cache");
+        assert_eq!(
+            remote.cached_path("MHRS_PC_Demo-1").unwrap(),
+            PathBuf::from("cache").join("MHRS_PC_Demo-1.list")
+        );
+        for project in ["", "../../x", "a/b", "a\\b", "..", "a.b", "C:x"] {
+            assert!(remote.cached_path(project).is_err(), "{}", project);
+        }
+    }
+}
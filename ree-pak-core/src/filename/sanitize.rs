@@ -0,0 +1,64 @@
+//! Replacement of characters that cannot appear in Windows file names.
+//!
+//! Names from lists occasionally contain such characters, writing them would
+//! fail with an OS error on Windows. Names that would leave the output
+//! directory are detected with [`is_relative_path`].
+
+use std::path::{Component, Path};
+
+/// Characters not allowed in Windows file names, besides control characters.
+pub const INVALID_NAME_CHARS: [char; 8] = ['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+/// Whether a character is not allowed in Windows file names.
+pub fn is_invalid_name_char(c: char) -> bool {
+    c.is_ascii_control() || INVALID_NAME_CHARS.contains(&c)
+}
+
+/// Replace characters not allowed in Windows file names in a relative path with
+/// `/` separators, `None` if it has none.
+pub fn sanitize_path(path: &str, replacement: char) -> Option<String> {
+    if !path.contains(is_invalid_name_char) {
+        return None;
+    }
+
+    Some(
+        path.chars()
+            .map(|c| if is_invalid_name_char(c) { replacement } else { c })
+            .collect(),
+    )
+}
+
+/// Whether a path stays under the directory it is joined to: not empty, not
+/// absolute and without `..` or drive prefix components.
+pub fn is_relative_path(path: &str) -> bool {
+    let mut components = Path::new(path).components().peekable();
+    components.peek().is_some()
+        && components.all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_path() {
+        assert_eq!(sanitize_path("natives/stm/a.user.2", '_'), None);
+        assert_eq!(
+            sanitize_path("natives/stm/a:b?\u{7}*.user.2", '_').unwrap(),
+            "natives/stm/a_b___.user.2"
+        );
+        assert_eq!(
+            sanitize_path("natives\\stm/\"x\".tex", '-').unwrap(),
+            "natives-stm/-x-.tex"
+        );
+    }
+
+    #[test]
+    fn test_is_relative_path() {
+        assert!(is_relative_path("natives/stm/a.user.2"));
+        assert!(is_relative_path("./_Unknown/0123"));
+        for path in ["", "/etc/passwd", "natives/../../a.txt", ".."] {
+            assert!(!is_relative_path(path), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Deterministic shortening of resolved paths that exceed filesystem limits.
+//!
+//! Lengths are counted in UTF-8 bytes, which is never less than the UTF-16
+//! units Windows counts, so a path within budget fits on both.
+
+use super::FileName;
+
+/// Longest file or directory name on common filesystems.
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+/// Directory of paths that cannot be shortened by truncating their file name.
+pub const LONG_PATH_DIR: &str = "_Long";
+
+/// Extensions longer than this are treated as part of the name.
+const MAX_EXTENSION_LEN: usize = 64;
+
+/// Shorten a relative path with `/` separators to at most `max_len` bytes and
+/// [`MAX_COMPONENT_LEN`] bytes per component, `None` if it already fits.
+///
+/// Over-long directory names and the file name are truncated and suffixed with
+/// `~` and a hash of the full path, keeping the extension. If the directories
+/// alone exceed the budget, the path becomes `_Long/<hash><extension>`. The
+/// hash is the mixed hash of the original path, so for listed names it is the
+/// hash of their entry.
+pub fn shorten_path(path: &str, max_len: usize) -> Option<String> {
+    if fits(path, max_len) {
+        return None;
+    }
+
+    let hash = FileName::new(path).hash_mixed();
+    let tag = format!("~{:016X}", hash);
+    let (dirs, file_name) = match path.rsplit_once('/') {
+        Some((dirs, file_name)) => (Some(dirs), file_name),
+        None => (None, path),
+    };
+    let (stem, extension) = split_extension(file_name);
+
+    let dirs: Vec<String> = dirs
+        .map(|dirs| dirs.split('/').map(|dir| shorten_component(dir, &tag)).collect())
+        .unwrap_or_default();
+    let dirs_len: usize = dirs.iter().map(|dir| dir.len() + 1).sum();
+    let name_budget = max_len.saturating_sub(dirs_len).min(MAX_COMPONENT_LEN);
+    if name_budget > tag.len() + extension.len() {
+        let stem = truncate(stem, name_budget - tag.len() - extension.len());
+        let mut shortened = dirs.join("/");
+        if !shortened.is_empty() {
+            shortened.push('/');
+        }
+        shortened.push_str(&format!("{}{}{}", stem, tag, extension));
+        return Some(shortened);
+    }
+
+    Some(format!("{}/{:016X}{}", LONG_PATH_DIR, hash, extension))
+}
+
+fn fits(path: &str, max_len: usize) -> bool {
+    path.len() <= max_len && path.split('/').all(|component| component.len() <= MAX_COMPONENT_LEN)
+}
+
+/// Split a file name at its first dot, e.g. `a.mesh.2109148288` into `a` and `.mesh.2109148288`.
+fn split_extension(file_name: &str) -> (&str, &str) {
+    match file_name.find('.') {
+        Some(pos) if pos > 0 && file_name.len() - pos <= MAX_EXTENSION_LEN => file_name.split_at(pos),
+        _ => (file_name, ""),
+    }
+}
+
+fn shorten_component(component: &str, tag: &str) -> String {
+    if component.len() <= MAX_COMPONENT_LEN {
+        return component.to_string();
+    }
+
+    format!("{}{}", truncate(component, MAX_COMPONENT_LEN - tag.len()), tag)
+}
+
+/// Truncate a string to at most `max_len` bytes at a char boundary.
+fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shorten_path() {
+        assert_eq!(shorten_path("natives/stm/a.user.2", 64), None);
+
+        let long_name = format!("natives/stm/{}.mesh.2109148288", "x".repeat(300));
+        let hash = FileName::new(&long_name).hash_mixed();
+        let shortened = shorten_path(&long_name, 4096).unwrap();
+        assert!(shortened.starts_with("natives/stm/xxx"));
+        assert!(shortened.ends_with(&format!("~{:016X}.mesh.2109148288", hash)));
+        assert!(fits(&shortened, 4096));
+        // deterministic
+        assert_eq!(shorten_path(&long_name, 4096).unwrap(), shortened);
+
+        let shortened = shorten_path(&format!("natives/stm/{}.tex", "y".repeat(50)), 40).unwrap();
+        assert!(fits(&shortened, 40));
+        assert!(shortened.starts_with("natives/stm/yyy"));
+
+        let long_dirs = format!("{}file.tex", "d/".repeat(40));
+        let hash = FileName::new(&long_dirs).hash_mixed();
+        assert_eq!(
+            shorten_path(&long_dirs, 32).unwrap(),
+            format!("_Long/{:016X}.tex", hash)
+        );
+
+        // multi-byte chars are not split
+        let shortened = shorten_path(&"é".repeat(200), 4096).unwrap();
+        assert!(fits(&shortened, 4096));
+    }
+}
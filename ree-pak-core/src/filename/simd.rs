@@ -0,0 +1,198 @@
+//! Murmur3 over UTF-16 with SIMD, hashing two ASCII names in both cases at once.
+//!
+//! The four lanes hold the lower and upper case hashes of two names. Blocks
+//! both names have are mixed in SIMD registers, the rest of the longer name
+//! continues in scalar code from its lane state. SSE2 or NEON is selected at
+//! runtime, other targets use the scalar lanes.
+
+use super::{hash_mixed_ascii_from, hash_mixed_str, Murmur3, ASCII_LOWER, ASCII_UPPER, MURMUR3_SEED};
+
+pub(super) fn hash_mixed_batch(names: &[&str]) -> Vec<u64> {
+    let mut hashes = Vec::with_capacity(names.len());
+    let mut pairs = names.chunks_exact(2);
+    for pair in &mut pairs {
+        let (a, b) = (pair[0], pair[1]);
+        if a.is_ascii() && b.is_ascii() {
+            let (hash_a, hash_b) = hash_mixed_ascii_pair(a.as_bytes(), b.as_bytes());
+            hashes.push(hash_a);
+            hashes.push(hash_b);
+        } else {
+            hashes.push(hash_mixed_str(a));
+            hashes.push(hash_mixed_str(b));
+        }
+    }
+    hashes.extend(pairs.remainder().iter().map(|name| hash_mixed_str(name)));
+
+    hashes
+}
+
+fn hash_mixed_ascii_pair(a: &[u8], b: &[u8]) -> (u64, u64) {
+    let blocks = a.len().min(b.len()) / 2;
+    let [lower_a, upper_a, lower_b, upper_b] = mix_blocks(a, b, blocks);
+    let finish = |name: &[u8], lower: u32, upper: u32| {
+        hash_mixed_ascii_from(
+            &name[blocks * 2..],
+            Murmur3 { h: lower },
+            Murmur3 { h: upper },
+            name.len() as u32 * 2,
+        )
+    };
+
+    (finish(a, lower_a, upper_a), finish(b, lower_b, upper_b))
+}
+
+/// Block `i` of two names as lanes `[lower a, upper a, lower b, upper b]`.
+#[inline]
+fn block_lanes(a: &[u8], b: &[u8], i: usize) -> [u32; 4] {
+    let (a0, a1) = (a[i * 2] as usize, a[i * 2 + 1] as usize);
+    let (b0, b1) = (b[i * 2] as usize, b[i * 2 + 1] as usize);
+    [
+        ASCII_LOWER[a0] as u32 | (ASCII_LOWER[a1] as u32) << 16,
+        ASCII_UPPER[a0] as u32 | (ASCII_UPPER[a1] as u32) << 16,
+        ASCII_LOWER[b0] as u32 | (ASCII_LOWER[b1] as u32) << 16,
+        ASCII_UPPER[b0] as u32 | (ASCII_UPPER[b1] as u32) << 16,
+    ]
+}
+
+/// Mix the first `blocks` blocks of two names, returning the lane states.
+fn mix_blocks(a: &[u8], b: &[u8], blocks: usize) -> [u32; 4] {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if std::arch::is_x86_feature_detected!("sse2") {
+        // SAFETY: SSE2 is available
+        return unsafe { sse2::mix_blocks(a, b, blocks) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: NEON is available
+        return unsafe { neon::mix_blocks(a, b, blocks) };
+    }
+
+    scalar_mix_blocks(a, b, blocks)
+}
+
+fn scalar_mix_blocks(a: &[u8], b: &[u8], blocks: usize) -> [u32; 4] {
+    let mut lanes = [MURMUR3_SEED; 4].map(Murmur3::new);
+    for i in 0..blocks {
+        for (lane, k) in lanes.iter_mut().zip(block_lanes(a, b, i)) {
+            lane.block(k);
+        }
+    }
+
+    lanes.map(|lane| lane.h)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::{Murmur3, MURMUR3_SEED};
+
+    /// Multiply 32-bit lanes, SSE2 only multiplies the even lanes into 64 bits.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn mullo(a: __m128i, b: __m128i) -> __m128i {
+        let even = _mm_mul_epu32(a, b);
+        let odd = _mm_mul_epu32(_mm_srli_epi64::<32>(a), _mm_srli_epi64::<32>(b));
+        _mm_unpacklo_epi32(
+            _mm_shuffle_epi32::<0b00_00_10_00>(even),
+            _mm_shuffle_epi32::<0b00_00_10_00>(odd),
+        )
+    }
+
+    /// Mask of the 16-bit lanes within `first..=last`.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn in_range(units: __m128i, first: u8, last: u8) -> __m128i {
+        _mm_and_si128(
+            _mm_cmpgt_epi16(units, _mm_set1_epi16(first as i16 - 1)),
+            _mm_cmplt_epi16(units, _mm_set1_epi16(last as i16 + 1)),
+        )
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn mix_blocks(a: &[u8], b: &[u8], blocks: usize) -> [u32; 4] {
+        let c1 = _mm_set1_epi32(Murmur3::C1 as i32);
+        let c2 = _mm_set1_epi32(Murmur3::C2 as i32);
+        let n = _mm_set1_epi32(Murmur3::N as i32);
+        let mut h = _mm_set1_epi32(MURMUR3_SEED as i32);
+        // case folding of the UTF-16 units, adding 0x20 in lower lanes and subtracting it in upper lanes
+        let lower_lanes = _mm_set_epi32(0, -1, 0, -1);
+        let case_bit = _mm_set1_epi16(0x20);
+        let to_lower = _mm_and_si128(lower_lanes, case_bit);
+        let to_upper = _mm_andnot_si128(lower_lanes, case_bit);
+        for i in 0..blocks {
+            let unit_a = a[i * 2] as i32 | (a[i * 2 + 1] as i32) << 16;
+            let unit_b = b[i * 2] as i32 | (b[i * 2 + 1] as i32) << 16;
+            let units = _mm_set_epi32(unit_b, unit_b, unit_a, unit_a);
+            let mut k = _mm_add_epi16(units, _mm_and_si128(in_range(units, b'A', b'Z'), to_lower));
+            k = _mm_sub_epi16(k, _mm_and_si128(in_range(units, b'a', b'z'), to_upper));
+            k = mullo(k, c1);
+            k = _mm_or_si128(_mm_slli_epi32::<15>(k), _mm_srli_epi32::<17>(k));
+            k = mullo(k, c2);
+            h = _mm_xor_si128(h, k);
+            h = _mm_or_si128(_mm_slli_epi32::<13>(h), _mm_srli_epi32::<19>(h));
+            // h * 5 + n
+            h = _mm_add_epi32(_mm_add_epi32(_mm_slli_epi32::<2>(h), h), n);
+        }
+
+        // SAFETY: both types are 16 bytes of plain integers
+        unsafe { std::mem::transmute::<__m128i, [u32; 4]>(h) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{block_lanes, Murmur3, MURMUR3_SEED};
+
+    #[target_feature(enable = "neon")]
+    pub(super) fn mix_blocks(a: &[u8], b: &[u8], blocks: usize) -> [u32; 4] {
+        let n = vdupq_n_u32(Murmur3::N);
+        let mut h = vdupq_n_u32(MURMUR3_SEED);
+        for i in 0..blocks {
+            let lanes = block_lanes(a, b, i);
+            // SAFETY: `lanes` holds 4 values
+            let mut k = unsafe { vld1q_u32(lanes.as_ptr()) };
+            k = vmulq_n_u32(k, Murmur3::C1);
+            k = vorrq_u32(vshlq_n_u32::<15>(k), vshrq_n_u32::<17>(k));
+            k = vmulq_n_u32(k, Murmur3::C2);
+            h = veorq_u32(h, k);
+            h = vorrq_u32(vshlq_n_u32::<13>(h), vshrq_n_u32::<19>(h));
+            h = vaddq_u32(vmulq_n_u32(h, 5), n);
+        }
+
+        let mut lanes = [0; 4];
+        // SAFETY: `lanes` has room for 4 values
+        unsafe { vst1q_u32(lanes.as_mut_ptr(), h) };
+        lanes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let names = [
+            "natives/stm/camera/collisionfilter/defaultcamera.cfil.7",
+            "natives/STM/a.user.2",
+            "",
+            "x",
+            "natives/stm/\u{4E2D}.user.2",
+            "natives/stm/gui/ui0100.gui.400023",
+            "odd",
+        ];
+        let hashes = hash_mixed_batch(&names);
+        for (name, hash) in names.iter().zip(hashes) {
+            assert_eq!(hash, hash_mixed_str(name), "{}", name);
+        }
+
+        let (a, b) = (b"natives/stm/abc.tex", b"NATIVES/x");
+        assert_eq!(mix_blocks(a, b, 4), scalar_mix_blocks(a, b, 4));
+    }
+}
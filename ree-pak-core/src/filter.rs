@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::error::{PakError, Result};
+
+/// File name used for ignore files by default.
+pub const PAK_IGNORE_FILE_NAME: &str = ".pakignore";
+
+/// Excludes resolved entry paths by patterns in gitignore syntax, e.g. from a `.pakignore` file.
+///
+/// Paths are matched relative to the archive root, such as `natives/stm/sound/**`.
+#[derive(Debug, Clone)]
+pub struct PakIgnore {
+    matcher: Gitignore,
+}
+
+impl PakIgnore {
+    pub fn from_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        Self::from_patterns(content.lines())
+    }
+
+    pub fn from_patterns<'a, I>(patterns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| PakError::InvalidIgnorePattern(e.to_string()))?;
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| PakError::InvalidIgnorePattern(e.to_string()))?;
+
+        Ok(Self { matcher })
+    }
+
+    /// Check if a resolved entry path, or any of its parent directories, is ignored.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.matcher.matched_path_or_any_parents(path, false).is_ignore()
+    }
+}
+
+/// Selects resolved entry paths by include and exclude globs, e.g. `natives/stm/sound/**`.
+///
+/// A path is selected if it matches any include glob, or there are none, and no
+/// exclude glob. Matching is case insensitive, like the path hashes.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+/// Collects globs of a [`PathFilter`], compiled once by [`PathFilterBuilder::build`].
+#[derive(Debug, Clone, Default)]
+pub struct PathFilterBuilder {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl PathFilter {
+    pub fn builder() -> PathFilterBuilder {
+        PathFilterBuilder::default()
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.include.as_ref().is_none_or(|include| include.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+impl PathFilterBuilder {
+    pub fn include_glob(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(compile_glob(pattern)?);
+        Ok(self)
+    }
+
+    pub fn exclude_glob(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(compile_glob(pattern)?);
+        Ok(self)
+    }
+
+    /// Whether no glob was added, so every path matches.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn build(self) -> Result<PathFilter> {
+        let include = if self.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(self.include)?)
+        };
+
+        Ok(PathFilter {
+            include,
+            exclude: build_glob_set(self.exclude)?,
+        })
+    }
+}
+
+fn compile_glob(pattern: &str) -> Result<Glob> {
+    GlobBuilder::new(pattern)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| PakError::InvalidGlob(e.to_string()))
+}
+
+fn build_glob_set(globs: Vec<Glob>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob);
+    }
+
+    builder.build().map_err(|e| PakError::InvalidGlob(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pak_ignore() {
+        let ignore = PakIgnore::from_patterns(["# movies and sound", "*.mov.*", "natives/stm/sound/"]).unwrap();
+
+        assert!(ignore.is_ignored("natives/stm/movie/opening.mov.1"));
+        assert!(ignore.is_ignored("natives/stm/sound/bgm/title.sbnk.1.x64"));
+        assert!(!ignore.is_ignored("natives/stm/camera/defaultcamera.cfil.7"));
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let filter = PathFilter::builder()
+            .include_glob("natives/stm/sound/**")
+            .unwrap()
+            .exclude_glob("**/*.sbnk.*")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(filter.is_match("natives/STM/sound/bgm/title.spck.1.x64"));
+        assert!(!filter.is_match("natives/stm/sound/bgm/title.sbnk.1.x64"));
+        assert!(!filter.is_match("natives/stm/camera/defaultcamera.cfil.7"));
+        assert!(PathFilter::builder().build().unwrap().is_match("anything"));
+        assert!(PathFilter::builder().include_glob("a/[b").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Byte layout of the data region of a pak.
+//!
+//! The data region is split into ranges owned by a single entry, gaps not
+//! owned by any entry, and overlaps shared by several entries. Healthy paks
+//! have neither gaps nor overlaps, so both point at layout anomalies of broken
+//! or hand-edited archives.
+
+use serde::{Serialize, Serializer};
+
+use crate::error::{PakError, Result};
+use crate::pak::PakArchive;
+use crate::spec;
+
+/// Size of the key block following an encrypted entry table.
+const KEY_SIZE: u64 = 128;
+
+/// Interval map of the data region of a pak.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LayoutMap {
+    /// Offset where the data region starts, right after the entry table.
+    pub data_start: u64,
+    /// Size of the pak file, ranges past it point at truncated data.
+    pub file_size: u64,
+    /// Ranges sorted by offset, covering the data region without holes.
+    pub regions: Vec<Region>,
+}
+
+/// A range of bytes `[start, end)` of the data region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+    #[serde(flatten)]
+    pub kind: RegionKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RegionKind {
+    /// Owned by a single entry.
+    Entry {
+        #[serde(serialize_with = "serialize_hash")]
+        hash: u64,
+    },
+    /// Not owned by any entry.
+    Gap,
+    /// Shared by several entries.
+    Overlap {
+        #[serde(serialize_with = "serialize_hashes")]
+        hashes: Vec<u64>,
+    },
+}
+
+// hashes are written as hex strings, JSON numbers lose precision past 2^53 in most viewers
+fn serialize_hash<S: Serializer>(hash: &u64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:016X}", hash))
+}
+
+fn serialize_hashes<S: Serializer>(hashes: &[u64], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(hashes.iter().map(|hash| format!("{:016X}", hash)))
+}
+
+impl LayoutMap {
+    /// Build the map of an archive read from a file of `file_size` bytes.
+    pub fn new(archive: &PakArchive, file_size: u64) -> Self {
+        let header = archive.header();
+        let mut data_start = spec::Header::SIZE as u64 + header.entry_size() as u64 * header.total_files() as u64;
+        if header.feature() == 8 {
+            data_start += KEY_SIZE;
+        }
+
+        // boundaries of entry ranges, an entry covers [offset, offset + size)
+        let mut events: Vec<(u64, bool, u64)> = vec![];
+        for entry in archive.entries() {
+            let size = entry.real_compressed_size();
+            if size == 0 {
+                continue;
+            }
+            let end = entry.offset().saturating_add(size);
+            events.push((entry.offset(), true, entry.hash()));
+            events.push((end, false, entry.hash()));
+        }
+        // ends sort before starts at the same offset, adjacent entries do not overlap
+        events.sort_by_key(|&(offset, is_start, _)| (offset, is_start));
+
+        let mut map = Self {
+            data_start,
+            file_size,
+            regions: vec![],
+        };
+        let mut active: Vec<u64> = vec![];
+        let mut cursor = data_start;
+        for (offset, is_start, hash) in events {
+            if offset > cursor {
+                map.push(cursor, offset, &active);
+                cursor = offset;
+            }
+            if is_start {
+                active.push(hash);
+            } else if let Some(pos) = active.iter().position(|&h| h == hash) {
+                active.remove(pos);
+            }
+        }
+        if file_size > cursor {
+            map.push(cursor, file_size, &active);
+        }
+
+        map
+    }
+
+    /// Number of gap and overlap regions.
+    pub fn anomaly_count(&self) -> (usize, usize) {
+        let gaps = self.regions.iter().filter(|r| r.kind == RegionKind::Gap).count();
+        let overlaps = self
+            .regions
+            .iter()
+            .filter(|r| matches!(r.kind, RegionKind::Overlap { .. }))
+            .count();
+
+        (gaps, overlaps)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| PakError::IO(std::io::Error::other(e)))
+    }
+
+    /// Append a range, merging it into the previous one if they are of the same kind.
+    fn push(&mut self, start: u64, end: u64, active: &[u64]) {
+        let kind = match active {
+            [] => RegionKind::Gap,
+            [hash] => RegionKind::Entry { hash: *hash },
+            hashes => {
+                let mut hashes = hashes.to_vec();
+                hashes.sort_unstable();
+                RegionKind::Overlap { hashes }
+            }
+        };
+        if let Some(last) = self.regions.last_mut() {
+            if last.end == start && last.kind == kind {
+                last.end = end;
+                return;
+            }
+        }
+        self.regions.push(Region { start, end, kind });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::{CompressionMethod, PakEntry, PakHeader};
+
+    #[test]
+    fn test_layout_map() {
+        let header = PakHeader::new(4, 0, 0, 3, 0).unwrap();
+        let data_start = 16 + 48 * 3;
+        let entries = vec![
+            PakEntry::new(1, data_start, 10, 10, CompressionMethod::None),
+            // gap of 5 bytes, then overlaps the next entry by 4 bytes
+            PakEntry::new(2, data_start + 15, 10, 10, CompressionMethod::None),
+            PakEntry::new(3, data_start + 21, 10, 10, CompressionMethod::None),
+        ];
+        let archive = PakArchive::new(header, entries);
+
+        let map = LayoutMap::new(&archive, data_start + 40);
+        let regions: Vec<(u64, u64, RegionKind)> = map
+            .regions
+            .iter()
+            .map(|r| (r.start - data_start, r.end - data_start, r.kind.clone()))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                (0, 10, RegionKind::Entry { hash: 1 }),
+                (10, 15, RegionKind::Gap),
+                (15, 21, RegionKind::Entry { hash: 2 }),
+                (21, 25, RegionKind::Overlap { hashes: vec![2, 3] }),
+                (25, 31, RegionKind::Entry { hash: 3 }),
+                (31, 40, RegionKind::Gap),
+            ]
+        );
+        assert_eq!(map.anomaly_count(), (2, 1));
+    }
+}
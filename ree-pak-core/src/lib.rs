@@ -1,5 +1,20 @@
+pub mod compare;
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod compat;
+pub mod concurrency;
+pub mod content_hash;
+pub mod env;
 pub mod error;
+pub mod export;
 pub mod filename;
+pub mod filter;
+pub mod layout;
+pub mod memory;
+pub mod metadata;
 pub mod pak;
+pub mod permissions;
+pub mod progress;
 pub mod read;
-mod spec;
+pub mod spec;
+pub mod stats;
+pub mod write;
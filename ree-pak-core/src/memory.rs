@@ -0,0 +1,158 @@
+//! Estimates of the peak memory of planned extraction and packing.
+//!
+//! Hosts on memory-constrained machines check an estimate before starting and
+//! pick a thread count that fits, instead of running out of memory midway.
+//! The figures are approximations of buffers and codec state, not measurements.
+
+use crate::concurrency::{ConcurrencyConfig, DEFAULT_MAX_IN_FLIGHT_BYTES};
+use crate::pak::{CompressionMethod, PakEntry};
+
+/// Buffers of a worker streaming an entry: the source reader and the copy buffer.
+pub const STREAM_BUFFER_BYTES: u64 = 64 * 1024;
+/// Deflate decoder state with its 32 KiB window.
+pub const DEFLATE_DECODER_BYTES: u64 = 64 * 1024;
+/// Zstd decoder state besides its window, which is at most the decoded size of the entry.
+pub const ZSTD_DECODER_BYTES: u64 = 256 * 1024;
+/// Largest zstd window assumed for decoding, that of the highest regular compression levels.
+pub const ZSTD_MAX_WINDOW_BYTES: u64 = 8 << 20;
+/// Deflate encoder state.
+pub const DEFLATE_ENCODER_BYTES: u64 = 320 * 1024;
+/// Zstd encoder state at the default level, with its 2 MiB window and match tables.
+pub const ZSTD_ENCODER_BYTES: u64 = 3 << 20;
+/// Memory of one entry of the entry table kept by the writer.
+const ENTRY_BYTES: u64 = std::mem::size_of::<PakEntry>() as u64;
+
+/// Estimate of the peak memory of extracting a set of entries in parallel.
+///
+/// Streaming extraction holds buffers and codec state per worker. Buffered
+/// extraction, such as into a tar archive, also holds whole decoded entries,
+/// bounded by the in-flight limit of [`ConcurrencyConfig`].
+#[derive(Debug, Clone)]
+pub struct ExtractMemoryEstimate {
+    threads: usize,
+    buffered: bool,
+    max_in_flight_bytes: u64,
+    /// Memory of each entry while a worker streams it.
+    worker_costs: Vec<u64>,
+    /// Decoded sizes of the entries.
+    sizes: Vec<u64>,
+}
+
+impl ExtractMemoryEstimate {
+    /// Estimate for the thread count and in-flight limit of `config`.
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            threads: config.max_threads().unwrap_or_else(rayon::current_num_threads).max(1),
+            buffered: false,
+            max_in_flight_bytes: config.max_in_flight_bytes().unwrap_or(DEFAULT_MAX_IN_FLIGHT_BYTES),
+            worker_costs: vec![],
+            sizes: vec![],
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Whether whole decoded entries are held in memory.
+    pub fn with_buffered(mut self, buffered: bool) -> Self {
+        self.buffered = buffered;
+        self
+    }
+
+    pub fn add_entry(&mut self, entry: &PakEntry) {
+        self.worker_costs.push(STREAM_BUFFER_BYTES + decoder_bytes(entry));
+        self.sizes.push(entry.uncompressed_size());
+    }
+
+    #[inline]
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Peak bytes with the worst entries running at the same time.
+    pub fn estimated_peak_memory(&self) -> u64 {
+        self.peak_with_threads(self.threads)
+    }
+
+    /// Most threads whose estimate stays within `budget`, at least one.
+    pub fn threads_within(&self, budget: u64) -> usize {
+        (1..=self.threads)
+            .rev()
+            .find(|&threads| self.peak_with_threads(threads) <= budget)
+            .unwrap_or(1)
+    }
+
+    fn peak_with_threads(&self, threads: usize) -> u64 {
+        let workers = sum_largest(&self.worker_costs, threads);
+        if !self.buffered {
+            return workers;
+        }
+        // entries larger than the limit run alone
+        let largest = self.sizes.iter().copied().max().unwrap_or(0);
+        let held = sum_largest(&self.sizes, threads)
+            .min(self.max_in_flight_bytes)
+            .max(largest);
+
+        workers + held
+    }
+}
+
+/// Estimate of the peak memory of writing a pak with `total_files` entries.
+///
+/// The writer compresses one entry at a time, holding the entry table and the
+/// state of one encoder.
+pub fn pack_peak_memory(total_files: u32, compression: CompressionMethod) -> u64 {
+    let encoder = match compression {
+        CompressionMethod::None => 0,
+        CompressionMethod::Deflate => DEFLATE_ENCODER_BYTES,
+        CompressionMethod::Zstd => ZSTD_ENCODER_BYTES,
+    };
+
+    STREAM_BUFFER_BYTES + encoder + ENTRY_BYTES * total_files as u64
+}
+
+fn decoder_bytes(entry: &PakEntry) -> u64 {
+    match entry.compression_method() {
+        CompressionMethod::None => 0,
+        CompressionMethod::Deflate => DEFLATE_DECODER_BYTES,
+        CompressionMethod::Zstd => ZSTD_DECODER_BYTES + entry.uncompressed_size().min(ZSTD_MAX_WINDOW_BYTES),
+    }
+}
+
+/// Sum of the `count` largest values.
+fn sum_largest(values: &[u64], count: usize) -> u64 {
+    let mut values = values.to_vec();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+    values.iter().take(count).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_memory_estimate() {
+        let mut estimate = ExtractMemoryEstimate::new(ConcurrencyConfig::default()).with_threads(4);
+        for size in [100, 200, 20 << 20] {
+            estimate.add_entry(&PakEntry::new(size, 0, size, size, CompressionMethod::Zstd));
+        }
+        estimate.add_entry(&PakEntry::new(4, 0, 10, 10, CompressionMethod::None));
+
+        let streaming = 4 * STREAM_BUFFER_BYTES + 3 * ZSTD_DECODER_BYTES + ZSTD_MAX_WINDOW_BYTES + 300;
+        assert_eq!(estimate.estimated_peak_memory(), streaming);
+        let buffered = estimate.clone().with_buffered(true);
+        assert_eq!(buffered.estimated_peak_memory(), streaming + (20 << 20) + 310);
+
+        let one_thread = STREAM_BUFFER_BYTES + ZSTD_DECODER_BYTES + ZSTD_MAX_WINDOW_BYTES;
+        assert_eq!(estimate.threads_within(one_thread), 1);
+        assert_eq!(
+            estimate.threads_within(one_thread + STREAM_BUFFER_BYTES + ZSTD_DECODER_BYTES + 200),
+            2
+        );
+        assert_eq!(estimate.threads_within(u64::MAX), 4);
+
+        assert!(pack_peak_memory(10, CompressionMethod::Zstd) > pack_peak_memory(10, CompressionMethod::None));
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PakError, Result};
+use crate::filename::FileName;
+
+/// Reserved path of the metadata entry, the entry is stored under its hash.
+pub const METADATA_PATH: &str = "__ree_pak_metadata.json";
+
+/// Optional information identifying a mod pak, stored as a JSON entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PakMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Build time in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_timestamp: Option<u64>,
+    /// Name and version of the tool that wrote the pak.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+}
+
+impl PakMetadata {
+    /// Hash of the reserved metadata entry.
+    pub fn hash() -> u64 {
+        FileName::new(METADATA_PATH).hash_mixed()
+    }
+
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| PakError::InvalidMetadata(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| PakError::InvalidMetadata(e.to_string()))
+    }
+}
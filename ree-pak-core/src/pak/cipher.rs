@@ -26,6 +26,41 @@ pub fn decrypt_data(data: &[u8], enc_key: &[u8]) -> Vec<u8> {
     result
 }
 
+/// Encrypt data with an encrypted key block, the inverse of [`decrypt_data`].
+///
+/// The data cipher is a XOR stream, so encryption and decryption are the same operation.
+pub fn encrypt_data(data: &[u8], enc_key: &[u8]) -> Vec<u8> {
+    decrypt_data(data, enc_key)
+}
+
+/// Generate a random encrypted key block from the current [`crate::env::Env`].
+///
+/// Any key block below the modulus is valid, the data key is derived from it
+/// with the public exponent just as when reading.
+pub fn generate_key() -> [u8; 128] {
+    let mut key = [0; 128];
+    crate::env::env().fill_random(&mut key);
+    // keep the key below the modulus
+    key[127] &= 0x7F;
+
+    key
+}
+
+/// Derive an encrypted key block from `seed`, the same seed always gives the same key.
+pub fn derive_key(seed: &[u8]) -> [u8; 128] {
+    let mut key = [0; 128];
+    for (i, chunk) in key.chunks_exact_mut(16).enumerate() {
+        let mut block = seed.to_vec();
+        block.push(i as u8);
+        let hash = murmur3::murmur3_x64_128(&mut block.as_slice(), 0).unwrap_or_default();
+        chunk.copy_from_slice(&hash.to_le_bytes());
+    }
+    // keep the key below the modulus
+    key[127] &= 0x7F;
+
+    key
+}
+
 fn decrypt_key(enc_key: &[u8]) -> Vec<u8> {
     let enc_key_int = BigUint::from_bytes_le(&resize_key(enc_key));
     let result_int = enc_key_int.modpow(&EXPONENT_INT, &MODULUS_INT);
@@ -1,4 +1,5 @@
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CompressionMethod {
     #[default]
     None,
@@ -6,6 +7,17 @@ pub enum CompressionMethod {
     Zstd,
 }
 
+impl CompressionMethod {
+    /// Whether this build can read and write the method, codecs can be left out with features.
+    pub fn is_supported(self) -> bool {
+        match self {
+            CompressionMethod::None => true,
+            CompressionMethod::Deflate => cfg!(feature = "deflate"),
+            CompressionMethod::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
 impl From<i64> for CompressionMethod {
     fn from(value: i64) -> Self {
         if value & 0xF == 1 {
@@ -25,3 +37,13 @@ impl From<i64> for CompressionMethod {
         }
     }
 }
+
+impl From<CompressionMethod> for i64 {
+    fn from(value: CompressionMethod) -> Self {
+        match value {
+            CompressionMethod::None => 0,
+            CompressionMethod::Deflate => 1,
+            CompressionMethod::Zstd => 2,
+        }
+    }
+}
@@ -2,6 +2,30 @@ use crate::spec;
 
 use super::compression::CompressionMethod;
 
+/// Likely cause of an entry whose sizes do not fit its compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySuspicion {
+    /// Stored entry with less data than its decoded size, as if compressed in chunks this reader does not know.
+    LikelyChunked,
+    /// Sizes no codec produces, the entry table was likely parsed with the wrong pak version.
+    LikelyWrongVersion,
+    /// Stored entry with data but no uncompressed size, rejected by [`ZeroSizePolicy::Reject`](crate::read::ZeroSizePolicy::Reject).
+    ZeroUncompressedSize,
+}
+
+impl std::fmt::Display for EntrySuspicion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            EntrySuspicion::LikelyChunked => "likely chunked data, which this reader does not support",
+            EntrySuspicion::LikelyWrongVersion => {
+                "likely a pak version this reader misparses, check the header version"
+            }
+            EntrySuspicion::ZeroUncompressedSize => "data is stored but the uncompressed size is zero",
+        };
+        f.write_str(reason)
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct PakEntry {
     hash_name_lower: u32,
@@ -10,12 +34,16 @@ pub struct PakEntry {
     compressed_size: u64,
     uncompressed_size: u64,
     compression_method: CompressionMethod,
+    encryption_type: u16,
+    /// Low bits of the stored compression field, the codec of encrypted entries is only kept here.
+    raw_compression: u16,
     checksum: u64,
 }
 
 impl PakEntry {
     pub fn real_compressed_size(&self) -> u64 {
-        if self.compression_method == CompressionMethod::None {
+        // the method of encrypted entries does not describe the data, their size is as stored
+        if self.compression_method == CompressionMethod::None && self.encryption_type == 0 {
             self.compressed_size.max(self.uncompressed_size)
         } else {
             self.compressed_size
@@ -29,6 +57,89 @@ impl PakEntry {
         upper << 32 | lower
     }
 
+    pub(crate) fn new(
+        hash: u64,
+        offset: u64,
+        compressed_size: u64,
+        uncompressed_size: u64,
+        compression_method: CompressionMethod,
+    ) -> Self {
+        Self {
+            hash_name_lower: hash as u32,
+            hash_name_upper: (hash >> 32) as u32,
+            offset,
+            compressed_size,
+            uncompressed_size,
+            compression_method,
+            encryption_type: 0,
+            raw_compression: i64::from(compression_method) as u16,
+            checksum: 0,
+        }
+    }
+
+    /// Compression field as written to the entry table, codec bits with the encryption type in bits 16 and up.
+    ///
+    /// Encrypted entries keep the field as read, so rewriting the table leaves them unchanged.
+    pub(crate) fn compression_field(&self) -> i64 {
+        if self.encryption_type != 0 {
+            self.raw_compression as i64 | (self.encryption_type as i64) << 16
+        } else {
+            self.compression_method.into()
+        }
+    }
+
+    /// Copy of the entry with both sizes set to `size`, for stored entries.
+    pub(crate) fn with_stored_size(&self, size: u64) -> Self {
+        Self {
+            compressed_size: size,
+            uncompressed_size: size,
+            ..self.clone()
+        }
+    }
+
+    /// Copy of the entry with its data placed at a new location.
+    pub(crate) fn with_data(&self, offset: u64, compressed_size: u64, compression_method: CompressionMethod) -> Self {
+        Self {
+            offset,
+            compressed_size,
+            compression_method,
+            raw_compression: if self.encryption_type != 0 {
+                self.raw_compression
+            } else {
+                i64::from(compression_method) as u16
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Check the sizes against the compression method, `None` if they are consistent.
+    ///
+    /// Entries with resource encryption are not checked, their method does not describe the data.
+    /// Stored entries without an uncompressed size are left to [`ZeroSizePolicy`](crate::read::ZeroSizePolicy).
+    pub fn suspicion(&self) -> Option<EntrySuspicion> {
+        if self.encryption_type != 0 || self.compressed_size == 0 {
+            return None;
+        }
+        if self.compression_method == CompressionMethod::None && self.uncompressed_size == 0 {
+            return None;
+        }
+        match self.compression_method {
+            CompressionMethod::None if self.compressed_size < self.uncompressed_size => {
+                Some(EntrySuspicion::LikelyChunked)
+            }
+            CompressionMethod::None if self.compressed_size > self.uncompressed_size => {
+                Some(EntrySuspicion::LikelyWrongVersion)
+            }
+            // deflate and zstd expand incompressible data by far less than this
+            CompressionMethod::Deflate | CompressionMethod::Zstd
+                if self.compressed_size > self.uncompressed_size + self.uncompressed_size / 8 + 1024 =>
+            {
+                Some(EntrySuspicion::LikelyWrongVersion)
+            }
+            _ => None,
+        }
+    }
+
     #[inline]
     pub fn offset(&self) -> u64 {
         self.offset
@@ -49,6 +160,12 @@ impl PakEntry {
         self.compression_method
     }
 
+    /// Resource encryption type from the upper bits of the compression field, 0 if not encrypted.
+    #[inline]
+    pub fn encryption_type(&self) -> u16 {
+        self.encryption_type
+    }
+
     #[inline]
     pub fn checksum(&self) -> u64 {
         self.checksum
@@ -76,6 +193,33 @@ impl From<spec::EntryV2> for PakEntry {
             compressed_size: value.compressed_size,
             uncompressed_size: value.uncompressed_size,
             compression_method: value.compression_method.into(),
+            encryption_type: (value.compression_method >> 16) as u16,
+            raw_compression: value.compression_method as u16,
+            checksum: value.checksum,
+        }
+    }
+}
+
+impl From<&PakEntry> for spec::EntryV1 {
+    fn from(value: &PakEntry) -> Self {
+        Self {
+            offset: value.offset,
+            uncompressed_size: value.uncompressed_size,
+            hash_name_lower: value.hash_name_lower,
+            hash_name_upper: value.hash_name_upper,
+        }
+    }
+}
+
+impl From<&PakEntry> for spec::EntryV2 {
+    fn from(value: &PakEntry) -> Self {
+        Self {
+            hash_name_lower: value.hash_name_lower,
+            hash_name_upper: value.hash_name_upper,
+            offset: value.offset,
+            compressed_size: value.real_compressed_size(),
+            uncompressed_size: value.uncompressed_size,
+            compression_method: value.compression_field(),
             checksum: value.checksum,
         }
     }
@@ -90,6 +234,7 @@ impl std::fmt::Debug for PakEntry {
             .field("compressed_size", &self.compressed_size)
             .field("uncompressed_size", &self.uncompressed_size)
             .field("compression_method", &self.compression_method)
+            .field("encryption_type", &self.encryption_type)
             .field("checksum", &format!("{:16x}", self.checksum))
             .finish()
     }
@@ -1,5 +1,9 @@
+use crate::error::Result;
 use crate::spec;
 
+/// Magic at the start of every pak header.
+pub const MAGIC: [u8; 4] = *b"KPKA";
+
 #[derive(Clone, Default)]
 pub struct PakHeader {
     magic: [u8; 4],
@@ -11,12 +15,24 @@ pub struct PakHeader {
 }
 
 impl PakHeader {
+    pub fn new(major_version: u8, minor_version: u8, feature: u16, total_files: u32, hash: u32) -> Result<Self> {
+        PakHeader::try_from(spec::Header {
+            magic: MAGIC,
+            major_version,
+            minor_version,
+            feature,
+            total_files,
+            hash,
+        })
+    }
+
+    /// Entry table layout, known to exist as the version was validated on construction.
+    pub fn toc_layout(&self) -> spec::TocLayout {
+        spec::TocLayout::for_version(self.major_version, self.minor_version).expect("Unsupported version")
+    }
+
     pub fn entry_size(&self) -> u32 {
-        match self.major_version {
-            2 => 24,
-            4 => 48,
-            _ => panic!("Unsupported major version"),
-        }
+        self.toc_layout().entry_size() as u32
     }
 
     #[inline]
@@ -53,14 +69,14 @@ impl PakHeader {
 impl TryFrom<spec::Header> for PakHeader {
     type Error = crate::error::PakError;
 
-    fn try_from(this: spec::Header) -> Result<Self, Self::Error> {
-        if &this.magic != b"KPKA" {
+    fn try_from(this: spec::Header) -> std::result::Result<Self, Self::Error> {
+        if this.magic != MAGIC {
             return Err(Self::Error::InvalidMagic {
-                expected: *b"KPKA",
+                expected: MAGIC,
                 found: this.magic,
             });
         }
-        if (this.major_version != 2 && this.major_version != 4) || ![0, 1].contains(&this.minor_version) {
+        if spec::TocLayout::for_version(this.major_version, this.minor_version).is_none() {
             return Err(Self::Error::UnsupportedVersion {
                 major: this.major_version,
                 minor: this.minor_version,
@@ -81,6 +97,19 @@ impl TryFrom<spec::Header> for PakHeader {
     }
 }
 
+impl From<&PakHeader> for spec::Header {
+    fn from(value: &PakHeader) -> Self {
+        Self {
+            magic: value.magic,
+            major_version: value.major_version,
+            minor_version: value.minor_version,
+            feature: value.feature,
+            total_files: value.total_files,
+            hash: value.hash,
+        }
+    }
+}
+
 impl std::fmt::Debug for PakHeader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PakHeader")
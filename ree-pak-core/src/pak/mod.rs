@@ -1,23 +1,49 @@
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::ops::{Bound, RangeBounds};
+use std::sync::OnceLock;
+
+use nohash::NoHashHasher;
+
+use crate::error::{PakError, Result};
+use crate::filename::FileName;
+
 mod cipher;
 mod compression;
 mod entry;
 mod header;
 
-pub(crate) use cipher::decrypt_data;
+pub(crate) use cipher::{decrypt_data, derive_key, encrypt_data, generate_key};
 pub use compression::CompressionMethod;
-pub use entry::PakEntry;
-pub use header::PakHeader;
+pub use entry::{EntrySuspicion, PakEntry};
+pub use header::{PakHeader, MAGIC};
+
+type EntryIndex = HashMap<u64, usize, BuildHasherDefault<NoHashHasher<u64>>>;
 
 /// Pak Archive, stores the header and entries.
 #[derive(Clone)]
 pub struct PakArchive {
     header: PakHeader,
     entries: Vec<PakEntry>,
+    base_offset: u64,
+    // positions of entries by hash, built on the first lookup
+    index: OnceLock<EntryIndex>,
 }
 
 impl PakArchive {
     pub fn new(header: PakHeader, entries: Vec<PakEntry>) -> Self {
-        PakArchive { header, entries }
+        PakArchive {
+            header,
+            entries,
+            base_offset: 0,
+            index: OnceLock::new(),
+        }
+    }
+
+    /// Offset of the header in the file, for paks found after leading junk.
+    pub fn with_base_offset(mut self, base_offset: u64) -> Self {
+        self.base_offset = base_offset;
+        self
     }
 
     #[inline]
@@ -29,4 +55,87 @@ impl PakArchive {
     pub fn entries(&self) -> &[PakEntry] {
         &self.entries
     }
+
+    /// Offset of the header in the file, entry offsets already include it.
+    #[inline]
+    pub fn base_offset(&self) -> u64 {
+        self.base_offset
+    }
+
+    /// Find an entry by its file name hash.
+    ///
+    /// The first lookup builds an index of all entries, later lookups take constant time.
+    /// If several entries share a hash, the first one in the TOC is returned.
+    pub fn find_entry(&self, hash: u64) -> Option<&PakEntry> {
+        let index = self.index.get_or_init(|| {
+            let mut index = EntryIndex::with_capacity_and_hasher(self.entries.len(), Default::default());
+            for (i, entry) in self.entries.iter().enumerate() {
+                index.entry(entry.hash()).or_insert(i);
+            }
+            index
+        });
+
+        index.get(&hash).map(|&i| &self.entries[i])
+    }
+
+    /// Find an entry by its path, the path is matched case-insensitively like the game does.
+    pub fn find_entry_by_path(&self, path: &str) -> Option<&PakEntry> {
+        self.find_entry(FileName::new(path).hash_mixed())
+    }
+
+    /// Get the entries in an ordinal index range of the TOC.
+    ///
+    /// The end of the range is clamped to the number of entries.
+    pub fn entries_in_range<B>(&self, range: B) -> Result<&[PakEntry]>
+    where
+        B: RangeBounds<usize>,
+    {
+        slice_range(&self.entries, range)
+    }
+}
+
+/// Get a sub slice by range, the end of the range is clamped to the slice length.
+pub(crate) fn slice_range<T, B>(items: &[T], range: B) -> Result<&[T]>
+where
+    B: RangeBounds<usize>,
+{
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.saturating_add(1),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => items.len(),
+    }
+    .min(items.len());
+
+    items.get(start..end).ok_or(PakError::EntryIndexOutOfBounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_entry() {
+        let entries = vec![
+            PakEntry::new(
+                FileName::new("natives/stm/a.user.2").hash_mixed(),
+                0,
+                1,
+                1,
+                CompressionMethod::None,
+            ),
+            PakEntry::new(2, 10, 1, 1, CompressionMethod::None),
+            PakEntry::new(2, 20, 1, 1, CompressionMethod::None),
+        ];
+        let archive = PakArchive::new(PakHeader::new(4, 0, 0, 3, 0).unwrap(), entries);
+
+        assert_eq!(archive.find_entry_by_path("NATIVES/STM/A.user.2").unwrap().offset(), 0);
+        // the first of duplicated hashes
+        assert_eq!(archive.find_entry(2).unwrap().offset(), 10);
+        assert!(archive.find_entry(3).is_none());
+    }
 }
@@ -0,0 +1,133 @@
+//! Permissions of extracted files and directories.
+//!
+//! By default files and directories are created with the mode the process
+//! umask leaves. A mode set here is applied after creation instead, so the
+//! result does not depend on the umask of the extracting process. Modes only
+//! have an effect on Unix and are ignored elsewhere.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Modes of created files and directories, `None` keeps the umask default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputPermissions {
+    file_mode: Option<u32>,
+    dir_mode: Option<u32>,
+}
+
+impl OutputPermissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mode of created files, e.g. `0o644`.
+    pub fn with_file_mode(mut self, mode: Option<u32>) -> Self {
+        self.file_mode = mode;
+        self
+    }
+
+    /// Mode of created directories, e.g. `0o755`.
+    pub fn with_dir_mode(mut self, mode: Option<u32>) -> Self {
+        self.dir_mode = mode;
+        self
+    }
+
+    #[inline]
+    pub fn file_mode(&self) -> Option<u32> {
+        self.file_mode
+    }
+
+    #[inline]
+    pub fn dir_mode(&self) -> Option<u32> {
+        self.dir_mode
+    }
+
+    /// Set the file mode on a file just created.
+    pub fn apply_to_file(&self, file: &File) -> io::Result<()> {
+        match self.file_mode {
+            Some(mode) => set_file_mode(file, mode),
+            None => Ok(()),
+        }
+    }
+
+    /// Create a directory and its missing parents.
+    ///
+    /// Only directories created by the call get the directory mode, existing
+    /// ones are left unchanged. A directory created concurrently by another
+    /// thread is not an error.
+    pub fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        let Some(mode) = self.dir_mode else {
+            return std::fs::create_dir_all(dir);
+        };
+        if dir.as_os_str().is_empty() || dir.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent() {
+            self.create_dir_all(parent)?;
+        }
+        match std::fs::create_dir(dir) {
+            Ok(()) => set_dir_mode(dir, mode),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_file_mode(file: &File, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    file.set_permissions(std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_file: &File, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_dir_mode(dir: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_dir_mode(_dir: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[test]
+    fn test_output_permissions() {
+        let root = std::env::temp_dir().join(format!("ree-pak-permissions-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let permissions = OutputPermissions::new()
+            .with_file_mode(Some(0o640))
+            .with_dir_mode(Some(0o751));
+
+        let dir = root.join("a/b");
+        permissions.create_dir_all(&dir).unwrap();
+        permissions.create_dir_all(&dir).unwrap();
+        assert_eq!(mode(&root), 0o700);
+        assert_eq!(mode(&root.join("a")), 0o751);
+        assert_eq!(mode(&dir), 0o751);
+
+        let path = dir.join("file.bin");
+        let file = File::create(&path).unwrap();
+        permissions.apply_to_file(&file).unwrap();
+        assert_eq!(mode(&path), 0o640);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
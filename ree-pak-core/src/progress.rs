@@ -0,0 +1,161 @@
+//! Progress counters of an extraction, shared between workers and a UI.
+//!
+//! Workers update the counters as files are written, a UI thread polls
+//! [`ExtractProgress::snapshot`] at its own pace instead of handling an event
+//! per file.
+
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counters of an extraction in progress, shared through an [`Arc`].
+#[derive(Debug, Default)]
+pub struct ExtractProgress {
+    total_files: u64,
+    total_bytes: u64,
+    files_done: AtomicU64,
+    files_failed: AtomicU64,
+    bytes_done: AtomicU64,
+    in_flight: AtomicU64,
+}
+
+/// Counters of an [`ExtractProgress`] at one point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    pub total_files: u64,
+    /// Sum of the uncompressed sizes of all files.
+    pub total_bytes: u64,
+    pub files_done: u64,
+    pub files_failed: u64,
+    /// Decoded bytes of finished and in-flight files.
+    pub bytes_done: u64,
+    /// Files being extracted right now.
+    pub in_flight: u64,
+}
+
+impl ProgressSnapshot {
+    /// Whether every file is done or failed.
+    pub fn is_finished(&self) -> bool {
+        self.files_done + self.files_failed >= self.total_files
+    }
+}
+
+impl ExtractProgress {
+    pub fn new(total_files: u64, total_bytes: u64) -> Arc<Self> {
+        Arc::new(Self {
+            total_files,
+            total_bytes,
+            ..Default::default()
+        })
+    }
+
+    /// Count a file as in flight until the returned guard is finished or dropped.
+    pub fn start_file(&self) -> FileProgress<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        FileProgress {
+            progress: self,
+            finished: false,
+        }
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            total_files: self.total_files,
+            total_bytes: self.total_bytes,
+            files_done: self.files_done.load(Ordering::Relaxed),
+            files_failed: self.files_failed.load(Ordering::Relaxed),
+            bytes_done: self.bytes_done.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A file being extracted, counted as failed if dropped before [`FileProgress::finish`].
+#[derive(Debug)]
+pub struct FileProgress<'a> {
+    progress: &'a ExtractProgress,
+    finished: bool,
+}
+
+impl FileProgress<'_> {
+    pub fn add_bytes(&self, bytes: u64) {
+        self.progress.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count the bytes read through `reader` as they are read.
+    pub fn track<R: Read>(&self, reader: R) -> ProgressReader<'_, R> {
+        ProgressReader {
+            inner: reader,
+            progress: self.progress,
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.finished = true;
+        self.progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for FileProgress<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.progress.files_failed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.progress.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Reader adding the bytes read to the progress of its file.
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a ExtractProgress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.bytes_done.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_snapshot() {
+        let progress = ExtractProgress::new(3, 10);
+        let first = progress.start_file();
+        let second = progress.start_file();
+        let mut data = vec![];
+        first.track(&b"12345678"[..]).read_to_end(&mut data).unwrap();
+        assert_eq!(
+            progress.snapshot(),
+            ProgressSnapshot {
+                total_files: 3,
+                total_bytes: 10,
+                bytes_done: 8,
+                in_flight: 2,
+                ..Default::default()
+            }
+        );
+
+        first.finish();
+        drop(second);
+        let third = progress.start_file();
+        third.add_bytes(2);
+        third.finish();
+        let snapshot = progress.snapshot();
+        assert_eq!(
+            (
+                snapshot.files_done,
+                snapshot.files_failed,
+                snapshot.bytes_done,
+                snapshot.in_flight
+            ),
+            (2, 1, 10, 0)
+        );
+        assert!(snapshot.is_finished());
+    }
+}
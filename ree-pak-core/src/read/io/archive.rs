@@ -1,9 +1,12 @@
-use std::io::{Cursor, Read, Seek};
+use std::io::{BufRead, Read, Seek};
 
 use crate::error::{PakError, Result};
+use crate::filename::FileNameTable;
+use crate::metadata::PakMetadata;
 use crate::pak::{PakArchive, PakEntry};
 
-use super::entry::PakEntryReader;
+use super::entry::{OwnedEntryReader, PakEntryReader};
+use super::part::PartReader;
 
 /// Read a pak archive.
 pub struct PakArchiveReader<'a, R> {
@@ -37,11 +40,11 @@ where
         self.archive.inner()
     }
 
-    pub fn owned_entry_reader(&mut self, entry: PakEntry) -> Result<PakEntryReader<Cursor<Vec<u8>>>> {
+    pub fn owned_entry_reader(&mut self, entry: PakEntry) -> Result<OwnedEntryReader> {
         PakEntryReader::new_owned(&mut self.reader, entry)
     }
 
-    pub fn owned_entry_reader_by_index(&mut self, index: usize) -> Result<PakEntryReader<Cursor<Vec<u8>>>> {
+    pub fn owned_entry_reader_by_index(&mut self, index: usize) -> Result<OwnedEntryReader> {
         let entry = self
             .archive
             .inner()
@@ -52,6 +55,130 @@ where
     }
 }
 
+impl<'a, R> PakArchiveReader<'a, R>
+where
+    R: BufRead + Seek,
+{
+    /// Get a streaming reader of the entry, borrowing the underlying reader.
+    pub fn entry_reader(&mut self, entry: &PakEntry) -> Result<PakEntryReader<PartReader<&mut R>>> {
+        PakEntryReader::new_streaming(&mut self.reader, entry)
+    }
+
+    /// Get a reader of the stored bytes of the entry, without decompression or decryption.
+    pub fn raw_entry_reader(&mut self, entry: &PakEntry) -> Result<PartReader<&mut R>> {
+        Ok(PartReader::raw_entry(&mut self.reader, entry)?)
+    }
+
+    /// Check that the entry data can be fully decoded to its declared size.
+    ///
+    /// The entry checksum is not checked, as its algorithm is unknown.
+    pub fn verify_entry(&mut self, entry: &PakEntry) -> Result<()> {
+        let mut entry_reader = self.entry_reader(entry)?;
+        let decoded_size = std::io::copy(&mut entry_reader, &mut std::io::sink())?;
+        if decoded_size != entry.uncompressed_size() {
+            return Err(PakError::EntrySizeMismatch {
+                hash: entry.hash(),
+                expected: entry.uncompressed_size(),
+                found: decoded_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Walk the entries with their names resolved by `table`, opening readers only when asked.
+    ///
+    /// ```no_run
+    /// # use std::io::Read;
+    /// # use ree_pak_core::{filename::FileNameTable, read::{io::archive::PakArchiveReader, read_archive}};
+    /// # fn main() -> ree_pak_core::error::Result<()> {
+    /// # let mut file = std::io::BufReader::new(std::fs::File::open("re_chunk_000.pak")?);
+    /// # let table = FileNameTable::from_list_file("MHWs.list")?;
+    /// let archive = read_archive(&mut file)?;
+    /// let mut reader = PakArchiveReader::new(&mut file, &archive);
+    /// let mut entries = reader.iter_named(&table);
+    /// while let Some(named) = entries.next_entry() {
+    ///     if named.name().is_some_and(|name| name.ends_with(".msg.23")) {
+    ///         let mut data = vec![];
+    ///         named.reader()?.read_to_end(&mut data)?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_named<'t>(&mut self, table: &'t FileNameTable) -> NamedEntries<'_, 'a, 't, R> {
+        NamedEntries {
+            archive_reader: self,
+            table,
+            index: 0,
+        }
+    }
+
+    /// Read the metadata entry, if the pak has one.
+    pub fn read_metadata(&mut self) -> Result<Option<PakMetadata>> {
+        let Some(entry) = self.archive.inner().find_entry(PakMetadata::hash()).cloned() else {
+            return Ok(None);
+        };
+        let mut data = vec![];
+        self.entry_reader(&entry)?.read_to_end(&mut data)?;
+
+        PakMetadata::from_json(&data).map(Some)
+    }
+}
+
+/// Entries of a pak with resolved names, from [`PakArchiveReader::iter_named`].
+///
+/// Each entry borrows the underlying reader, so entries are taken one at a time
+/// with [`NamedEntries::next_entry`] instead of through [`Iterator`].
+pub struct NamedEntries<'r, 'a, 't, R> {
+    archive_reader: &'r mut PakArchiveReader<'a, R>,
+    table: &'t FileNameTable,
+    index: usize,
+}
+
+impl<'t, R> NamedEntries<'_, '_, 't, R>
+where
+    R: BufRead + Seek,
+{
+    /// The next entry in TOC order, `None` after the last one.
+    pub fn next_entry(&mut self) -> Option<NamedEntry<'_, 't, R>> {
+        let entry = self.archive_reader.archive.inner().entries().get(self.index)?;
+        self.index += 1;
+        Some(NamedEntry {
+            name: self.table.get_file_name(entry.hash()),
+            entry,
+            reader: &mut self.archive_reader.reader,
+        })
+    }
+}
+
+/// An entry of [`NamedEntries`] with its name, `None` if the table has no name for its hash.
+pub struct NamedEntry<'e, 't, R> {
+    name: Option<&'t str>,
+    entry: &'e PakEntry,
+    reader: &'e mut R,
+}
+
+impl<'e, 't, R> NamedEntry<'e, 't, R>
+where
+    R: BufRead + Seek,
+{
+    #[inline]
+    pub fn name(&self) -> Option<&'t str> {
+        self.name
+    }
+
+    #[inline]
+    pub fn entry(&self) -> &'e PakEntry {
+        self.entry
+    }
+
+    /// Open a streaming reader of the decoded entry data.
+    pub fn reader(self) -> Result<PakEntryReader<PartReader<&'e mut R>>> {
+        PakEntryReader::new_streaming(self.reader, self.entry)
+    }
+}
+
 pub enum OwnedPakArchive<'a> {
     Owned(PakArchive),
     Borrowed(&'a PakArchive),
@@ -65,3 +192,42 @@ impl<'a> OwnedPakArchive<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+    use crate::read::read_archive;
+    use crate::write::{FileOptions, PakWriter};
+
+    #[test]
+    fn test_iter_named() {
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 2).unwrap();
+        for (path, data) in [("natives/a.txt", b"first"), ("natives/b.txt", b"secnd")] {
+            writer.start_file(path, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut pak = writer.finish().unwrap();
+        pak.set_position(0);
+        let archive = read_archive(&mut pak).unwrap();
+        let table = FileNameTable::from_list("natives/b.txt".to_string());
+
+        let mut reader = PakArchiveReader::new(&mut pak, &archive);
+        let mut entries = reader.iter_named(&table);
+        let mut found = vec![];
+        while let Some(named) = entries.next_entry() {
+            let name = named.name();
+            let mut data = String::new();
+            named.reader().unwrap().read_to_string(&mut data).unwrap();
+            found.push((name, data));
+        }
+        assert_eq!(
+            found,
+            [
+                (None, "first".to_string()),
+                (Some("natives/b.txt"), "secnd".to_string())
+            ]
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use std::io::{BufRead, Read};
 
-use crate::error::Result;
+use crate::error::{PakError, Result};
 use crate::pak::CompressionMethod;
 
 /// Read a compressed file.
 pub enum CompressedReader<R> {
     Store(R),
+    #[cfg(feature = "deflate")]
     Deflate(flate2::bufread::DeflateDecoder<R>),
+    #[cfg(feature = "zstd")]
     Zstd(zstd::Decoder<'static, R>),
 }
 
@@ -17,10 +19,24 @@ where
     pub fn new(reader: R, compression: CompressionMethod) -> Result<Self> {
         Ok(match compression {
             CompressionMethod::None => Self::Store(reader),
+            #[cfg(feature = "deflate")]
             CompressionMethod::Deflate => Self::Deflate(flate2::bufread::DeflateDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
             CompressionMethod::Zstd => Self::Zstd(zstd::stream::Decoder::with_buffer(reader)?),
+            #[allow(unreachable_patterns)]
+            compression => return Err(PakError::CodecDisabled(compression)),
         })
     }
+
+    pub fn into_inner(self) -> R {
+        match self {
+            CompressedReader::Store(inner) => inner,
+            #[cfg(feature = "deflate")]
+            CompressedReader::Deflate(inner) => inner.into_inner(),
+            #[cfg(feature = "zstd")]
+            CompressedReader::Zstd(inner) => inner.finish(),
+        }
+    }
 }
 
 impl<R> Read for CompressedReader<R>
@@ -30,7 +46,9 @@ where
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             CompressedReader::Store(inner) => inner.read(buf),
+            #[cfg(feature = "deflate")]
             CompressedReader::Deflate(inner) => inner.read(buf),
+            #[cfg(feature = "zstd")]
             CompressedReader::Zstd(inner) => inner.read(buf),
         }
     }
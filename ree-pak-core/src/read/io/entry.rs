@@ -1,14 +1,56 @@
-use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+use std::io::{self, BufRead, Cursor, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::error::Result;
-use crate::pak::PakEntry;
+use crate::pak::{CompressionMethod, PakEntry};
+use crate::read::source::SourceReader;
 
 use super::compressed::CompressedReader;
 use super::extension::ExtensionReader;
+use super::part::PartReader;
+
+/// Reject entries whose sizes do not fit their compression, a stored entry with
+/// less data than its size would otherwise read into the next entry.
+fn check_decodable(entry: &PakEntry) -> Result<()> {
+    match entry.suspicion() {
+        Some(suspicion) => Err(crate::read::suspicious_entry(entry, suspicion)),
+        None => Ok(()),
+    }
+}
+
+/// Entry reader holding the entry data in memory.
+pub type OwnedEntryReader = PakEntryReader<Cursor<Vec<u8>>>;
+/// Streaming entry reader with its own reader of a [`PakSource`](crate::read::source::PakSource).
+pub type SourceEntryReader = PakEntryReader<PartReader<SourceReader>>;
 
 /// Read a pak entry file.
+///
+/// Implements [`Seek`] when the part reader is seekable. Uncompressed entries seek directly,
+/// compressed entries are decoded up to the target position, restarting from the beginning
+/// when seeking backwards.
 pub struct PakEntryReader<R> {
-    reader: ExtensionReader<CompressedReader<R>>,
+    // only `None` if restarting the decoder failed
+    reader: Option<ExtensionReader<CompressedReader<CountingReader<R>>>>,
+    compression: CompressionMethod,
+    uncompressed_size: u64,
+    position: u64,
+    compressed_bytes: Arc<AtomicU64>,
+    uncompressed_bytes: u64,
+    decode_time: Duration,
+}
+
+/// Decode statistics of a [`PakEntryReader`], e.g. to find entries dominating extraction time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    pub codec: CompressionMethod,
+    /// Stored bytes consumed, including bytes decoded again after seeking backwards.
+    pub compressed_bytes: u64,
+    /// Decoded bytes returned by reads.
+    pub uncompressed_bytes: u64,
+    /// Time spent in reads and seeks, including reading the stored bytes.
+    pub decode_time: Duration,
 }
 
 impl<R> Read for PakEntryReader<R>
@@ -16,24 +58,96 @@ where
     R: BufRead,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.reader.read(buf)
+        let start = Instant::now();
+        let result = self.inner().and_then(|reader| reader.read(buf));
+        self.decode_time += start.elapsed();
+        let bytes_read = result?;
+        self.position += bytes_read as u64;
+        self.uncompressed_bytes += bytes_read as u64;
+        Ok(bytes_read)
     }
 }
 
-impl PakEntryReader<Cursor<Vec<u8>>> {
+impl<R> Seek for PakEntryReader<R>
+where
+    R: BufRead + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let start = Instant::now();
+        let result = self.seek_inner(pos);
+        self.decode_time += start.elapsed();
+        result
+    }
+}
+
+impl<R> PakEntryReader<R>
+where
+    R: BufRead + Seek,
+{
+    fn seek_inner(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.uncompressed_size.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position"))?;
+        if target == self.position {
+            return Ok(target);
+        }
+
+        // keep the magic bytes for extension detection
+        self.inner()?.fill_magic()?;
+
+        if self.compression == CompressionMethod::None {
+            let reader = self.inner()?;
+            // the only variant in builds without codecs
+            #[allow(irrefutable_let_patterns)]
+            if let CompressedReader::Store(part_reader) = reader.get_mut() {
+                part_reader.inner.seek(SeekFrom::Start(target))?;
+            }
+            reader.discard_buffered();
+            self.position = target;
+            return Ok(target);
+        }
+
+        if target < self.position {
+            self.restart()?;
+        }
+        let skip = target - self.position;
+        io::copy(&mut self.inner()?.take(skip), &mut io::sink())?;
+        self.position = target;
+
+        Ok(target)
+    }
+}
+
+impl OwnedEntryReader {
     /// Create a new owned reader from full pak reader
     pub fn new_owned<R1>(reader: &mut R1, entry: PakEntry) -> Result<Self>
     where
         R1: Read + Seek,
     {
+        check_decodable(&entry)?;
         reader.seek(SeekFrom::Start(entry.offset()))?;
         let mut data = vec![0; entry.real_compressed_size() as usize];
         reader.read_exact(&mut data)?;
         let owned_reader = Cursor::new(data);
 
-        let compression = entry.compression_method();
-        let r = ExtensionReader::new(CompressedReader::new(owned_reader, compression)?);
-        Ok(Self { reader: r })
+        Self::from_part_reader(owned_reader, &entry)
+    }
+}
+
+impl<R> PakEntryReader<PartReader<R>>
+where
+    R: BufRead + Seek,
+{
+    /// Create a new streaming reader from full pak reader.
+    ///
+    /// Entry data is read on demand, so memory usage does not depend on the entry size.
+    pub fn new_streaming(reader: R, entry: &PakEntry) -> Result<Self> {
+        let part_reader = PartReader::new(reader, entry.offset(), entry.real_compressed_size())?;
+
+        Self::from_part_reader(part_reader, entry)
     }
 }
 
@@ -41,13 +155,171 @@ impl<R> PakEntryReader<R>
 where
     R: BufRead,
 {
+    /// Fails with [`PakError::SuspiciousEntry`](crate::error::PakError::SuspiciousEntry)
+    /// for entries with a [`PakEntry::suspicion`], their data cannot be decoded as declared.
     pub fn from_part_reader(part_reader: R, entry: &PakEntry) -> Result<Self> {
+        check_decodable(entry)?;
         let compression = entry.compression_method();
-        let r = ExtensionReader::new(CompressedReader::new(part_reader, compression)?);
-        Ok(Self { reader: r })
+        let compressed_bytes = Arc::new(AtomicU64::new(0));
+        let counting_reader = CountingReader {
+            inner: part_reader,
+            count: compressed_bytes.clone(),
+        };
+        let r = ExtensionReader::new(CompressedReader::new(counting_reader, compression)?);
+        Ok(Self {
+            reader: Some(r),
+            compression,
+            uncompressed_size: entry.uncompressed_size(),
+            position: 0,
+            compressed_bytes,
+            uncompressed_bytes: 0,
+            decode_time: Duration::ZERO,
+        })
+    }
+
+    /// Statistics of the reads so far.
+    pub fn decode_stats(&self) -> DecodeStats {
+        DecodeStats {
+            codec: self.compression,
+            compressed_bytes: self.compressed_bytes.load(Ordering::Relaxed),
+            uncompressed_bytes: self.uncompressed_bytes,
+            decode_time: self.decode_time,
+        }
     }
 
     pub fn determine_extension(&self) -> Option<&str> {
-        self.reader.determine_extension()
+        self.reader.as_ref().and_then(|reader| reader.determine_extension())
+    }
+
+    fn inner(&mut self) -> io::Result<&mut ExtensionReader<CompressedReader<CountingReader<R>>>> {
+        self.reader
+            .as_mut()
+            .ok_or_else(|| io::Error::other("entry reader is unusable after a failed seek"))
+    }
+}
+
+impl<R> PakEntryReader<R>
+where
+    R: BufRead + Seek,
+{
+    /// Restart decoding from the beginning of the entry.
+    fn restart(&mut self) -> io::Result<()> {
+        let Some(reader) = self.reader.take() else {
+            return Err(io::Error::other("entry reader is unusable after a failed seek"));
+        };
+        let (compressed, magic) = reader.into_parts();
+        let mut part_reader = compressed.into_inner();
+        part_reader.inner.rewind()?;
+        let compressed = CompressedReader::new(part_reader, self.compression).map_err(io::Error::other)?;
+        let mut reader = magic.with_inner(compressed);
+        reader.discard_buffered();
+
+        self.reader = Some(reader);
+        self.position = 0;
+        Ok(())
+    }
+}
+
+/// Counts the stored bytes consumed by the decoder.
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.count.fetch_add(amt as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::spec;
+
+    fn entry_of(data: &[u8], compression_method: i64) -> PakEntry {
+        PakEntry::from(spec::EntryV2 {
+            hash_name_lower: 0,
+            hash_name_upper: 0,
+            offset: 0,
+            compressed_size: data.len() as u64,
+            uncompressed_size: 1024,
+            compression_method,
+            checksum: 0,
+        })
+    }
+
+    fn assert_seek<R: BufRead + Seek>(reader: &mut PakEntryReader<R>, content: &[u8]) {
+        let mut buf = [0u8; 4];
+        reader.seek(SeekFrom::Start(100)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[100..104]);
+        reader.seek(SeekFrom::Current(-50)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[54..58]);
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[1020..]);
+        reader.rewind().unwrap();
+        let mut all = vec![];
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, content);
+        assert_eq!(reader.determine_extension(), Some("tex"));
+    }
+
+    #[test]
+    fn test_owned_readers_are_send() {
+        fn assert_send_static<T: Send + 'static>() {}
+        assert_send_static::<OwnedEntryReader>();
+        assert_send_static::<SourceEntryReader>();
+    }
+
+    #[test]
+    fn test_seek() {
+        let content: Vec<u8> = b"TEX\0".iter().copied().chain((4..1024).map(|i| i as u8)).collect();
+
+        let mut stored =
+            PakEntryReader::from_part_reader(Cursor::new(content.clone()), &entry_of(&content, 0)).unwrap();
+        assert_seek(&mut stored, &content);
+
+        let mut encoder = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&content).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut deflate =
+            PakEntryReader::from_part_reader(Cursor::new(compressed.clone()), &entry_of(&compressed, 1)).unwrap();
+        assert_seek(&mut deflate, &content);
+    }
+
+    #[test]
+    fn test_decode_stats() {
+        let content = b"decode stats ".repeat(100);
+        let mut encoder = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader =
+            PakEntryReader::from_part_reader(Cursor::new(compressed.clone()), &entry_of(&compressed, 1)).unwrap();
+        assert_eq!(reader.decode_stats().uncompressed_bytes, 0);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        let stats = reader.decode_stats();
+        assert_eq!(stats.codec, CompressionMethod::Deflate);
+        assert_eq!(stats.compressed_bytes, compressed.len() as u64);
+        assert_eq!(stats.uncompressed_bytes, content.len() as u64);
+        assert!(stats.decode_time > Duration::ZERO);
     }
 }
@@ -5,6 +5,8 @@ pub struct ExtensionReader<R> {
     reader: R,
     magic_bytes: [u8; 8],
     magic_read_length: usize,
+    magic_filled: bool,
+    magic_consumed: usize,
 }
 
 impl<R> Read for ExtensionReader<R>
@@ -12,22 +14,13 @@ where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if self.magic_read_length < 8 {
-            let bytes_to_read = 8 - self.magic_read_length;
-            let bytes_read = self
-                .reader
-                .read(&mut self.magic_bytes[self.magic_read_length..bytes_to_read])?;
-            self.magic_read_length += bytes_read;
-
-            let bytes_to_copy = self.magic_read_length.min(buf.len());
-            buf[..bytes_to_copy].copy_from_slice(&self.magic_bytes[..bytes_to_copy]);
-
-            if bytes_to_copy == 8 {
-                let remaining = &mut buf[bytes_to_copy..];
-                let additional_read = self.reader.read(remaining)?;
-                return Ok(bytes_to_copy + additional_read);
-            }
+        self.fill_magic()?;
 
+        if self.magic_consumed < self.magic_read_length {
+            let bytes_to_copy = (self.magic_read_length - self.magic_consumed).min(buf.len());
+            buf[..bytes_to_copy]
+                .copy_from_slice(&self.magic_bytes[self.magic_consumed..self.magic_consumed + bytes_to_copy]);
+            self.magic_consumed += bytes_to_copy;
             return Ok(bytes_to_copy);
         }
 
@@ -35,6 +28,46 @@ where
     }
 }
 
+impl<R> ExtensionReader<R> {
+    /// Drop buffered magic bytes not yet returned, following reads come from the inner reader directly.
+    ///
+    /// Used after the inner reader is repositioned, the magic bytes are kept for extension detection.
+    pub fn discard_buffered(&mut self) {
+        self.magic_consumed = self.magic_read_length;
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Split into the inner reader and the magic bytes state.
+    pub fn into_parts(self) -> (R, ExtensionReader<()>) {
+        let magic = ExtensionReader {
+            reader: (),
+            magic_bytes: self.magic_bytes,
+            magic_read_length: self.magic_read_length,
+            magic_filled: self.magic_filled,
+            magic_consumed: self.magic_consumed,
+        };
+        (self.reader, magic)
+    }
+
+    /// Replace the inner reader, keeping the magic bytes already read.
+    pub fn with_inner<R2>(self, reader: R2) -> ExtensionReader<R2> {
+        ExtensionReader {
+            reader,
+            magic_bytes: self.magic_bytes,
+            magic_read_length: self.magic_read_length,
+            magic_filled: self.magic_filled,
+            magic_consumed: self.magic_consumed,
+        }
+    }
+}
+
 impl<R> ExtensionReader<R>
 where
     R: Read,
@@ -44,9 +77,23 @@ where
             reader,
             magic_bytes: [0; 8],
             magic_read_length: 0,
+            magic_filled: false,
+            magic_consumed: 0,
         }
     }
 
+    /// Read the magic bytes from the start of the stream if not read yet.
+    pub fn fill_magic(&mut self) -> std::io::Result<()> {
+        // fill magic bytes before passing data through
+        while !self.magic_filled {
+            let bytes_read = self.reader.read(&mut self.magic_bytes[self.magic_read_length..])?;
+            self.magic_read_length += bytes_read;
+            self.magic_filled = bytes_read == 0 || self.magic_read_length == 8;
+        }
+
+        Ok(())
+    }
+
     pub fn magic_bytes(&self) -> &[u8; 8] {
         &self.magic_bytes
     }
@@ -221,3 +268,36 @@ where
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_in_small_chunks() {
+        let data = b"TEX\0\x01\x02\x03\x04remaining data";
+        let mut reader = ExtensionReader::new(&data[..]);
+        let mut output = vec![];
+        let mut buf = [0u8; 3];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+        }
+
+        assert_eq!(output, data);
+        assert_eq!(reader.determine_extension(), Some("tex"));
+    }
+
+    #[test]
+    fn test_read_short_data() {
+        let mut reader = ExtensionReader::new(&b"short"[..]);
+        let mut output = vec![];
+        reader.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, b"short");
+        assert_eq!(reader.determine_extension(), None);
+    }
+}
@@ -2,3 +2,4 @@ pub mod archive;
 pub mod compressed;
 pub mod entry;
 pub mod extension;
+pub mod part;
@@ -0,0 +1,91 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+use crate::pak::PakEntry;
+
+/// Reads a byte range of the underlying reader as if it were the whole stream.
+pub struct PartReader<R> {
+    reader: R,
+    start: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<R> PartReader<R>
+where
+    R: Seek,
+{
+    pub fn new(mut reader: R, start: u64, len: u64) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            reader,
+            start,
+            len,
+            position: 0,
+        })
+    }
+
+    /// Read the stored bytes of an entry as they are, without decompression or decryption.
+    pub fn raw_entry(reader: R, entry: &PakEntry) -> io::Result<Self> {
+        Self::new(reader, entry.offset(), entry.real_compressed_size())
+    }
+}
+
+impl<R> PartReader<R> {
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.position)
+    }
+}
+
+impl<R> Read for PartReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = (buf.len() as u64).min(self.remaining()) as usize;
+        let bytes_read = self.reader.read(&mut buf[..max])?;
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R> BufRead for PartReader<R>
+where
+    R: BufRead,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let remaining = self.remaining();
+        if remaining == 0 {
+            return Ok(&[]);
+        }
+        let buf = self.reader.fill_buf()?;
+        let max = (buf.len() as u64).min(remaining) as usize;
+        Ok(&buf[..max])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt);
+        self.position += amt as u64;
+    }
+}
+
+impl<R> Seek for PartReader<R>
+where
+    R: Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position"))?;
+
+        self.reader.seek(SeekFrom::Start(self.start + position))?;
+        self.position = position;
+        Ok(position)
+    }
+}
@@ -1,22 +1,213 @@
 pub mod io;
+pub mod multi;
+pub mod order;
+pub mod source;
+pub mod temp;
 
 use std::io::{Cursor, Read};
+use std::sync::Arc;
 
-use crate::error::Result;
-use crate::pak::{self, PakArchive, PakEntry, PakHeader};
+use crate::error::{PakError, Result};
+use crate::pak::{self, CompressionMethod, EntrySuspicion, PakArchive, PakEntry, PakHeader};
 use crate::spec;
 
+/// Default limit of entries in a pak, an entry table of 384 MiB.
+///
+/// Higher counts are taken as a corrupt header unless the limit is raised.
+pub const DEFAULT_MAX_ENTRIES: u32 = 8 * 1024 * 1024;
+
+/// Handling of stored entries with data but an uncompressed size of zero.
+///
+/// Some tables only fill the stored size of uncompressed entries, others
+/// leave it over from a broken conversion, so the size to trust is a choice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroSizePolicy {
+    /// Read the stored data, its size becomes the uncompressed size.
+    #[default]
+    StoredSize,
+    /// Treat the entry as an empty file.
+    Empty,
+    /// Fail with [`PakError::SuspiciousEntry`].
+    Reject,
+}
+
+/// Diagnostics emitted while reading an entry table, see [`ReadOptions::on_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadEvent {
+    /// A stored entry had data but no uncompressed size, `size` is the size used under the [`ZeroSizePolicy`].
+    ZeroSizeFallback { hash: u64, stored_size: u64, size: u64 },
+    /// An entry has a [`PakEntry::suspicion`] and is kept, as validation is disabled.
+    SuspiciousEntry { hash: u64, suspicion: EntrySuspicion },
+}
+
+type EventCallback = Arc<dyn Fn(&ReadEvent) + Send + Sync>;
+type HeaderCheck = Arc<dyn Fn(&PakHeader, &[u8]) -> std::result::Result<(), String> + Send + Sync>;
+
+/// Offset of the header bytes after the entry count, the field read as [`PakHeader::hash`].
+pub const HEADER_TAIL_OFFSET: usize = 12;
+
+/// Options of [`read_archive_with_options`].
+#[derive(Clone)]
+pub struct ReadOptions {
+    max_entries: u32,
+    validate_entries: bool,
+    zero_size_policy: ZeroSizePolicy,
+    magic_scan_window: u64,
+    on_event: Option<EventCallback>,
+    header_check: Option<HeaderCheck>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            validate_entries: false,
+            zero_size_policy: ZeroSizePolicy::default(),
+            magic_scan_window: 0,
+            on_event: None,
+            header_check: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ReadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadOptions")
+            .field("max_entries", &self.max_entries)
+            .field("validate_entries", &self.validate_entries)
+            .field("zero_size_policy", &self.zero_size_policy)
+            .field("magic_scan_window", &self.magic_scan_window)
+            .field("on_event", &self.on_event.is_some())
+            .field("header_check", &self.header_check.is_some())
+            .finish()
+    }
+}
+
+impl ReadOptions {
+    /// Reject headers declaring more entries, [`DEFAULT_MAX_ENTRIES`] by default.
+    pub fn with_max_entries(mut self, max_entries: u32) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Reject entries whose sizes do not fit their compression, disabled by default.
+    ///
+    /// Without it such paks are read anyway, so the other entries stay readable.
+    /// Each inconsistent entry is reported as [`ReadEvent::SuspiciousEntry`], and
+    /// opening a [`PakEntryReader`](io::entry::PakEntryReader) for it fails with
+    /// [`PakError::SuspiciousEntry`].
+    pub fn with_validate_entries(mut self, validate_entries: bool) -> Self {
+        self.validate_entries = validate_entries;
+        self
+    }
+
+    /// Set the handling of stored entries with data but no uncompressed size.
+    pub fn with_zero_size_policy(mut self, zero_size_policy: ZeroSizePolicy) -> Self {
+        self.zero_size_policy = zero_size_policy;
+        self
+    }
+
+    /// Search for the header magic up to `window` bytes into the file, for dumps with junk before the header.
+    ///
+    /// Disabled with 0, the default. Entry offsets are relative to the header, so
+    /// they are moved by the [`PakArchive::base_offset`] the header is found at.
+    pub fn with_magic_scan(mut self, window: u64) -> Self {
+        self.magic_scan_window = window;
+        self
+    }
+
+    /// Receive diagnostics about entries read with a fallback.
+    pub fn on_event<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ReadEvent) + Send + Sync + 'static,
+    {
+        self.on_event = Some(Arc::new(callback));
+        self
+    }
+
+    /// Check the header before the entry table is read, with the raw bytes from [`HEADER_TAIL_OFFSET`].
+    ///
+    /// The meaning of the tail is not known and may differ per title, the check
+    /// lets research code try interpretations. An error of the check fails the
+    /// read with [`PakError::HeaderRejected`]. The tail is kept as
+    /// [`PakHeader::hash`] either way, so it survives rewriting the pak.
+    pub fn with_header_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&PakHeader, &[u8]) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.header_check = Some(Arc::new(check));
+        self
+    }
+
+    #[inline]
+    pub fn max_entries(&self) -> u32 {
+        self.max_entries
+    }
+
+    #[inline]
+    pub fn validate_entries(&self) -> bool {
+        self.validate_entries
+    }
+
+    #[inline]
+    pub fn zero_size_policy(&self) -> ZeroSizePolicy {
+        self.zero_size_policy
+    }
+
+    #[inline]
+    pub fn magic_scan_window(&self) -> u64 {
+        self.magic_scan_window
+    }
+
+    fn emit(&self, event: ReadEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(&event);
+        }
+    }
+}
+
 pub fn read_archive<R>(reader: &mut R) -> Result<PakArchive>
+where
+    R: Read,
+{
+    read_archive_with_options(reader, ReadOptions::default())
+}
+
+/// Read the header and entry table, rejecting entry counts above the limit of the options.
+///
+/// The entry table buffer grows as it is read, so a truncated file fails
+/// without allocating the size its header declares. Inconsistent entries fail
+/// with [`PakError::SuspiciousEntry`] if validation is enabled, otherwise they
+/// are reported as [`ReadEvent::SuspiciousEntry`].
+pub fn read_archive_with_options<R>(reader: &mut R, options: ReadOptions) -> Result<PakArchive>
 where
     R: Read,
 {
     // read header
-    let spec_header = spec::Header::from_reader(reader)?;
+    let (spec_header, base_offset) = if options.magic_scan_window > 0 {
+        scan_header(reader, options.magic_scan_window)?
+    } else {
+        (spec::Header::from_reader(reader)?, 0)
+    };
+    let raw_header = spec_header.clone().into_bytes();
     let header = PakHeader::try_from(spec_header)?;
+    if let Some(check) = &options.header_check {
+        check(&header, &raw_header[HEADER_TAIL_OFFSET..]).map_err(PakError::HeaderRejected)?;
+    }
+    if header.total_files() > options.max_entries {
+        return Err(PakError::EntryCountLimit {
+            found: header.total_files(),
+            limit: options.max_entries,
+        });
+    }
 
     // read entries
-    let mut entry_table_bytes = vec![0; (header.entry_size() * header.total_files()) as usize];
-    reader.read_exact(&mut entry_table_bytes)?;
+    let table_size = header.entry_size() as u64 * header.total_files() as u64;
+    let mut entry_table_bytes = vec![];
+    reader.take(table_size).read_to_end(&mut entry_table_bytes)?;
+    if (entry_table_bytes.len() as u64) < table_size {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
     // decrypt
     if header.feature() == 8 {
         let mut raw_key = [0; 128];
@@ -24,19 +215,110 @@ where
         entry_table_bytes = pak::decrypt_data(&entry_table_bytes, &raw_key);
     }
     // parse entries
-    let entries = read_entries(&mut Cursor::new(&entry_table_bytes), &header)?;
+    let mut entries = read_entries(&mut Cursor::new(&entry_table_bytes), &header)?;
+    resolve_zero_sizes(&mut entries, &options)?;
+    validate_entries(&entries, &options)?;
+    if base_offset > 0 {
+        for entry in entries.iter_mut() {
+            *entry = entry.with_data(
+                entry.offset() + base_offset,
+                entry.compressed_size(),
+                entry.compression_method(),
+            );
+        }
+    }
 
-    Ok(PakArchive::new(header, entries))
+    Ok(PakArchive::new(header, entries).with_base_offset(base_offset))
+}
+
+/// Read up to the first header magic within `window` bytes, returning the header and its offset.
+///
+/// The reader is not seekable, so it is consumed byte by byte and left after the header.
+fn scan_header<R>(reader: &mut R, window: u64) -> Result<(spec::Header, u64)>
+where
+    R: Read,
+{
+    let mut head = [0u8; spec::Header::SIZE];
+    reader.read_exact(&mut head[..4])?;
+    let mut offset = 0;
+    while head[..4] != pak::MAGIC {
+        if offset >= window {
+            return Err(PakError::InvalidMagic {
+                expected: pak::MAGIC,
+                found: head[..4].try_into().unwrap(),
+            });
+        }
+        head.copy_within(1..4, 0);
+        reader.read_exact(&mut head[3..4])?;
+        offset += 1;
+    }
+    reader.read_exact(&mut head[4..])?;
+
+    Ok((spec::Header::from_bytes(&head)?, offset))
+}
+
+/// Apply the [`ZeroSizePolicy`] of the options to stored entries with data but no uncompressed size.
+fn resolve_zero_sizes(entries: &mut [PakEntry], options: &ReadOptions) -> Result<()> {
+    for entry in entries.iter_mut() {
+        let missing_size = entry.compression_method() == CompressionMethod::None
+            && entry.encryption_type() == 0
+            && entry.uncompressed_size() == 0
+            && entry.compressed_size() != 0;
+        if !missing_size {
+            continue;
+        }
+
+        let size = match options.zero_size_policy {
+            ZeroSizePolicy::StoredSize => entry.compressed_size(),
+            ZeroSizePolicy::Empty => 0,
+            ZeroSizePolicy::Reject => return Err(suspicious_entry(entry, EntrySuspicion::ZeroUncompressedSize)),
+        };
+        options.emit(ReadEvent::ZeroSizeFallback {
+            hash: entry.hash(),
+            stored_size: entry.compressed_size(),
+            size,
+        });
+        *entry = entry.with_stored_size(size);
+    }
+
+    Ok(())
+}
+
+/// Fail on the first entry with a [`PakEntry::suspicion`], or report each one if validation is disabled.
+fn validate_entries(entries: &[PakEntry], options: &ReadOptions) -> Result<()> {
+    for entry in entries {
+        let Some(suspicion) = entry.suspicion() else {
+            continue;
+        };
+        if options.validate_entries {
+            return Err(suspicious_entry(entry, suspicion));
+        }
+        options.emit(ReadEvent::SuspiciousEntry {
+            hash: entry.hash(),
+            suspicion,
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn suspicious_entry(entry: &PakEntry, suspicion: EntrySuspicion) -> PakError {
+    PakError::SuspiciousEntry {
+        hash: entry.hash(),
+        suspicion,
+        method: entry.compression_method(),
+        compressed_size: entry.compressed_size(),
+        uncompressed_size: entry.uncompressed_size(),
+    }
 }
 
 fn read_entries<R>(reader: &mut R, header: &PakHeader) -> Result<Vec<PakEntry>>
 where
     R: Read,
 {
-    if header.major_version() == 2 && header.minor_version() == 0 {
-        read_entries_v1(reader, header.total_files())
-    } else {
-        read_entries_v2(reader, header.total_files())
+    match header.toc_layout() {
+        spec::TocLayout::V1 => read_entries_v1(reader, header.total_files()),
+        spec::TocLayout::V2 => read_entries_v2(reader, header.total_files()),
     }
 }
 
@@ -67,3 +349,224 @@ where
 
     Ok(entries)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::io::entry::PakEntryReader;
+    use super::*;
+
+    fn header_bytes(total_files: u32) -> Vec<u8> {
+        let header = PakHeader::new(4, 0, 0, total_files, 0).unwrap();
+        spec::Header::from(&header).into_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_entry_count_limit() {
+        let pak = header_bytes(u32::MAX);
+        assert!(matches!(
+            read_archive(&mut pak.as_slice()),
+            Err(PakError::EntryCountLimit {
+                found: u32::MAX,
+                limit: DEFAULT_MAX_ENTRIES
+            })
+        ));
+
+        // within a raised limit, the truncated table is reported without allocating it
+        let options = ReadOptions::default().with_max_entries(u32::MAX);
+        assert!(matches!(
+            read_archive_with_options(&mut pak.as_slice(), options),
+            Err(PakError::IO(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+
+        let options = ReadOptions::default().with_max_entries(0);
+        assert!(read_archive_with_options(&mut header_bytes(0).as_slice(), options.clone()).is_ok());
+        assert!(read_archive_with_options(&mut header_bytes(1).as_slice(), options).is_err());
+    }
+
+    #[test]
+    fn test_header_check() {
+        let mut pak = header_bytes(0);
+        pak[HEADER_TAIL_OFFSET..].copy_from_slice(&0xDEADBEEF_u32.to_le_bytes());
+        let tail = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = tail.clone();
+        let options = ReadOptions::default().with_header_check(move |header, tail| {
+            seen.lock().unwrap().extend_from_slice(tail);
+            if header.hash() == 0xDEADBEEF {
+                Err("unsigned".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(matches!(
+            read_archive_with_options(&mut pak.as_slice(), options.clone()),
+            Err(PakError::HeaderRejected(reason)) if reason == "unsigned"
+        ));
+        assert_eq!(*tail.lock().unwrap(), [0xEF, 0xBE, 0xAD, 0xDE]);
+        assert!(read_archive_with_options(&mut header_bytes(0).as_slice(), options).is_ok());
+    }
+
+    /// A v4.0 pak table with one entry of the given sizes and compression field.
+    fn single_entry_pak(compressed_size: u64, uncompressed_size: u64, compression_method: i64) -> Vec<u8> {
+        let mut pak = header_bytes(1);
+        let entry = spec::EntryV2 {
+            hash_name_lower: 1,
+            hash_name_upper: 2,
+            offset: 0,
+            compressed_size,
+            uncompressed_size,
+            compression_method,
+            checksum: 0,
+        };
+        pak.extend_from_slice(&entry.into_bytes());
+        pak
+    }
+
+    #[test]
+    fn test_suspicious_entries() {
+        let validate = || ReadOptions::default().with_validate_entries(true);
+        for (compressed_size, uncompressed_size, method) in [(100, 100, 0), (0, 100, 0), (60, 100, 2), (110, 100, 1)] {
+            let pak = single_entry_pak(compressed_size, uncompressed_size, method);
+            assert!(read_archive_with_options(&mut pak.as_slice(), validate()).is_ok());
+        }
+        // resource encryption keeps sizes of compressed data with the method cleared
+        let pak = single_entry_pak(60, 100, 2 | 1 << 16);
+        assert!(read_archive_with_options(&mut pak.as_slice(), validate()).is_ok());
+
+        let pak = single_entry_pak(60, 100, 0);
+        assert!(matches!(
+            read_archive_with_options(&mut pak.as_slice(), validate()),
+            Err(PakError::SuspiciousEntry {
+                hash: 0x2_0000_0001,
+                suspicion: EntrySuspicion::LikelyChunked,
+                ..
+            })
+        ));
+        // by default the entry is kept and reported
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = events.clone();
+        let options = ReadOptions::default().on_event(move |event| seen.lock().unwrap().push(event.clone()));
+        let archive = read_archive_with_options(&mut pak.as_slice(), options).unwrap();
+        assert_eq!(archive.entries()[0].suspicion(), Some(EntrySuspicion::LikelyChunked));
+        assert_eq!(
+            *events.lock().unwrap(),
+            [ReadEvent::SuspiciousEntry {
+                hash: 0x2_0000_0001,
+                suspicion: EntrySuspicion::LikelyChunked,
+            }]
+        );
+        // decoding it fails instead of reading past its data
+        let mut reader = Cursor::new(pak.clone());
+        for result in [
+            PakEntryReader::new_streaming(&mut reader, &archive.entries()[0]).map(|_| ()),
+            PakEntryReader::new_owned(&mut reader, archive.entries()[0].clone()).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(PakError::SuspiciousEntry {
+                    suspicion: EntrySuspicion::LikelyChunked,
+                    ..
+                })
+            ));
+        }
+
+        for (compressed_size, uncompressed_size, method) in [(160, 100, 0), (1 << 20, 100, 2)] {
+            let pak = single_entry_pak(compressed_size, uncompressed_size, method);
+            assert!(matches!(
+                read_archive_with_options(&mut pak.as_slice(), validate()),
+                Err(PakError::SuspiciousEntry {
+                    suspicion: EntrySuspicion::LikelyWrongVersion,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_magic_scan() {
+        let data_offset = (spec::Header::SIZE + spec::EntryV2::SIZE) as u64;
+        let mut pak = single_entry_pak(5, 5, 0);
+        pak[spec::Header::SIZE + 8..spec::Header::SIZE + 16].copy_from_slice(&data_offset.to_le_bytes());
+        pak.extend_from_slice(b"hello");
+        // junk with a partial magic in it
+        let mut dumped = b"KPK\0junk".to_vec();
+        dumped.extend_from_slice(&pak);
+
+        assert!(matches!(
+            read_archive(&mut dumped.as_slice()),
+            Err(PakError::InvalidMagic { .. })
+        ));
+        let options = ReadOptions::default().with_magic_scan(4);
+        assert!(matches!(
+            read_archive_with_options(&mut dumped.as_slice(), options),
+            Err(PakError::InvalidMagic { .. })
+        ));
+
+        let options = ReadOptions::default().with_magic_scan(1024);
+        let archive = read_archive_with_options(&mut dumped.as_slice(), options.clone()).unwrap();
+        assert_eq!(archive.base_offset(), 8);
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.offset(), data_offset + 8);
+        let mut data = vec![];
+        io::entry::PakEntryReader::new_owned(&mut Cursor::new(&dumped), entry.clone())
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"hello");
+
+        let archive = read_archive_with_options(&mut pak.as_slice(), options).unwrap();
+        assert_eq!((archive.base_offset(), archive.entries()[0].offset()), (0, data_offset));
+    }
+
+    #[test]
+    fn test_zero_size_policy() {
+        // v1 tables and empty compressed files keep their sizes without a fallback
+        for (compressed_size, uncompressed_size, method) in [(0, 100, 0), (0, 0, 0), (9, 0, 2), (100, 100, 0)] {
+            let pak = single_entry_pak(compressed_size, uncompressed_size, method);
+            let options = ReadOptions::default()
+                .with_zero_size_policy(ZeroSizePolicy::Reject)
+                .on_event(|event| panic!("unexpected {:?}", event));
+            let archive = read_archive_with_options(&mut pak.as_slice(), options).unwrap();
+            assert_eq!(archive.entries()[0].uncompressed_size(), uncompressed_size);
+        }
+
+        // a stored entry with data but no uncompressed size
+        let data_offset = (spec::Header::SIZE + spec::EntryV2::SIZE) as u64;
+        let mut pak = single_entry_pak(5, 0, 0);
+        pak[spec::Header::SIZE + 8..spec::Header::SIZE + 16].copy_from_slice(&data_offset.to_le_bytes());
+        pak.extend_from_slice(b"hello");
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = events.clone();
+        let options = ReadOptions::default().on_event(move |event| recorded.lock().unwrap().push(event.clone()));
+        let archive = read_archive_with_options(&mut pak.as_slice(), options).unwrap();
+        let entry = &archive.entries()[0];
+        assert_eq!((entry.compressed_size(), entry.uncompressed_size()), (5, 5));
+        let mut data = vec![];
+        io::entry::PakEntryReader::new_owned(&mut Cursor::new(&pak), entry.clone())
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(
+            *events.lock().unwrap(),
+            [ReadEvent::ZeroSizeFallback {
+                hash: 0x2_0000_0001,
+                stored_size: 5,
+                size: 5
+            }]
+        );
+
+        let options = ReadOptions::default().with_zero_size_policy(ZeroSizePolicy::Empty);
+        let archive = read_archive_with_options(&mut pak.as_slice(), options).unwrap();
+        assert_eq!(archive.entries()[0].real_compressed_size(), 0);
+
+        let options = ReadOptions::default().with_zero_size_policy(ZeroSizePolicy::Reject);
+        assert!(matches!(
+            read_archive_with_options(&mut pak.as_slice(), options),
+            Err(PakError::SuspiciousEntry {
+                suspicion: EntrySuspicion::ZeroUncompressedSize,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::pak::{self, PakArchive, PakEntry};
+
+use super::io::entry::{PakEntryReader, SourceEntryReader};
+use super::io::part::PartReader;
+use super::source::{PakSource, ReadSeek, SourceReader};
+
+/// A set of paks layered in patch order.
+///
+/// Later paks override entries of earlier ones with the same hash,
+/// e.g. `re_chunk_000.pak.patch_002.pak` overrides `re_chunk_000.pak.patch_001.pak`.
+pub struct MultiPak {
+    paks: Vec<MultiPakSource>,
+    entries: Vec<MultiPakEntry>,
+}
+
+/// A pak of a [`MultiPak`].
+pub struct MultiPakSource {
+    pub source: PakSource,
+    pub archive: PakArchive,
+}
+
+/// Where an effective entry of a [`MultiPak`] comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryProvenance {
+    /// Pak the effective entry is read from.
+    pub pak_index: usize,
+    /// Earlier paks with an entry of the same hash, in patch order.
+    pub overridden: Vec<usize>,
+}
+
+/// An effective entry of a [`MultiPak`], with the index of the pak it comes from.
+#[derive(Debug, Clone)]
+pub struct MultiPakEntry {
+    pub pak_index: usize,
+    pub entry: PakEntry,
+}
+
+impl MultiPak {
+    /// Load paks in the given order, later paks take priority.
+    pub fn open<P>(paths: &[P]) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_sources(paths.iter().map(|path| PakSource::File(path.as_ref().to_path_buf())))
+    }
+
+    /// Load paks from sources in the given order, later paks take priority.
+    pub fn open_sources<I>(sources: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = PakSource>,
+    {
+        let mut paks = vec![];
+        for source in sources {
+            let archive = super::read_archive(&mut source.open()?)?;
+            paks.push(MultiPakSource { source, archive });
+        }
+
+        Ok(Self::from_sources(paks))
+    }
+
+    /// Load a single pak held in memory.
+    pub fn from_bytes<B>(bytes: B) -> Result<Self>
+    where
+        B: Into<Arc<[u8]>>,
+    {
+        Self::open_sources([PakSource::Bytes(bytes.into())])
+    }
+
+    /// Load a single pak from a seekable reader, e.g. an embedded resource or a network stream.
+    pub fn from_reader<R>(reader: R) -> Result<Self>
+    where
+        R: ReadSeek + 'static,
+    {
+        Self::open_sources([PakSource::from_reader(reader)])
+    }
+
+    /// Load all `.pak` files in a directory, sorted in patch order.
+    pub fn open_dir<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut paths = vec![];
+        for dir_entry in std::fs::read_dir(dir.as_ref())? {
+            let path = dir_entry?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pak")) {
+                paths.push(path);
+            }
+        }
+        sort_patch_order(&mut paths);
+
+        Self::open(&paths)
+    }
+
+    pub fn from_sources(paks: Vec<MultiPakSource>) -> Self {
+        let mut entries: Vec<MultiPakEntry> = vec![];
+        let mut positions: HashMap<u64, usize> = HashMap::new();
+        for (pak_index, pak) in paks.iter().enumerate() {
+            for entry in pak.archive.entries() {
+                let item = MultiPakEntry {
+                    pak_index,
+                    entry: entry.clone(),
+                };
+                match positions.get(&entry.hash()) {
+                    Some(&pos) => entries[pos] = item,
+                    None => {
+                        positions.insert(entry.hash(), entries.len());
+                        entries.push(item);
+                    }
+                }
+            }
+        }
+
+        Self { paks, entries }
+    }
+
+    #[inline]
+    pub fn paks(&self) -> &[MultiPakSource] {
+        &self.paks
+    }
+
+    /// Effective entries after patch layering, in order of first appearance.
+    #[inline]
+    pub fn entries(&self) -> &[MultiPakEntry] {
+        &self.entries
+    }
+
+    /// Provenance of the effective entries, in the order of [`MultiPak::entries`].
+    pub fn provenance(&self) -> Vec<EntryProvenance> {
+        let mut paks_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (pak_index, pak) in self.paks.iter().enumerate() {
+            for entry in pak.archive.entries() {
+                let paks = paks_by_hash.entry(entry.hash()).or_default();
+                if paks.last() != Some(&pak_index) {
+                    paks.push(pak_index);
+                }
+            }
+        }
+
+        self.entries
+            .iter()
+            .map(|item| {
+                let mut overridden = paks_by_hash.remove(&item.entry.hash()).unwrap_or_default();
+                overridden.retain(|&pak_index| pak_index != item.pak_index);
+                EntryProvenance {
+                    pak_index: item.pak_index,
+                    overridden,
+                }
+            })
+            .collect()
+    }
+
+    /// Open a new reader of a pak, e.g. one per worker.
+    pub fn open_reader(&self, pak_index: usize) -> Result<SourceReader> {
+        Ok(self.paks[pak_index].source.open()?)
+    }
+
+    /// Open a streaming reader of an entry with its own reader of the pak.
+    ///
+    /// The reader is `Send + 'static`, so it can be moved into worker threads.
+    pub fn open_entry(&self, item: &MultiPakEntry) -> Result<SourceEntryReader> {
+        PakEntryReader::new_streaming(self.open_reader(item.pak_index)?, &item.entry)
+    }
+
+    /// Open a reader of the stored bytes of an entry, without decompression or decryption.
+    pub fn open_entry_raw(&self, item: &MultiPakEntry) -> Result<PartReader<SourceReader>> {
+        Ok(PartReader::raw_entry(self.open_reader(item.pak_index)?, &item.entry)?)
+    }
+
+    /// Get the effective entries in an ordinal index range.
+    pub fn entries_in_range<B>(&self, range: B) -> Result<&[MultiPakEntry]>
+    where
+        B: std::ops::RangeBounds<usize>,
+    {
+        pak::slice_range(&self.entries, range)
+    }
+}
+
+/// Get the patch number of a pak file name, e.g. 3 for `re_chunk_000.pak.patch_003.pak`.
+pub fn patch_number(file_name: &str) -> Option<u32> {
+    let (_, patch) = file_name.rsplit_once(".patch_")?;
+    let digits: String = patch.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    digits.parse().ok()
+}
+
+/// Get the base pak file name of a patch, e.g. `re_chunk_000.pak` for `re_chunk_000.pak.patch_003.pak`.
+pub fn patch_base_name(file_name: &str) -> Option<&str> {
+    patch_number(file_name)?;
+    file_name.rsplit_once(".patch_").map(|(base, _)| base)
+}
+
+/// Find the patches of a base pak in its directory.
+///
+/// Returns the base pak and its patches in patch order, with the number following
+/// the last patch. Patches are files named `<base>.patch_<number>.pak`.
+pub fn find_patches<P>(base_path: P) -> Result<(Vec<PathBuf>, u32)>
+where
+    P: AsRef<Path>,
+{
+    let base_path = base_path.as_ref();
+    let base_name = base_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir = match base_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut paths = vec![base_path.to_path_buf()];
+    let mut last_patch = 0;
+    for dir_entry in std::fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !path.is_file() || patch_base_name(&file_name) != Some(&base_name) || !file_name.ends_with(".pak") {
+            continue;
+        }
+        if let Some(number) = patch_number(&file_name) {
+            last_patch = last_patch.max(number);
+            paths.push(path);
+        }
+    }
+    sort_patch_order(&mut paths);
+
+    Ok((paths, last_patch + 1))
+}
+
+/// Find the base pak and the earlier patches of a patch pak in its directory.
+///
+/// Returns the paths in patch order ending with the patch itself, or `None`
+/// if the file is not a patch or its base pak is not in the same directory.
+pub fn find_patch_chain<P>(patch_path: P) -> Result<Option<Vec<PathBuf>>>
+where
+    P: AsRef<Path>,
+{
+    let patch_path = patch_path.as_ref();
+    let file_name = patch_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (Some(number), Some(base_name)) = (patch_number(&file_name), patch_base_name(&file_name)) else {
+        return Ok(None);
+    };
+    let base_path = patch_path.with_file_name(base_name);
+    if !base_path.is_file() {
+        return Ok(None);
+    }
+
+    let (mut paths, _) = find_patches(&base_path)?;
+    paths.retain(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        patch_number(&name).is_none_or(|n| n < number)
+    });
+    paths.push(patch_path.to_path_buf());
+
+    Ok(Some(paths))
+}
+
+/// Sort pak paths so base paks come first, followed by patches in ascending order.
+pub fn sort_patch_order(paths: &mut [PathBuf]) {
+    paths.sort_by_cached_key(|path| {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        (patch_number(&file_name).unwrap_or(0), file_name)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_patch_order() {
+        let mut paths: Vec<PathBuf> = [
+            "re_chunk_000.pak.patch_010.pak",
+            "re_chunk_000.pak.patch_002.pak",
+            "re_chunk_000.pak",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        sort_patch_order(&mut paths);
+
+        assert_eq!(
+            paths,
+            [
+                "re_chunk_000.pak",
+                "re_chunk_000.pak.patch_002.pak",
+                "re_chunk_000.pak.patch_010.pak"
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(patch_number("re_chunk_000.pak"), None);
+    }
+
+    #[test]
+    fn test_find_patch_chain() {
+        assert_eq!(
+            patch_base_name("re_chunk_000.pak.patch_003.pak"),
+            Some("re_chunk_000.pak")
+        );
+        assert_eq!(patch_base_name("re_chunk_000.pak"), None);
+
+        let dir = std::env::temp_dir().join(format!("ree-pak-patch-chain-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "re_chunk_000.pak",
+            "re_chunk_000.pak.patch_001.pak",
+            "re_chunk_000.pak.patch_002.pak",
+            "re_chunk_000.pak.patch_003.pak",
+            "re_chunk_001.pak.patch_001.pak",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let chain = find_patch_chain(dir.join("re_chunk_000.pak.patch_002.pak")).unwrap();
+        let names: Vec<String> = chain
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "re_chunk_000.pak",
+                "re_chunk_000.pak.patch_001.pak",
+                "re_chunk_000.pak.patch_002.pak"
+            ]
+        );
+        // no base pak
+        assert!(find_patch_chain(dir.join("re_chunk_001.pak.patch_001.pak"))
+            .unwrap()
+            .is_none());
+        assert!(find_patch_chain(dir.join("re_chunk_000.pak")).unwrap().is_none());
+
+        let (paths, next_patch) = find_patches(dir.join("re_chunk_000.pak")).unwrap();
+        assert_eq!(paths.len(), 4);
+        assert_eq!(next_patch, 4);
+        let (paths, next_patch) = find_patches(dir.join("re_chunk_002.pak")).unwrap();
+        assert_eq!((paths.len(), next_patch), (1, 1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_in_memory() {
+        use std::io::{Cursor, Read, Write};
+
+        use crate::read::io::entry::PakEntryReader;
+        use crate::write::{FileOptions, PakWriter};
+
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer.start_file("natives/stm/a.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"in memory").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        for multi_pak in [
+            MultiPak::from_bytes(bytes.clone()).unwrap(),
+            MultiPak::from_reader(Cursor::new(bytes)).unwrap(),
+        ] {
+            let item = &multi_pak.entries()[0];
+            let mut reader = multi_pak.open_reader(item.pak_index).unwrap();
+            let mut data = vec![];
+            PakEntryReader::new_streaming(&mut reader, &item.entry)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            assert_eq!(data, b"in memory");
+
+            let mut entry_reader = multi_pak.open_entry(item).unwrap();
+            let data = std::thread::spawn(move || {
+                let mut data = vec![];
+                entry_reader.read_to_end(&mut data).unwrap();
+                data
+            })
+            .join()
+            .unwrap();
+            assert_eq!(data, b"in memory");
+            // the raw bytes of a stored entry are its content
+            let mut raw = vec![];
+            multi_pak.open_entry_raw(item).unwrap().read_to_end(&mut raw).unwrap();
+            assert_eq!(raw, b"in memory");
+        }
+    }
+
+    #[test]
+    fn test_provenance_and_flatten() {
+        use std::io::{Cursor, Read, Write};
+
+        use crate::read::io::entry::PakEntryReader;
+        use crate::write::{flatten, FileOptions, PakOptions, PakWriter};
+
+        let make_pak = |files: &[(&str, &[u8])]| {
+            let mut writer = PakWriter::new(Cursor::new(vec![]), files.len() as u32).unwrap();
+            for (path, data) in files {
+                writer.start_file(path, FileOptions::default()).unwrap();
+                writer.write_all(data).unwrap();
+            }
+            PakSource::from(writer.finish().unwrap().into_inner())
+        };
+        let multi_pak = MultiPak::open_sources([
+            make_pak(&[("natives/a.txt", b"base a"), ("natives/b.txt", b"base b")]),
+            make_pak(&[("natives/b.txt", b"patch 1 b")]),
+            make_pak(&[("natives/b.txt", b"patch 2 b"), ("natives/c.txt", b"patch 2 c")]),
+        ])
+        .unwrap();
+
+        let provenance = multi_pak.provenance();
+        let expected = [(0, vec![]), (2, vec![0, 1]), (2, vec![])];
+        assert_eq!(provenance.len(), expected.len());
+        for (provenance, (pak_index, overridden)) in provenance.iter().zip(expected) {
+            assert_eq!(provenance.pak_index, pak_index);
+            assert_eq!(provenance.overridden, overridden);
+        }
+
+        let bytes = flatten(&multi_pak, Cursor::new(vec![]), PakOptions::default())
+            .unwrap()
+            .into_inner();
+        let flat = MultiPak::from_bytes(bytes).unwrap();
+        let mut reader = flat.open_reader(0).unwrap();
+        let contents: Vec<Vec<u8>> = flat
+            .entries()
+            .iter()
+            .map(|item| {
+                let mut data = vec![];
+                PakEntryReader::new_streaming(&mut reader, &item.entry)
+                    .unwrap()
+                    .read_to_end(&mut data)
+                    .unwrap();
+                data
+            })
+            .collect();
+        assert_eq!(contents, [&b"base a"[..], b"patch 2 b", b"patch 2 c"]);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::pak::PakEntry;
+
+/// Order in which entries are extracted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtractOrder {
+    /// Keep the entry table order.
+    #[default]
+    Toc,
+    /// Smallest uncompressed entries first, so most files are done early and
+    /// giant ones such as movies come last.
+    SmallestFirst,
+}
+
+impl ExtractOrder {
+    /// Sort items in this order, `entry` gives the entry of each item.
+    ///
+    /// The sort is stable, entries of the same size keep their relative order.
+    pub fn sort<T, F>(self, items: &mut [T], entry: F)
+    where
+        F: Fn(&T) -> &PakEntry,
+    {
+        match self {
+            ExtractOrder::Toc => {}
+            ExtractOrder::SmallestFirst => items.sort_by_key(|item| entry(item).uncompressed_size()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::CompressionMethod;
+
+    #[test]
+    fn test_smallest_first() {
+        let mut entries: Vec<PakEntry> = [(1, 300), (2, 10), (3, 300), (4, 0)]
+            .into_iter()
+            .map(|(hash, size)| PakEntry::new(hash, 0, size, size, CompressionMethod::None))
+            .collect();
+
+        ExtractOrder::Toc.sort(&mut entries, |entry| entry);
+        assert_eq!(entries.iter().map(|e| e.hash()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        ExtractOrder::SmallestFirst.sort(&mut entries, |entry| entry);
+        assert_eq!(entries.iter().map(|e| e.hash()).collect::<Vec<_>>(), [4, 2, 1, 3]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// A seekable reader that can be shared between threads.
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T> ReadSeek for T where T: Read + Seek + Send {}
+
+/// Where the bytes of a pak come from.
+///
+/// Every worker opens its own [`SourceReader`], so files are opened once per
+/// worker and in-memory paks are shared without copying. A generic reader is
+/// shared behind a lock, each [`SourceReader`] keeps its own position.
+#[derive(Clone)]
+pub enum PakSource {
+    File(PathBuf),
+    Bytes(Arc<[u8]>),
+    Reader(Arc<Mutex<dyn ReadSeek>>),
+}
+
+impl PakSource {
+    pub fn from_reader<R>(reader: R) -> Self
+    where
+        R: ReadSeek + 'static,
+    {
+        PakSource::Reader(Arc::new(Mutex::new(reader)))
+    }
+
+    /// Open a new reader at the start of the pak.
+    pub fn open(&self) -> io::Result<SourceReader> {
+        let inner = match self {
+            PakSource::File(path) => SourceInner::File(File::open(path)?),
+            PakSource::Bytes(bytes) => SourceInner::Bytes(Cursor::new(bytes.clone())),
+            PakSource::Reader(reader) => SourceInner::Shared {
+                reader: reader.clone(),
+                pos: 0,
+            },
+        };
+
+        Ok(SourceReader {
+            inner: BufReader::new(inner),
+        })
+    }
+}
+
+impl From<PathBuf> for PakSource {
+    fn from(value: PathBuf) -> Self {
+        PakSource::File(value)
+    }
+}
+
+impl From<Vec<u8>> for PakSource {
+    fn from(value: Vec<u8>) -> Self {
+        PakSource::Bytes(value.into())
+    }
+}
+
+impl From<Arc<[u8]>> for PakSource {
+    fn from(value: Arc<[u8]>) -> Self {
+        PakSource::Bytes(value)
+    }
+}
+
+impl fmt::Display for PakSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PakSource::File(path) => write!(f, "{}", path.display()),
+            PakSource::Bytes(bytes) => write!(f, "<memory, {} bytes>", bytes.len()),
+            PakSource::Reader(_) => write!(f, "<reader>"),
+        }
+    }
+}
+
+impl fmt::Debug for PakSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PakSource({})", self)
+    }
+}
+
+/// Buffered reader of a [`PakSource`].
+pub struct SourceReader {
+    inner: BufReader<SourceInner>,
+}
+
+enum SourceInner {
+    File(File),
+    Bytes(Cursor<Arc<[u8]>>),
+    Shared { reader: Arc<Mutex<dyn ReadSeek>>, pos: u64 },
+}
+
+impl Read for SourceInner {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SourceInner::File(file) => file.read(buf),
+            SourceInner::Bytes(cursor) => cursor.read(buf),
+            SourceInner::Shared { reader, pos } => {
+                let mut reader = reader
+                    .lock()
+                    .map_err(|_| io::Error::other("pak reader lock poisoned"))?;
+                reader.seek(SeekFrom::Start(*pos))?;
+                let n = reader.read(buf)?;
+                *pos += n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
+
+impl Seek for SourceInner {
+    fn seek(&mut self, seek_from: SeekFrom) -> io::Result<u64> {
+        match self {
+            SourceInner::File(file) => file.seek(seek_from),
+            SourceInner::Bytes(cursor) => cursor.seek(seek_from),
+            SourceInner::Shared { reader, pos } => {
+                let new_pos = match seek_from {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+                    SeekFrom::End(offset) => {
+                        let mut reader = reader
+                            .lock()
+                            .map_err(|_| io::Error::other("pak reader lock poisoned"))?;
+                        reader.seek(SeekFrom::End(0))?.checked_add_signed(offset)
+                    }
+                };
+                *pos = new_pos.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+                Ok(*pos)
+            }
+        }
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl io::BufRead for SourceReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, seek_from: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(seek_from)
+    }
+}
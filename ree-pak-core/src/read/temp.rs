@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::pak::PakEntry;
+
+use super::io::entry::PakEntryReader;
+
+/// Attempts at a free directory name before giving up.
+const CREATE_DIR_ATTEMPTS: usize = 16;
+
+/// Entries extracted to temporary files, e.g. to open them in an external program.
+///
+/// Files are placed in a private directory under the system temp directory,
+/// which is removed with all files when this is dropped.
+#[derive(Debug)]
+pub struct TempFiles {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl TempFiles {
+    /// Create the directory with a random name, so it cannot be one prepared by
+    /// another user of the shared temp directory.
+    pub fn new() -> Result<Self> {
+        let env = crate::env::env();
+        let mut attempts = 0;
+        loop {
+            let dir = std::env::temp_dir().join(format!("ree-pak-{:016x}", env.random_u64()));
+            match create_private_dir(&dir) {
+                Ok(()) => return Ok(Self { dir, files: vec![] }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < CREATE_DIR_ATTEMPTS => {
+                    attempts += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Extract an entry to a new temporary file and return its path.
+    ///
+    /// `file_name` is the resolved path of the entry if known, only its last
+    /// component is used. Unknown names fall back to the hash. A missing
+    /// extension is guessed from the content.
+    pub fn extract<R>(&mut self, reader: &mut R, entry: &PakEntry, file_name: Option<&str>) -> Result<PathBuf>
+    where
+        R: BufRead + Seek,
+    {
+        let file_name = file_name
+            .and_then(|name| name.rsplit(['/', '\\']).next())
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("{:016X}", entry.hash()));
+        // one subdirectory per file keeps the original name while staying unique
+        let file_dir = self.dir.join(self.files.len().to_string());
+        std::fs::create_dir_all(&file_dir)?;
+        let mut path = file_dir.join(&file_name);
+
+        let mut entry_reader = PakEntryReader::new_streaming(reader, entry)?;
+        let mut writer = BufWriter::new(File::create(&path)?);
+        io::copy(&mut entry_reader, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        if path.extension().is_none() {
+            if let Some(ext) = entry_reader.determine_extension() {
+                let new_path = path.with_extension(ext);
+                std::fs::rename(&path, &new_path)?;
+                path = new_path;
+            }
+        }
+        self.files.push(path.clone());
+
+        Ok(path)
+    }
+
+    /// Directory holding all temporary files.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Paths of the extracted files, in order of extraction.
+    #[inline]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        // files still opened by other programs may fail to be removed on some platforms
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Create a new directory only the current user can access, failing if it exists.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    builder.create(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::read::read_archive;
+    use crate::write::{FileOptions, PakWriter};
+
+    #[test]
+    fn test_temp_files() {
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 2).unwrap();
+        writer
+            .start_file("natives/stm/a/readme.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"text").unwrap();
+        writer.start_file("unknown", FileOptions::default()).unwrap();
+        writer.write_all(b"TEX\0texture data").unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+        let archive = read_archive(&mut reader).unwrap();
+
+        let mut temp_files = TempFiles::new().unwrap();
+        let path = temp_files
+            .extract(&mut reader, &archive.entries()[0], Some("natives/stm/a/readme.txt"))
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "readme.txt");
+        assert_eq!(std::fs::read(&path).unwrap(), b"text");
+        let path = temp_files.extract(&mut reader, &archive.entries()[1], None).unwrap();
+        assert_eq!(path.extension().unwrap(), "tex");
+
+        let dir = temp_files.dir().to_path_buf();
+        assert_ne!(TempFiles::new().unwrap().dir(), dir);
+        drop(temp_files);
+        assert!(!dir.exists());
+    }
+}
@@ -1,7 +1,8 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use crate::error::Result;
 
+/// Entry of version 2 paks, 24 bytes, stored uncompressed.
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct EntryV1 {
@@ -20,18 +21,44 @@ impl EntryV1 {
     {
         let mut buf = [0u8; Self::SIZE];
         reader.read_exact(&mut buf)?;
-        unsafe { Ok(std::mem::transmute::<[u8; Self::SIZE], Self>(buf)) }
+        Ok(Self::from_array(buf))
+    }
+
+    /// Parse from the start of a slice, fails if it is shorter than [`Self::SIZE`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_reader(&mut &bytes[..])
+    }
+
+    pub fn into_bytes(self) -> [u8; Self::SIZE] {
+        unsafe { std::mem::transmute::<Self, [u8; Self::SIZE]>(self) }
+    }
+
+    pub fn to_writer<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.clone().into_bytes())?;
+        Ok(())
+    }
+
+    fn from_array(buf: [u8; Self::SIZE]) -> Self {
+        unsafe { std::mem::transmute::<[u8; Self::SIZE], Self>(buf) }
     }
 }
 
+/// Entry of version 4 paks, 48 bytes.
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct EntryV2 {
+    /// Murmur3 hash of the lowercase path.
     pub hash_name_lower: u32,
+    /// Murmur3 hash of the uppercase path.
     pub hash_name_upper: u32,
+    /// Absolute offset of the stored data.
     pub offset: u64,
     pub compressed_size: u64,
     pub uncompressed_size: u64,
+    /// Compression in the low bits, encryption type in bits 16 and up.
     pub compression_method: i64,
     pub checksum: u64,
 }
@@ -45,7 +72,28 @@ impl EntryV2 {
     {
         let mut buf = [0u8; Self::SIZE];
         reader.read_exact(&mut buf)?;
-        unsafe { Ok(std::mem::transmute::<[u8; Self::SIZE], Self>(buf)) }
+        Ok(Self::from_array(buf))
+    }
+
+    /// Parse from the start of a slice, fails if it is shorter than [`Self::SIZE`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_reader(&mut &bytes[..])
+    }
+
+    pub fn into_bytes(self) -> [u8; Self::SIZE] {
+        unsafe { std::mem::transmute::<Self, [u8; Self::SIZE]>(self) }
+    }
+
+    pub fn to_writer<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.clone().into_bytes())?;
+        Ok(())
+    }
+
+    fn from_array(buf: [u8; Self::SIZE]) -> Self {
+        unsafe { std::mem::transmute::<[u8; Self::SIZE], Self>(buf) }
     }
 }
 
@@ -58,4 +106,17 @@ mod tests {
         assert_eq!(std::mem::size_of::<EntryV1>(), 24);
         assert_eq!(std::mem::size_of::<EntryV2>(), 48);
     }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut bytes = [0u8; EntryV2::SIZE];
+        bytes[8] = 0x10;
+        bytes[32] = 2;
+        let entry = EntryV2::from_bytes(&bytes).unwrap();
+        assert_eq!(entry.offset, 0x10);
+        assert_eq!(entry.compression_method, 2);
+        assert_eq!(entry.into_bytes(), bytes);
+
+        assert!(EntryV1::from_bytes(&bytes[..EntryV1::SIZE - 1]).is_err());
+    }
 }
@@ -1,20 +1,25 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use crate::error::Result;
 
+/// Pak file header, 16 bytes at the start of the file.
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct Header {
+    /// Always `KPKA`.
     pub magic: [u8; 4],
+    /// With the minor version, selects the [`TocLayout`](super::TocLayout) of the entry table.
     pub major_version: u8,
     pub minor_version: u8,
+    /// Feature flags, 8 means the entry table is encrypted and followed by a 128 byte key.
     pub feature: u16,
+    /// Number of entries in the entry table following the header.
     pub total_files: u32,
     pub hash: u32,
 }
 
 impl Header {
-    const SIZE: usize = std::mem::size_of::<Self>();
+    pub const SIZE: usize = std::mem::size_of::<Self>();
 
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
@@ -22,6 +27,27 @@ impl Header {
     {
         let mut buf = [0u8; Self::SIZE];
         reader.read_exact(&mut buf)?;
-        unsafe { Ok(std::mem::transmute::<[u8; Self::SIZE], Self>(buf)) }
+        Ok(Self::from_array(buf))
+    }
+
+    /// Parse from the start of a slice, fails if it is shorter than [`Self::SIZE`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_reader(&mut &bytes[..])
+    }
+
+    pub fn into_bytes(self) -> [u8; Self::SIZE] {
+        unsafe { std::mem::transmute::<Self, [u8; Self::SIZE]>(self) }
+    }
+
+    pub fn to_writer<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.clone().into_bytes())?;
+        Ok(())
+    }
+
+    fn from_array(buf: [u8; Self::SIZE]) -> Self {
+        unsafe { std::mem::transmute::<[u8; Self::SIZE], Self>(buf) }
     }
 }
@@ -1,7 +1,16 @@
+//! Raw on-disk layouts of the pak header and entries.
+//!
+//! The structs mirror the file layout byte for byte on little-endian targets, so
+//! they can be used by external tools that inspect pak bytes directly. Use
+//! [`read_archive`](crate::read::read_archive) for validated, decrypted entries.
+
 mod entry;
 mod header;
+mod toc;
 
 pub use entry::EntryV1;
 pub use entry::EntryV2;
 
 pub use header::Header;
+
+pub use toc::TocLayout;
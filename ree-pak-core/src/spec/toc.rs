@@ -0,0 +1,52 @@
+use super::{EntryV1, EntryV2};
+
+/// Entry table layout of a pak version.
+///
+/// Only the `(major, minor)` pairs below have been seen in released games. Other
+/// pairs are rejected instead of guessed, as a wrong guess shifts every offset
+/// after the header.
+///
+/// | Version  | Entries     |
+/// |----------|-------------|
+/// | 2.0      | [`EntryV1`] |
+/// | 4.0, 4.1 | [`EntryV2`] |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TocLayout {
+    V1,
+    V2,
+}
+
+impl TocLayout {
+    /// Layout of a version, `None` if it is not supported.
+    pub fn for_version(major_version: u8, minor_version: u8) -> Option<Self> {
+        match (major_version, minor_version) {
+            (2, 0) => Some(Self::V1),
+            (4, 0) | (4, 1) => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    /// Size of one entry in the table.
+    pub fn entry_size(self) -> usize {
+        match self {
+            Self::V1 => EntryV1::SIZE,
+            Self::V2 => EntryV2::SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_version() {
+        assert_eq!(TocLayout::for_version(2, 0), Some(TocLayout::V1));
+        assert_eq!(TocLayout::for_version(4, 1), Some(TocLayout::V2));
+        // 2.1 used to be accepted and read as v2 entries from a table of v1 size
+        assert_eq!(TocLayout::for_version(2, 1), None);
+        assert_eq!(TocLayout::for_version(4, 2), None);
+        assert_eq!(TocLayout::V1.entry_size(), 24);
+        assert_eq!(TocLayout::V2.entry_size(), 48);
+    }
+}
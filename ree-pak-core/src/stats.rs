@@ -0,0 +1,113 @@
+//! Compression statistics of entries grouped by path prefix.
+
+use std::collections::BTreeMap;
+
+use crate::pak::{CompressionMethod, PakEntry};
+
+/// Number of path components grouped by default, e.g. `natives/stm/sound`.
+pub const DEFAULT_PREFIX_DEPTH: usize = 3;
+
+/// Prefix of entries whose path is not known.
+pub const UNKNOWN_PREFIX: &str = "_Unknown";
+
+/// Totals of the entries of one codec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodecStats {
+    pub count: usize,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+impl CodecStats {
+    /// Stored size relative to the uncompressed size, 1.0 for empty entries.
+    pub fn ratio(&self) -> f64 {
+        if self.uncompressed_size == 0 {
+            return 1.0;
+        }
+        self.compressed_size as f64 / self.uncompressed_size as f64
+    }
+
+    fn add(&mut self, entry: &PakEntry) {
+        self.count += 1;
+        self.compressed_size += entry.real_compressed_size();
+        self.uncompressed_size += entry.uncompressed_size();
+    }
+}
+
+/// Codec mix of entries by path prefix.
+#[derive(Debug, Clone)]
+pub struct CodecSummary {
+    depth: usize,
+    prefixes: BTreeMap<String, BTreeMap<CompressionMethod, CodecStats>>,
+}
+
+impl Default for CodecSummary {
+    fn default() -> Self {
+        Self::new(DEFAULT_PREFIX_DEPTH)
+    }
+}
+
+impl CodecSummary {
+    /// Group by the first `depth` path components.
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth: depth.max(1),
+            prefixes: BTreeMap::new(),
+        }
+    }
+
+    /// Add an entry by its resolved path, `None` if the path is not known.
+    pub fn add(&mut self, path: Option<&str>, entry: &PakEntry) {
+        let prefix = match path {
+            Some(path) => prefix_of(path, self.depth),
+            None => UNKNOWN_PREFIX,
+        };
+        self.prefixes
+            .entry(prefix.to_string())
+            .or_default()
+            .entry(entry.compression_method())
+            .or_default()
+            .add(entry);
+    }
+
+    /// Stats per codec of each prefix, sorted by prefix.
+    #[inline]
+    pub fn prefixes(&self) -> &BTreeMap<String, BTreeMap<CompressionMethod, CodecStats>> {
+        &self.prefixes
+    }
+}
+
+/// Directory prefix of a path with at most `depth` components, the file name is never included.
+fn prefix_of(path: &str, depth: usize) -> &str {
+    let dir = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+    match dir.match_indices('/').nth(depth - 1) {
+        Some((end, _)) => &dir[..end],
+        None => dir,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_summary() {
+        assert_eq!(prefix_of("natives/stm/sound/bgm/a.sbnk.1", 3), "natives/stm/sound");
+        assert_eq!(prefix_of("natives/stm/a.user.2", 3), "natives/stm");
+        assert_eq!(prefix_of("a.user.2", 3), "");
+
+        let mut summary = CodecSummary::default();
+        let zstd = PakEntry::new(1, 0, 25, 100, CompressionMethod::Zstd);
+        let stored = PakEntry::new(2, 0, 50, 50, CompressionMethod::None);
+        summary.add(Some("natives/stm/sound/bgm/a.sbnk.1"), &zstd);
+        summary.add(Some("natives/stm/sound/se/b.sbnk.1"), &zstd);
+        summary.add(Some("natives/stm/sound/c.spck.1"), &stored);
+        summary.add(None, &stored);
+
+        let sound = &summary.prefixes()["natives/stm/sound"];
+        assert_eq!(sound[&CompressionMethod::Zstd].count, 2);
+        assert_eq!(sound[&CompressionMethod::Zstd].ratio(), 0.25);
+        assert_eq!(sound[&CompressionMethod::None].uncompressed_size, 50);
+        assert_eq!(summary.prefixes()[UNKNOWN_PREFIX][&CompressionMethod::None].count, 1);
+    }
+}
@@ -0,0 +1,260 @@
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::error::{PakError, Result};
+use crate::filename::FileName;
+use crate::pak::{self, PakEntry, PakHeader};
+use crate::read::read_archive;
+use crate::spec;
+
+use super::writer::{write_entry_data, FileOptions, KEY_SIZE};
+
+/// Size of the buffer moving entry data within the file.
+const MOVE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Edit the entries of an existing version 4 pak in place.
+///
+/// New data is appended at the end of the file and the entry table is
+/// rewritten by [`PakEditor::finish`], keeping the version, feature flags and
+/// header hash. Data of replaced and removed entries stays in the file without
+/// being referenced, rebuild the pak to reclaim the space. When the table grows
+/// into the data after it, the entries in the way are moved to the end.
+pub struct PakEditor<F> {
+    file: F,
+    header: PakHeader,
+    entries: Vec<PakEntry>,
+    end: u64,
+}
+
+impl<F> PakEditor<F>
+where
+    F: Read + Write + Seek,
+{
+    pub fn open(mut file: F) -> Result<Self> {
+        file.seek(SeekFrom::Start(0))?;
+        let archive = read_archive(&mut BufReader::new(&mut file))?;
+        let header = archive.header().clone();
+        if header.major_version() != 4 {
+            return Err(PakError::UnsupportedVersion {
+                major: header.major_version(),
+                minor: header.minor_version(),
+            });
+        }
+        let end = file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            file,
+            header,
+            entries: archive.entries().to_vec(),
+            end,
+        })
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[PakEntry] {
+        &self.entries
+    }
+
+    /// Add a file, replacing the entry of the same path if there is one.
+    pub fn add_file<R>(&mut self, path: &str, data: &mut R, options: FileOptions) -> Result<()>
+    where
+        R: Read,
+    {
+        self.add_file_hash(FileName::new(path).hash_mixed(), data, options)
+    }
+
+    /// Add a file with a known hash, replacing the entry of the same hash if there is one.
+    ///
+    /// A replaced entry keeps its position in the table.
+    pub fn add_file_hash<R>(&mut self, hash: u64, data: &mut R, options: FileOptions) -> Result<()>
+    where
+        R: Read,
+    {
+        if !options.compression().is_supported() {
+            return Err(PakError::CodecDisabled(options.compression()));
+        }
+        self.file.seek(SeekFrom::Start(self.end))?;
+        let entry = write_entry_data(&mut self.file, hash, data, options)?;
+        self.end = self.file.stream_position()?;
+        match self.entries.iter_mut().find(|existing| existing.hash() == hash) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the entries of a path, returning whether there were any.
+    pub fn remove_file(&mut self, path: &str) -> bool {
+        self.remove_hash(FileName::new(path).hash_mixed())
+    }
+
+    /// Remove the entries of a hash, returning whether there were any.
+    pub fn remove_hash(&mut self, hash: u64) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.hash() != hash);
+        self.entries.len() != len
+    }
+
+    /// Write the entry table, moving data out of its way first.
+    pub fn finish(mut self) -> Result<F> {
+        let header = PakHeader::new(
+            self.header.major_version(),
+            self.header.minor_version(),
+            self.header.feature(),
+            self.entries.len() as u32,
+            self.header.hash(),
+        )?;
+        let encrypt_toc = header.feature() == 8;
+        let mut toc_end = spec::Header::SIZE as u64 + header.entry_size() as u64 * self.entries.len() as u64;
+        if encrypt_toc {
+            toc_end += KEY_SIZE;
+        }
+
+        // a small file can end inside the new table, moved data goes after both
+        self.end = self.end.max(toc_end);
+        for i in 0..self.entries.len() {
+            let entry = &self.entries[i];
+            if entry.offset() >= toc_end {
+                continue;
+            }
+            let size = entry.real_compressed_size();
+            move_data(&mut self.file, entry.offset(), self.end, size)?;
+            self.entries[i] = entry.with_data(self.end, entry.compressed_size(), entry.compression_method());
+            self.end += size;
+        }
+
+        self.file.seek(SeekFrom::Start(0))?;
+        if encrypt_toc {
+            super::write_encrypted_toc(&mut self.file, &header, &self.entries, &pak::generate_key())?;
+        } else {
+            super::write_toc(&mut self.file, &header, &self.entries)?;
+        }
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.flush()?;
+
+        Ok(self.file)
+    }
+}
+
+/// Copy `size` bytes from `from` to `to`, the ranges must not overlap.
+fn move_data<F>(file: &mut F, from: u64, to: u64, size: u64) -> Result<()>
+where
+    F: Read + Write + Seek,
+{
+    let mut buf = vec![0; MOVE_BUFFER_SIZE.min(size as usize)];
+    let mut moved = 0;
+    while moved < size {
+        let len = buf.len().min((size - moved) as usize);
+        file.seek(SeekFrom::Start(from + moved))?;
+        file.read_exact(&mut buf[..len])?;
+        file.seek(SeekFrom::Start(to + moved))?;
+        file.write_all(&buf[..len])?;
+        moved += len as u64;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pak::CompressionMethod;
+    use crate::read::io::entry::PakEntryReader;
+    use crate::write::{PakOptions, PakWriter};
+
+    fn read_files(pak: &mut Cursor<Vec<u8>>) -> Vec<(u64, Vec<u8>)> {
+        pak.rewind().unwrap();
+        let archive = read_archive(pak).unwrap();
+        archive
+            .entries()
+            .iter()
+            .map(|entry| {
+                let mut data = vec![];
+                PakEntryReader::new_streaming(&mut *pak, entry)
+                    .unwrap()
+                    .read_to_end(&mut data)
+                    .unwrap();
+                (entry.hash(), data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_in_place() {
+        for encrypt_toc in [false, true] {
+            let options = PakOptions::default()
+                .with_encrypted_toc(encrypt_toc)
+                .with_header_hash(0xABCD);
+            let mut writer = PakWriter::with_options(Cursor::new(vec![]), 3, options).unwrap();
+            for (path, data) in [("a", b"first"), ("b", b"secnd"), ("c", b"third")] {
+                writer.start_file(path, FileOptions::default()).unwrap();
+                writer.write_all(data).unwrap();
+            }
+            let pak = writer.finish().unwrap();
+
+            let mut editor = PakEditor::open(pak).unwrap();
+            let zstd = FileOptions::default().with_compression(CompressionMethod::Zstd);
+            editor.add_file("b", &mut &b"replaced"[..], zstd).unwrap();
+            assert!(editor.remove_file("c"));
+            assert!(!editor.remove_file("c"));
+            // two more entries than removed, the table grows into the data of "a"
+            for path in ["d", "e"] {
+                editor
+                    .add_file(path, &mut path.as_bytes(), FileOptions::default())
+                    .unwrap();
+            }
+            let mut pak = editor.finish().unwrap();
+
+            let hash = |path: &str| FileName::new(path).hash_mixed();
+            let expected: Vec<(u64, Vec<u8>)> = [("a", "first"), ("b", "replaced"), ("d", "d"), ("e", "e")]
+                .iter()
+                .map(|(path, data)| (hash(path), data.as_bytes().to_vec()))
+                .collect();
+            assert_eq!(read_files(&mut pak), expected);
+            pak.rewind().unwrap();
+            let archive = read_archive(&mut pak).unwrap();
+            assert_eq!(archive.header().feature(), if encrypt_toc { 8 } else { 0 });
+            assert_eq!(archive.header().hash(), 0xABCD);
+            assert_eq!(archive.entries()[1].compression_method(), CompressionMethod::Zstd);
+        }
+    }
+
+    #[test]
+    fn test_keep_encrypted_entry() {
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 2).unwrap();
+        for (path, data) in [("a", b"first"), ("b", b"secnd")] {
+            writer.start_file(path, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut pak = writer.finish().unwrap().into_inner();
+        // mark "a" as zstd with resource encryption 1, and shorter than decoded
+        let entry_a = spec::Header::SIZE;
+        let field = 2_i64 | 1 << 16;
+        pak[entry_a + 32..entry_a + 40].copy_from_slice(&field.to_le_bytes());
+        pak[entry_a + 16..entry_a + 24].copy_from_slice(&4_u64.to_le_bytes());
+
+        let mut editor = PakEditor::open(Cursor::new(pak)).unwrap();
+        // the grown table moves the data of "a"
+        editor
+            .add_file("c", &mut &b"third"[..], FileOptions::default())
+            .unwrap();
+        let mut pak = editor.finish().unwrap();
+
+        pak.rewind().unwrap();
+        let archive = read_archive(&mut pak).unwrap();
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.hash(), FileName::new("a").hash_mixed());
+        assert_eq!(entry.encryption_type(), 1);
+        assert_eq!(entry.compressed_size(), 4);
+        let table = &pak.get_ref()[entry_a..entry_a + spec::EntryV2::SIZE];
+        let written = spec::EntryV2::from_bytes(table).unwrap();
+        assert_eq!(written.compression_method, field);
+        assert_eq!(written.compressed_size, 4);
+        let mut data = vec![0; 4];
+        pak.seek(SeekFrom::Start(entry.offset())).unwrap();
+        pak.read_exact(&mut data).unwrap();
+        assert_eq!(data, b"firs");
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::Read;
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+use std::io::{self, Write};
+
+use crate::error::{PakError, Result};
+use crate::pak::CompressionMethod;
+use crate::spec;
+
+use super::writer::{FileOptions, PakOptions, KEY_SIZE};
+
+/// Largest file a FAT32 volume can hold.
+pub const FAT32_MAX_FILE_SIZE: u64 = u32::MAX as u64;
+/// Largest file an exFAT volume can hold.
+pub const EXFAT_MAX_FILE_SIZE: u64 = u64::MAX;
+
+/// Bytes from the start of each file compressed to estimate its ratio.
+pub(super) const SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// Prediction of the size of a pak written by [`PakWriter`](super::PakWriter).
+///
+/// Files up to the sample size are compressed as a whole and are exact, larger
+/// files are assumed to compress like their first bytes. The entry table size
+/// is exact.
+#[derive(Debug, Clone, Default)]
+pub struct SizeEstimate {
+    options: PakOptions,
+    files: u32,
+    uncompressed_size: u64,
+    data_size: u64,
+}
+
+impl SizeEstimate {
+    pub fn new(options: PakOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Add a file of `size` bytes read from `reader`, only a sample of it is read.
+    pub fn add_file<R>(&mut self, reader: R, size: u64, options: FileOptions) -> Result<()>
+    where
+        R: Read,
+    {
+        let mut sample = Vec::with_capacity(size.min(SAMPLE_SIZE) as usize);
+        reader.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+        let stored_sample = compressed_len(&sample, options.compression())?;
+
+        let stored_size = if sample.is_empty() || sample.len() as u64 >= size {
+            stored_sample
+        } else {
+            (size as f64 * stored_sample as f64 / sample.len() as f64).ceil() as u64
+        };
+        self.files += 1;
+        self.uncompressed_size += size;
+        self.data_size += stored_size;
+
+        Ok(())
+    }
+
+    /// Number of files added.
+    #[inline]
+    pub fn files(&self) -> u32 {
+        self.files
+    }
+
+    /// Total size of the added files before compression.
+    #[inline]
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Predicted size of the pak, including the header and entry table.
+    pub fn estimated_size(&self) -> u64 {
+        let mut toc_size = spec::Header::SIZE as u64 + spec::EntryV2::SIZE as u64 * self.files as u64;
+        if self.options.encrypt_toc() {
+            toc_size += KEY_SIZE;
+        }
+
+        toc_size + self.data_size
+    }
+
+    /// Whether the predicted pak is larger than `limit`, e.g. [`FAT32_MAX_FILE_SIZE`].
+    #[inline]
+    pub fn exceeds(&self, limit: u64) -> bool {
+        self.estimated_size() > limit
+    }
+}
+
+/// Size of `data` compressed with the same settings as the writer.
+pub(super) fn compressed_len(data: &[u8], compression: CompressionMethod) -> Result<u64> {
+    let len = match compression {
+        CompressionMethod::None => data.len() as u64,
+        #[cfg(feature = "deflate")]
+        CompressionMethod::Deflate => {
+            let mut encoder = flate2::write::DeflateEncoder::new(CountingWriter(0), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?.0
+        }
+        #[cfg(feature = "zstd")]
+        CompressionMethod::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(CountingWriter(0), 0)?;
+            encoder.write_all(data)?;
+            encoder.finish()?.0
+        }
+        #[allow(unreachable_patterns)]
+        compression => return Err(PakError::CodecDisabled(compression)),
+    };
+
+    Ok(len)
+}
+
+/// Discards written bytes, counting them.
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+struct CountingWriter(u64);
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::write::PakWriter;
+
+    #[test]
+    fn test_estimate_small_files_exact() {
+        let files: [(&[u8], CompressionMethod); 3] = [
+            (b"stored data", CompressionMethod::None),
+            (b"deflate data deflate data", CompressionMethod::Deflate),
+            (b"zstd data zstd data zstd data", CompressionMethod::Zstd),
+        ];
+        let options = PakOptions::default().with_encrypted_toc(true);
+        let mut estimate = SizeEstimate::new(options);
+        let mut writer = PakWriter::with_options(Cursor::new(vec![]), files.len() as u32, options).unwrap();
+        for (i, (data, compression)) in files.iter().enumerate() {
+            let file_options = FileOptions::default().with_compression(*compression);
+            estimate.add_file(*data, data.len() as u64, file_options).unwrap();
+            writer.start_file(&i.to_string(), file_options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let written = writer.finish().unwrap().into_inner();
+
+        assert_eq!(estimate.files(), 3);
+        assert_eq!(estimate.estimated_size(), written.len() as u64);
+        assert!(!estimate.exceeds(FAT32_MAX_FILE_SIZE));
+    }
+
+    #[test]
+    fn test_estimate_large_file_extrapolates() {
+        let data = vec![7u8; SAMPLE_SIZE as usize * 4];
+        let mut estimate = SizeEstimate::new(PakOptions::default());
+        estimate
+            .add_file(data.as_slice(), data.len() as u64, FileOptions::default())
+            .unwrap();
+        let toc_size = (spec::Header::SIZE + spec::EntryV2::SIZE) as u64;
+        assert_eq!(estimate.estimated_size(), toc_size + data.len() as u64);
+        assert_eq!(estimate.uncompressed_size(), data.len() as u64);
+    }
+}
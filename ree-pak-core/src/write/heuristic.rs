@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::Deserialize;
+
+use crate::error::{PakError, Result};
+use crate::pak::CompressionMethod;
+
+use super::estimate::{compressed_len, SAMPLE_SIZE};
+
+/// Default largest ratio of compressed to original sample size still worth compressing.
+pub const DEFAULT_MAX_RATIO: f64 = 0.9;
+
+/// Chooses per file whether compression is worth it.
+///
+/// Files with a configured extension get its compression. Others are sampled:
+/// the start of the file is compressed, and the file is stored uncompressed if
+/// the sample shrinks to no less than `max_ratio` of its size, as with textures
+/// or sound banks that are compressed already.
+///
+/// ```json
+/// { "max_ratio": 0.85, "extensions": { "tex": "none", "user": "zstd" } }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionHeuristic {
+    max_ratio: f64,
+    extensions: HashMap<String, CompressionMethod>,
+}
+
+/// Why [`CompressionHeuristic::choose`] picked a compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionReason {
+    /// Configured for the extension of the file.
+    Extension,
+    /// No compression was requested, the file is stored.
+    NotRequested,
+    /// The sample compressed well.
+    Compressible,
+    /// The sample did not compress well, the file is stored.
+    Incompressible,
+    /// Empty files are stored.
+    Empty,
+}
+
+impl Default for CompressionHeuristic {
+    fn default() -> Self {
+        Self {
+            max_ratio: DEFAULT_MAX_RATIO,
+            extensions: HashMap::new(),
+        }
+    }
+}
+
+impl CompressionHeuristic {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        let heuristic: Self =
+            serde_json::from_slice(data).map_err(|e| PakError::InvalidCompressionConfig(e.to_string()))?;
+
+        Ok(heuristic.normalized())
+    }
+
+    pub fn with_max_ratio(mut self, max_ratio: f64) -> Self {
+        self.max_ratio = max_ratio;
+        self
+    }
+
+    /// Use `compression` for files of an extension, e.g. `tex` for `a.tex.143221013`.
+    pub fn with_extension(mut self, extension: &str, compression: CompressionMethod) -> Self {
+        self.extensions.insert(extension.to_ascii_lowercase(), compression);
+        self
+    }
+
+    #[inline]
+    pub fn max_ratio(&self) -> f64 {
+        self.max_ratio
+    }
+
+    /// Choose the compression of a file, `compression` is the one requested for the pak.
+    ///
+    /// A sample of the file is read unless its extension is configured or no
+    /// compression is requested.
+    pub fn choose<R>(
+        &self,
+        path: &str,
+        reader: R,
+        compression: CompressionMethod,
+    ) -> Result<(CompressionMethod, CompressionReason)>
+    where
+        R: Read,
+    {
+        if let Some(&configured) = extension(path).and_then(|ext| self.extensions.get(&ext.to_ascii_lowercase())) {
+            return Ok((configured, CompressionReason::Extension));
+        }
+
+        if compression == CompressionMethod::None {
+            return Ok((compression, CompressionReason::NotRequested));
+        }
+
+        let mut sample = vec![];
+        reader.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+        if sample.is_empty() {
+            return Ok((CompressionMethod::None, CompressionReason::Empty));
+        }
+        let ratio = compressed_len(&sample, compression)? as f64 / sample.len() as f64;
+        if ratio < self.max_ratio {
+            Ok((compression, CompressionReason::Compressible))
+        } else {
+            Ok((CompressionMethod::None, CompressionReason::Incompressible))
+        }
+    }
+
+    fn normalized(mut self) -> Self {
+        self.extensions = self
+            .extensions
+            .into_iter()
+            .map(|(ext, compression)| (ext.to_ascii_lowercase(), compression))
+            .collect();
+        self
+    }
+}
+
+/// First extension of the file name, RE Engine paths end in a version, e.g. `tex` for `a.tex.143221013`.
+fn extension(path: &str) -> Option<&str> {
+    let file_name = path.rsplit(['/', '\\']).next()?;
+    file_name.split('.').nth(1).filter(|ext| !ext.is_empty())
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_compression() {
+        let heuristic = CompressionHeuristic::from_json(br#"{"extensions": {"TEX": "none", "bin": "zstd"}}"#).unwrap();
+        assert_eq!(heuristic.max_ratio(), DEFAULT_MAX_RATIO);
+        let zstd = CompressionMethod::Zstd;
+
+        let text = b"natives/stm/text text text ".repeat(100);
+        assert_eq!(
+            heuristic.choose("natives/stm/a.txt", text.as_slice(), zstd).unwrap(),
+            (zstd, CompressionReason::Compressible)
+        );
+        assert_eq!(
+            heuristic
+                .choose("natives/stm/a.tex.143221013", text.as_slice(), zstd)
+                .unwrap(),
+            (CompressionMethod::None, CompressionReason::Extension)
+        );
+
+        // pseudo-random bytes do not compress
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        assert_eq!(
+            heuristic
+                .choose("natives/stm/a.spck.1", noise.as_slice(), zstd)
+                .unwrap(),
+            (CompressionMethod::None, CompressionReason::Incompressible)
+        );
+        assert_eq!(
+            heuristic.choose("natives/stm/a.bin", noise.as_slice(), zstd).unwrap(),
+            (zstd, CompressionReason::Extension)
+        );
+        assert_eq!(
+            heuristic.choose("natives/stm/empty.txt", &b""[..], zstd).unwrap(),
+            (CompressionMethod::None, CompressionReason::Empty)
+        );
+    }
+}
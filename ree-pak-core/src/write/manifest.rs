@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{PakError, Result};
+use crate::pak::CompressionMethod;
+
+/// Files to pack with their pak paths, e.g. a mod assembled from several folders.
+///
+/// ```json
+/// {
+///   "files": [
+///     { "path": "natives/stm/a.tex", "source": "textures/a.tex", "compression": "zstd" },
+///     { "path": "natives/stm/b.user.2", "source": "/shared/b.user.2" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackManifest {
+    pub files: Vec<ManifestFile>,
+}
+
+/// A file of a [`PackManifest`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFile {
+    /// Path of the entry in the pak.
+    pub path: String,
+    /// File holding the content.
+    pub source: PathBuf,
+    /// Compression of the entry, `None` uses the compression of the pak.
+    #[serde(default)]
+    pub compression: Option<CompressionMethod>,
+}
+
+impl PackManifest {
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).map_err(|e| PakError::InvalidManifest(e.to_string()))
+    }
+
+    /// Load a manifest file, relative sources are resolved against its directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut manifest = Self::from_json(&std::fs::read(path)?)?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        for file in &mut manifest.files {
+            if file.source.is_relative() {
+                file.source = base_dir.join(&file.source);
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = std::env::temp_dir().join(format!("ree-pak-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest_path = dir.join("pack.json");
+        std::fs::write(
+            &manifest_path,
+            r#"{"files": [
+                {"path": "natives/stm/a.tex", "source": "textures/a.tex", "compression": "zstd"},
+                {"path": "natives/stm/b.txt", "source": "/shared/b.txt"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let manifest = PackManifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files[0].source, dir.join("textures/a.tex"));
+        assert_eq!(manifest.files[0].compression, Some(CompressionMethod::Zstd));
+        assert_eq!(manifest.files[1].source, Path::new("/shared/b.txt"));
+        assert_eq!(manifest.files[1].compression, None);
+
+        let unknown_field = br#"{"files": [{"path": "a", "source": "a", "encryption": 1}]}"#;
+        assert!(matches!(
+            PackManifest::from_json(unknown_field),
+            Err(PakError::InvalidManifest(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
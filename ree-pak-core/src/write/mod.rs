@@ -0,0 +1,251 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use crate::error::{PakError, Result};
+use crate::pak::{self, CompressionMethod, PakArchive, PakEntry, PakHeader};
+use crate::read::io::entry::PakEntryReader;
+use crate::read::multi::MultiPak;
+use crate::read::source::SourceReader;
+use crate::spec;
+
+mod editor;
+mod estimate;
+mod heuristic;
+mod manifest;
+mod validate;
+mod writer;
+
+pub use editor::PakEditor;
+pub use estimate::{SizeEstimate, EXFAT_MAX_FILE_SIZE, FAT32_MAX_FILE_SIZE};
+pub use heuristic::{CompressionHeuristic, CompressionReason, DEFAULT_MAX_RATIO};
+pub use manifest::{ManifestFile, PackManifest};
+pub use validate::validate_payload;
+pub use writer::{FileOptions, PakOptions, PakWriter, WriteEvent};
+
+/// Write the header and the unencrypted entry table.
+///
+/// Entries are written in the layout of the header version.
+pub fn write_toc<W>(writer: &mut W, header: &PakHeader, entries: &[PakEntry]) -> Result<()>
+where
+    W: Write,
+{
+    spec::Header::from(header).to_writer(writer)?;
+    for entry in entries {
+        if is_v1_layout(header) {
+            spec::EntryV1::from(entry).to_writer(writer)?;
+        } else {
+            spec::EntryV2::from(entry).to_writer(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the header and the entry table encrypted with `raw_key`, followed by the key.
+///
+/// The header feature must be 8 for readers to decrypt the table.
+pub fn write_encrypted_toc<W>(
+    writer: &mut W,
+    header: &PakHeader,
+    entries: &[PakEntry],
+    raw_key: &[u8; 128],
+) -> Result<()>
+where
+    W: Write,
+{
+    spec::Header::from(header).to_writer(writer)?;
+    let mut table = Vec::with_capacity(header.entry_size() as usize * entries.len());
+    for entry in entries {
+        if is_v1_layout(header) {
+            spec::EntryV1::from(entry).to_writer(&mut table)?;
+        } else {
+            spec::EntryV2::from(entry).to_writer(&mut table)?;
+        }
+    }
+    writer.write_all(&pak::encrypt_data(&table, raw_key))?;
+    writer.write_all(raw_key)?;
+
+    Ok(())
+}
+
+/// Convert an archive into another TOC version, copying entry data from `reader`.
+///
+/// The entry table of the output is never encrypted. Entries are stored
+/// decompressed when the target layout cannot describe compression (v2.0),
+/// which fails with [`PakError::EncryptedEntry`] for entries with resource
+/// encryption. Otherwise their compression field is kept as is.
+pub fn convert_archive<R, W>(
+    reader: &mut R,
+    archive: &PakArchive,
+    writer: &mut W,
+    major_version: u8,
+    minor_version: u8,
+) -> Result<()>
+where
+    R: BufRead + Seek,
+    W: Write,
+{
+    let src_header = archive.header();
+    let header = PakHeader::new(
+        major_version,
+        minor_version,
+        0,
+        src_header.total_files(),
+        src_header.hash(),
+    )?;
+    let decompress = is_v1_layout(&header);
+    if decompress {
+        if let Some(entry) = archive.entries().iter().find(|entry| entry.encryption_type() != 0) {
+            return Err(PakError::EncryptedEntry {
+                hash: entry.hash(),
+                encryption_type: entry.encryption_type(),
+            });
+        }
+    } else {
+        // data is copied as is, reject broken entries before anything is written
+        for entry in archive.entries() {
+            validate_payload(reader, entry)?;
+        }
+    }
+
+    // lay out entry data right after the entry table
+    let mut offset = spec::Header::SIZE as u64 + header.entry_size() as u64 * header.total_files() as u64;
+    let entries: Vec<PakEntry> = archive
+        .entries()
+        .iter()
+        .map(|entry| {
+            let entry = if decompress {
+                entry.with_data(offset, entry.uncompressed_size(), CompressionMethod::None)
+            } else {
+                entry.with_data(offset, entry.real_compressed_size(), entry.compression_method())
+            };
+            offset += entry.real_compressed_size();
+            entry
+        })
+        .collect();
+
+    write_toc(writer, &header, &entries)?;
+    for (src_entry, entry) in archive.entries().iter().zip(&entries) {
+        let expected = entry.real_compressed_size();
+        let written = if decompress {
+            let mut entry_reader = PakEntryReader::new_streaming(&mut *reader, src_entry)?;
+            io::copy(&mut entry_reader, writer)?
+        } else {
+            reader.seek(SeekFrom::Start(src_entry.offset()))?;
+            io::copy(&mut (&mut *reader).take(expected), writer)?
+        };
+        if written != expected {
+            return Err(PakError::EntrySizeMismatch {
+                hash: entry.hash(),
+                expected,
+                found: written,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the effective entries of a [`MultiPak`] into a single pak, merging its patches.
+///
+/// The stored data of each entry is copied as is with
+/// [`PakWriter::copy_raw_entry`], in the order of [`MultiPak::entries`].
+pub fn flatten<W>(multi_pak: &MultiPak, writer: W, options: PakOptions) -> Result<W>
+where
+    W: Write + Seek,
+{
+    let entries = multi_pak.entries();
+    let mut pak_writer = PakWriter::with_options(writer, entries.len() as u32, options)?;
+    let mut readers: Vec<Option<SourceReader>> = multi_pak.paks().iter().map(|_| None).collect();
+    for item in entries {
+        let reader = match &mut readers[item.pak_index] {
+            Some(reader) => reader,
+            slot => slot.insert(multi_pak.open_reader(item.pak_index)?),
+        };
+        pak_writer.copy_raw_entry(reader, &item.entry)?;
+    }
+
+    pak_writer.finish()
+}
+
+fn is_v1_layout(header: &PakHeader) -> bool {
+    header.entry_size() as usize == spec::EntryV1::SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::read::read_archive;
+
+    #[test]
+    fn test_convert_round_trip() {
+        let data = b"entry data";
+        let spec_entry = spec::EntryV2 {
+            hash_name_lower: 0x65B486A1,
+            hash_name_upper: 0x958EDD0C,
+            offset: (spec::Header::SIZE + spec::EntryV2::SIZE) as u64,
+            compressed_size: data.len() as u64,
+            uncompressed_size: data.len() as u64,
+            compression_method: 0,
+            checksum: 0,
+        };
+        let header = PakHeader::new(4, 0, 0, 1, 0).unwrap();
+        let mut src = vec![];
+        write_toc(&mut src, &header, &[PakEntry::from(spec_entry)]).unwrap();
+        src.extend_from_slice(data);
+
+        let mut reader = Cursor::new(src);
+        let archive = read_archive(&mut reader).unwrap();
+        let mut v1 = vec![];
+        convert_archive(&mut reader, &archive, &mut v1, 2, 0).unwrap();
+
+        let mut reader = Cursor::new(v1);
+        let archive = read_archive(&mut reader).unwrap();
+        assert_eq!(archive.header().major_version(), 2);
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.hash(), 0x958EDD0C65B486A1);
+        assert_eq!(entry.offset(), (spec::Header::SIZE + spec::EntryV1::SIZE) as u64);
+        let mut entry_data = vec![];
+        PakEntryReader::new_streaming(&mut reader, entry)
+            .unwrap()
+            .read_to_end(&mut entry_data)
+            .unwrap();
+        assert_eq!(entry_data, data);
+    }
+
+    #[test]
+    fn test_convert_encrypted_entry() {
+        let data = b"encrypted";
+        let field = 2_i64 | 1 << 16;
+        let spec_entry = spec::EntryV2 {
+            hash_name_lower: 0x65B486A1,
+            hash_name_upper: 0x958EDD0C,
+            offset: (spec::Header::SIZE + spec::EntryV2::SIZE) as u64,
+            compressed_size: data.len() as u64,
+            uncompressed_size: 20,
+            compression_method: field,
+            checksum: 0,
+        };
+        let header = PakHeader::new(4, 0, 0, 1, 0).unwrap();
+        let mut src = vec![];
+        write_toc(&mut src, &header, &[PakEntry::from(spec_entry)]).unwrap();
+        src.extend_from_slice(data);
+        let mut reader = Cursor::new(src);
+        let archive = read_archive(&mut reader).unwrap();
+
+        let mut v1 = vec![];
+        assert!(matches!(
+            convert_archive(&mut reader, &archive, &mut v1, 2, 0),
+            Err(PakError::EncryptedEntry { encryption_type: 1, .. })
+        ));
+        assert!(v1.is_empty());
+
+        let mut v4 = vec![];
+        convert_archive(&mut reader, &archive, &mut v4, 4, 1).unwrap();
+        let written = spec::EntryV2::from_bytes(&v4[spec::Header::SIZE..]).unwrap();
+        assert_eq!(written.compression_method, field);
+        assert_eq!(written.compressed_size, data.len() as u64);
+        assert!(v4.ends_with(data));
+    }
+}
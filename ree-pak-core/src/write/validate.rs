@@ -0,0 +1,98 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+use crate::error::{PakError, Result};
+use crate::pak::{CompressionMethod, PakEntry};
+use crate::read::io::entry::PakEntryReader;
+
+/// Number of bytes test-decoded from the start of compressed entries.
+const TEST_DECODE_SIZE: u64 = 64 * 1024;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Check that the stored data of an entry matches its declared compression and sizes.
+///
+/// The data must lie within the reader, zstd data must start with a frame magic,
+/// and the first block of compressed data must decode. Used before copying
+/// entry data as is, so broken payloads are rejected before the TOC is written.
+pub fn validate_payload<R>(reader: &mut R, entry: &PakEntry) -> Result<()>
+where
+    R: BufRead + Seek,
+{
+    let invalid = |reason: String| PakError::InvalidEntryPayload {
+        hash: entry.hash(),
+        reason,
+    };
+
+    let stream_len = reader.seek(SeekFrom::End(0))?;
+    let data_end = entry.offset().checked_add(entry.real_compressed_size());
+    if data_end.is_none_or(|end| end > stream_len) {
+        return Err(invalid(format!(
+            "data at {}+{} is out of the file of {} bytes",
+            entry.offset(),
+            entry.real_compressed_size(),
+            stream_len
+        )));
+    }
+
+    match entry.compression_method() {
+        CompressionMethod::None => return Ok(()),
+        CompressionMethod::Deflate => {}
+        CompressionMethod::Zstd => {
+            let mut magic = [0; 4];
+            reader.seek(SeekFrom::Start(entry.offset()))?;
+            reader.read_exact(&mut magic)?;
+            if magic != ZSTD_MAGIC {
+                return Err(invalid(format!("zstd frame magic not found, found {:02X?}", magic)));
+            }
+        }
+    }
+
+    let expected = entry.uncompressed_size().min(TEST_DECODE_SIZE);
+    let decoded = PakEntryReader::new_streaming(&mut *reader, entry)
+        .and_then(|entry_reader| Ok(io::copy(&mut entry_reader.take(expected), &mut io::sink())?))
+        .map_err(|e| invalid(format!("failed to decode: {}", e)))?;
+    if decoded != expected {
+        return Err(invalid(format!(
+            "decoded {} bytes, expected at least {}",
+            decoded, expected
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::spec;
+
+    fn entry_of(offset: u64, compressed_size: u64, uncompressed_size: u64, compression_method: i64) -> PakEntry {
+        PakEntry::from(spec::EntryV2 {
+            hash_name_lower: 0,
+            hash_name_upper: 0,
+            offset,
+            compressed_size,
+            uncompressed_size,
+            compression_method,
+            checksum: 0,
+        })
+    }
+
+    #[test]
+    fn test_validate_payload() {
+        let content = b"payload payload payload";
+        let compressed = zstd::encode_all(&content[..], 0).unwrap();
+        let len = compressed.len() as u64;
+        let mut reader = Cursor::new(compressed);
+
+        assert!(validate_payload(&mut reader, &entry_of(0, len, content.len() as u64, 2)).is_ok());
+        // declared as zstd, but missing the frame start
+        assert!(validate_payload(&mut reader, &entry_of(1, len - 1, content.len() as u64, 2)).is_err());
+        // declared as deflate
+        assert!(validate_payload(&mut reader, &entry_of(0, len, content.len() as u64, 1)).is_err());
+        // out of the file
+        assert!(validate_payload(&mut reader, &entry_of(0, len + 1, len + 1, 0)).is_err());
+    }
+}
@@ -0,0 +1,687 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use crate::error::{PakError, Result};
+use crate::filename::FileName;
+use crate::metadata::PakMetadata;
+use crate::pak::{self, CompressionMethod, PakEntry, PakHeader};
+use crate::spec;
+
+/// Size of the key block following an encrypted entry table.
+pub(super) const KEY_SIZE: u64 = 128;
+
+/// Options of an entry written by [`PakWriter`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileOptions {
+    compression: CompressionMethod,
+}
+
+impl FileOptions {
+    pub fn with_compression(mut self, compression: CompressionMethod) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[inline]
+    pub fn compression(&self) -> CompressionMethod {
+        self.compression
+    }
+}
+
+/// Options of a pak written by [`PakWriter`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PakOptions {
+    encrypt_toc: bool,
+    deterministic: bool,
+    header_hash: u32,
+}
+
+impl PakOptions {
+    /// Options reproducing the header of an existing pak, its feature flags and hash.
+    pub fn from_header(header: &PakHeader) -> Self {
+        Self::default()
+            .with_encrypted_toc(header.feature() == 8)
+            .with_header_hash(header.hash())
+    }
+
+    /// Encrypt the entry table with a random key, as retail patch paks do (feature 8).
+    pub fn with_encrypted_toc(mut self, encrypt_toc: bool) -> Self {
+        self.encrypt_toc = encrypt_toc;
+        self
+    }
+
+    /// Write byte-identical paks for identical input.
+    ///
+    /// The entry table is sorted by hash, every reserved entry must be written
+    /// so no zeroed slots are left, and an encrypted table uses a key derived
+    /// from its contents instead of a random one. The data is laid out in the
+    /// order files are written, so callers should write them in a stable order.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Value of the hash field of the header, its meaning is not known and it is 0 by default.
+    pub fn with_header_hash(mut self, header_hash: u32) -> Self {
+        self.header_hash = header_hash;
+        self
+    }
+
+    #[inline]
+    pub fn encrypt_toc(&self) -> bool {
+        self.encrypt_toc
+    }
+
+    #[inline]
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    #[inline]
+    pub fn header_hash(&self) -> u32 {
+        self.header_hash
+    }
+
+    fn feature(&self) -> u16 {
+        if self.encrypt_toc {
+            8
+        } else {
+            0
+        }
+    }
+}
+
+/// Progress events emitted by [`PakWriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteEvent {
+    FileStart {
+        hash: u64,
+    },
+    FileDone {
+        hash: u64,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    },
+    /// The entry table is written, `total_size` is the size of the pak.
+    Finish {
+        total_files: u32,
+        total_size: u64,
+    },
+}
+
+type EventCallback = Box<dyn FnMut(&WriteEvent) + Send>;
+
+/// Write a version 4.0 pak file entry by entry.
+///
+/// Space for the entry table is reserved up front, the table itself
+/// is written by [`PakWriter::finish`].
+pub struct PakWriter<W: Write + Seek> {
+    inner: EntryWriter<W>,
+    options: PakOptions,
+    capacity: u32,
+    entries: Vec<PakEntry>,
+    current: Option<CurrentEntry>,
+    on_event: Option<EventCallback>,
+}
+
+struct CurrentEntry {
+    hash: u64,
+    offset: u64,
+    compression: CompressionMethod,
+    uncompressed_size: u64,
+}
+
+enum EntryWriter<W: Write> {
+    Closed,
+    Store(W),
+    #[cfg(feature = "deflate")]
+    Deflate(flate2::write::DeflateEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W> PakWriter<W>
+where
+    W: Write + Seek,
+{
+    /// Create a writer with room for `total_files` entries.
+    pub fn new(writer: W, total_files: u32) -> Result<Self> {
+        Self::with_options(writer, total_files, PakOptions::default())
+    }
+
+    pub fn with_options(mut writer: W, total_files: u32, options: PakOptions) -> Result<Self> {
+        let header = PakHeader::new(4, 0, options.feature(), total_files, options.header_hash)?;
+        let mut toc_size = spec::Header::SIZE as u64 + header.entry_size() as u64 * total_files as u64;
+        if options.encrypt_toc {
+            toc_size += KEY_SIZE;
+        }
+        writer.seek(SeekFrom::Start(0))?;
+        io::copy(&mut io::repeat(0).take(toc_size), &mut writer)?;
+
+        Ok(Self {
+            inner: EntryWriter::Store(writer),
+            options,
+            capacity: total_files,
+            entries: Vec::with_capacity(total_files as usize),
+            current: None,
+            on_event: None,
+        })
+    }
+
+    /// Set a callback receiving progress events.
+    pub fn on_event<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&WriteEvent) + Send + 'static,
+    {
+        self.on_event = Some(Box::new(callback));
+        self
+    }
+
+    /// Start a new entry, hashing its path. Finishes the previous entry.
+    pub fn start_file(&mut self, path: &str, options: FileOptions) -> Result<()> {
+        self.start_file_hash(FileName::new(path).hash_mixed(), options)
+    }
+
+    /// Start a new entry with a known hash. Finishes the previous entry.
+    pub fn start_file_hash(&mut self, hash: u64, options: FileOptions) -> Result<()> {
+        if !options.compression.is_supported() {
+            return Err(PakError::CodecDisabled(options.compression));
+        }
+        let (writer, offset) = self.begin_entry(hash)?;
+        self.inner = EntryWriter::new(writer, options.compression)?;
+        self.current = Some(CurrentEntry {
+            hash,
+            offset,
+            compression: options.compression,
+            uncompressed_size: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Copy the stored data of an entry of another pak as is, keeping its compression.
+    ///
+    /// The data is validated against the declared compression and sizes first.
+    pub fn copy_raw_entry<R>(&mut self, reader: &mut R, entry: &PakEntry) -> Result<()>
+    where
+        R: BufRead + Seek,
+    {
+        super::validate_payload(reader, entry)?;
+        let (mut writer, offset) = self.begin_entry(entry.hash())?;
+        reader.seek(SeekFrom::Start(entry.offset()))?;
+        let copied = io::copy(&mut (&mut *reader).take(entry.real_compressed_size()), &mut writer);
+        self.inner = EntryWriter::Store(writer);
+        let copied = copied?;
+        if copied != entry.real_compressed_size() {
+            return Err(PakError::EntrySizeMismatch {
+                hash: entry.hash(),
+                expected: entry.real_compressed_size(),
+                found: copied,
+            });
+        }
+
+        // the copy keeps the compression field, encryption type and checksum of the source
+        self.push_entry(entry.with_data(offset, copied, entry.compression_method()));
+        Ok(())
+    }
+
+    /// Write the metadata entry, it takes one of the reserved entries.
+    pub fn write_metadata(&mut self, metadata: &PakMetadata) -> Result<()> {
+        let data = metadata.to_json()?;
+        self.start_file_hash(PakMetadata::hash(), FileOptions::default())?;
+        self.write_all(&data)?;
+
+        Ok(())
+    }
+
+    /// Number of entries started so far.
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.current.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finish the last entry and write the header and entry table.
+    pub fn finish(mut self) -> Result<W> {
+        self.finish_file()?;
+        let mut writer = self.inner.take_plain()?;
+        let end = writer.stream_position()?;
+        if self.options.deterministic {
+            if self.entries.len() as u32 != self.capacity {
+                return Err(PakError::UnusedEntries {
+                    reserved: self.capacity,
+                    written: self.entries.len() as u32,
+                });
+            }
+            self.entries.sort_by_key(|entry| entry.hash());
+        }
+
+        // unused reserved entries stay zeroed, they are not referenced by the header
+        let header = PakHeader::new(
+            4,
+            0,
+            self.options.feature(),
+            self.entries.len() as u32,
+            self.options.header_hash,
+        )?;
+        writer.seek(SeekFrom::Start(0))?;
+        if self.options.encrypt_toc {
+            let key = if self.options.deterministic {
+                let mut table = vec![];
+                super::write_toc(&mut table, &header, &self.entries)?;
+                pak::derive_key(&table)
+            } else {
+                pak::generate_key()
+            };
+            super::write_encrypted_toc(&mut writer, &header, &self.entries, &key)?;
+        } else {
+            super::write_toc(&mut writer, &header, &self.entries)?;
+        }
+        writer.seek(SeekFrom::Start(end))?;
+
+        self.emit(WriteEvent::Finish {
+            total_files: header.total_files(),
+            total_size: end,
+        });
+        Ok(writer)
+    }
+
+    /// Finish the previous entry and take the plain writer at the offset of a new one.
+    fn begin_entry(&mut self, hash: u64) -> Result<(W, u64)> {
+        self.finish_file()?;
+        if self.entries.len() as u32 >= self.capacity {
+            return Err(PakError::TooManyEntries(self.capacity));
+        }
+
+        let mut writer = self.inner.take_plain()?;
+        let offset = writer.stream_position()?;
+        self.emit(WriteEvent::FileStart { hash });
+        Ok((writer, offset))
+    }
+
+    fn finish_file(&mut self) -> Result<()> {
+        let Some(current) = self.current.take() else {
+            return Ok(());
+        };
+        let mut writer = self.inner.take_plain()?;
+        let compressed_size = writer.stream_position()? - current.offset;
+        self.inner = EntryWriter::Store(writer);
+
+        let entry = PakEntry::new(
+            current.hash,
+            current.offset,
+            compressed_size,
+            current.uncompressed_size,
+            current.compression,
+        );
+        self.push_entry(entry);
+
+        Ok(())
+    }
+
+    fn push_entry(&mut self, entry: PakEntry) {
+        self.emit(WriteEvent::FileDone {
+            hash: entry.hash(),
+            compressed_size: entry.compressed_size(),
+            uncompressed_size: entry.uncompressed_size(),
+        });
+        self.entries.push(entry);
+    }
+
+    fn emit(&mut self, event: WriteEvent) {
+        if let Some(on_event) = &mut self.on_event {
+            on_event(&event);
+        }
+    }
+}
+
+impl<W> Write for PakWriter<W>
+where
+    W: Write + Seek,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(current) = self.current.as_mut() else {
+            return Err(io::Error::other("no entry started, call `start_file` first"));
+        };
+        let bytes_written = self.inner.write(buf)?;
+        current.uncompressed_size += bytes_written as u64;
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Encode the data of one entry at the current position of `writer`, returning its entry.
+pub(super) fn write_entry_data<W, R>(writer: &mut W, hash: u64, data: &mut R, options: FileOptions) -> Result<PakEntry>
+where
+    W: Write + Seek,
+    R: Read,
+{
+    let offset = writer.stream_position()?;
+    let mut encoder = EntryWriter::new(&mut *writer, options.compression)?;
+    let uncompressed_size = io::copy(data, &mut encoder)?;
+    let writer = encoder.take_plain()?;
+    let compressed_size = writer.stream_position()? - offset;
+
+    Ok(PakEntry::new(
+        hash,
+        offset,
+        compressed_size,
+        uncompressed_size,
+        options.compression,
+    ))
+}
+
+impl<W> Write for EntryWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            EntryWriter::Closed => Err(io::Error::other("pak writer is closed")),
+            EntryWriter::Store(writer) => writer.write(buf),
+            #[cfg(feature = "deflate")]
+            EntryWriter::Deflate(writer) => writer.write(buf),
+            #[cfg(feature = "zstd")]
+            EntryWriter::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            EntryWriter::Closed => Ok(()),
+            EntryWriter::Store(writer) => writer.flush(),
+            #[cfg(feature = "deflate")]
+            EntryWriter::Deflate(writer) => writer.flush(),
+            #[cfg(feature = "zstd")]
+            EntryWriter::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+impl<W> EntryWriter<W>
+where
+    W: Write,
+{
+    /// Start encoding with `compression`, which must be supported by the build.
+    fn new(writer: W, compression: CompressionMethod) -> Result<Self> {
+        Ok(match compression {
+            CompressionMethod::None => EntryWriter::Store(writer),
+            #[cfg(feature = "deflate")]
+            CompressionMethod::Deflate => EntryWriter::Deflate(flate2::write::DeflateEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => EntryWriter::Zstd(zstd::stream::write::Encoder::new(writer, 0)?),
+            #[allow(unreachable_patterns)]
+            _ => return Err(PakError::CodecDisabled(compression)),
+        })
+    }
+
+    /// Finish the encoder if any, leaving the writer closed until it is put back.
+    fn take_plain(&mut self) -> io::Result<W> {
+        match std::mem::replace(self, EntryWriter::Closed) {
+            EntryWriter::Closed => Err(io::Error::other("pak writer is closed")),
+            EntryWriter::Store(writer) => Ok(writer),
+            #[cfg(feature = "deflate")]
+            EntryWriter::Deflate(writer) => writer.finish(),
+            #[cfg(feature = "zstd")]
+            EntryWriter::Zstd(writer) => writer.finish(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "deflate", feature = "zstd"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::read::{
+        io::{archive::PakArchiveReader, entry::PakEntryReader},
+        read_archive,
+    };
+
+    #[test]
+    fn test_write_read_back() {
+        let files: [(&str, &[u8], CompressionMethod); 3] = [
+            ("natives/stm/a.txt", b"stored data", CompressionMethod::None),
+            (
+                "natives/stm/b.txt",
+                b"deflate data deflate data",
+                CompressionMethod::Deflate,
+            ),
+            (
+                "natives/stm/c.txt",
+                b"zstd data zstd data zstd data",
+                CompressionMethod::Zstd,
+            ),
+        ];
+
+        // reserve one spare entry
+        let mut writer = PakWriter::new(Cursor::new(vec![]), files.len() as u32 + 1).unwrap();
+        for (path, data, compression) in files {
+            writer
+                .start_file(path, FileOptions::default().with_compression(compression))
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        let archive = read_archive(&mut reader).unwrap();
+        assert_eq!(archive.entries().len(), files.len());
+        for ((path, data, compression), entry) in files.iter().zip(archive.entries()) {
+            assert_eq!(entry.hash(), FileName::new(path).hash_mixed());
+            assert_eq!(entry.compression_method(), *compression);
+            let mut entry_data = vec![];
+            PakEntryReader::new_streaming(&mut reader, entry)
+                .unwrap()
+                .read_to_end(&mut entry_data)
+                .unwrap();
+            assert_eq!(entry_data, *data);
+        }
+
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 0).unwrap();
+        assert!(writer.start_file("a", FileOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_deflate_stream() {
+        let data = b"deflate data deflate data deflate data".repeat(16);
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        let options = FileOptions::default().with_compression(CompressionMethod::Deflate);
+        writer.start_file("natives/stm/a.txt", options).unwrap();
+        writer.write_all(&data).unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        // entries hold a raw deflate stream, without zlib or gzip framing
+        let archive = read_archive(&mut reader).unwrap();
+        let entry = &archive.entries()[0];
+        assert!(entry.compressed_size() < data.len() as u64);
+        crate::write::validate_payload(&mut reader, entry).unwrap();
+        let mut stored = vec![];
+        PakArchiveReader::new(&mut reader, &archive)
+            .raw_entry_reader(entry)
+            .unwrap()
+            .read_to_end(&mut stored)
+            .unwrap();
+        let mut decoded = vec![];
+        flate2::read::DeflateDecoder::new(&stored[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let metadata = PakMetadata {
+            name: Some("Test Mod".to_string()),
+            author: Some("someone".to_string()),
+            ..Default::default()
+        };
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer.write_metadata(&metadata).unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        let archive = read_archive(&mut reader).unwrap();
+        let mut archive_reader = PakArchiveReader::new(reader, &archive);
+        assert_eq!(archive_reader.read_metadata().unwrap(), Some(metadata));
+    }
+
+    #[test]
+    fn test_copy_raw_entry() {
+        let data = b"zstd data zstd data zstd data";
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer
+            .start_file("a", FileOptions::default().with_compression(CompressionMethod::Zstd))
+            .unwrap();
+        writer.write_all(data).unwrap();
+        let mut src = writer.finish().unwrap();
+        src.rewind().unwrap();
+        let src_archive = read_archive(&mut src).unwrap();
+
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer.copy_raw_entry(&mut src, &src_archive.entries()[0]).unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        let archive = read_archive(&mut reader).unwrap();
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.compression_method(), CompressionMethod::Zstd);
+        let mut entry_data = vec![];
+        PakEntryReader::new_streaming(&mut reader, entry)
+            .unwrap()
+            .read_to_end(&mut entry_data)
+            .unwrap();
+        assert_eq!(entry_data, data);
+    }
+
+    #[test]
+    fn test_copy_encrypted_entry() {
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer.start_file("a", FileOptions::default()).unwrap();
+        writer.write_all(b"encrypted").unwrap();
+        let mut src = writer.finish().unwrap().into_inner();
+        // deflate with resource encryption 1, stored shorter than decoded
+        let field = 1_i64 | 1 << 16;
+        src[spec::Header::SIZE + 32..spec::Header::SIZE + 40].copy_from_slice(&field.to_le_bytes());
+        src[spec::Header::SIZE + 16..spec::Header::SIZE + 24].copy_from_slice(&6_u64.to_le_bytes());
+        let mut src = Cursor::new(src);
+        let src_archive = read_archive(&mut src).unwrap();
+
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1).unwrap();
+        writer.copy_raw_entry(&mut src, &src_archive.entries()[0]).unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        let archive = read_archive(&mut reader).unwrap();
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.encryption_type(), 1);
+        assert_eq!(entry.compressed_size(), 6);
+        assert_eq!(entry.uncompressed_size(), 9);
+        let table = &reader.get_ref()[spec::Header::SIZE..];
+        assert_eq!(spec::EntryV2::from_bytes(table).unwrap().compression_method, field);
+    }
+
+    #[test]
+    fn test_encrypted_toc() {
+        let options = PakOptions::default().with_encrypted_toc(true);
+        let mut writer = PakWriter::with_options(Cursor::new(vec![]), 2, options).unwrap();
+        writer.start_file("natives/stm/a.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"data").unwrap();
+        let mut reader = writer.finish().unwrap();
+        reader.rewind().unwrap();
+
+        let archive = read_archive(&mut reader).unwrap();
+        assert_eq!(archive.header().feature(), 8);
+        let entry = &archive.entries()[0];
+        assert_eq!(entry.hash(), FileName::new("natives/stm/a.txt").hash_mixed());
+        assert_eq!(entry.uncompressed_size(), 4);
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let options = PakOptions::default()
+            .with_encrypted_toc(true)
+            .with_header_hash(0x12345678);
+        let mut writer = PakWriter::with_options(Cursor::new(vec![]), 1, options).unwrap();
+        writer.start_file("a", FileOptions::default()).unwrap();
+        let written = writer.finish().unwrap().into_inner();
+
+        let header = read_archive(&mut Cursor::new(&written)).unwrap().header().clone();
+        assert_eq!(header.feature(), 8);
+        assert_eq!(header.hash(), 0x12345678);
+
+        let mut writer = PakWriter::with_options(Cursor::new(vec![]), 1, PakOptions::from_header(&header)).unwrap();
+        writer.start_file("a", FileOptions::default()).unwrap();
+        let rewritten = writer.finish().unwrap().into_inner();
+        assert_eq!(rewritten[..spec::Header::SIZE], written[..spec::Header::SIZE]);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let options = PakOptions::default().with_encrypted_toc(true).with_deterministic(true);
+        let write = |paths: &[&str]| {
+            let mut writer = PakWriter::with_options(Cursor::new(vec![]), paths.len() as u32, options).unwrap();
+            for path in paths {
+                writer
+                    .start_file(path, FileOptions::default().with_compression(CompressionMethod::Zstd))
+                    .unwrap();
+                writer.write_all(path.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        };
+        let paths = ["natives/stm/b.txt", "natives/stm/a.txt", "natives/stm/c.txt"];
+        let written = write(&paths);
+        assert_eq!(written, write(&paths));
+
+        let archive = read_archive(&mut Cursor::new(written)).unwrap();
+        let hashes: Vec<u64> = archive.entries().iter().map(|entry| entry.hash()).collect();
+        assert!(hashes.is_sorted());
+
+        let mut writer = PakWriter::with_options(Cursor::new(vec![]), 2, options).unwrap();
+        writer.start_file("a", FileOptions::default()).unwrap();
+        assert!(matches!(
+            writer.finish(),
+            Err(PakError::UnusedEntries {
+                reserved: 2,
+                written: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_events() {
+        let events = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = events.clone();
+        let mut writer = PakWriter::new(Cursor::new(vec![]), 1)
+            .unwrap()
+            .on_event(move |event| recorded.lock().unwrap().push(event.clone()));
+        writer.start_file_hash(1, FileOptions::default()).unwrap();
+        writer.write_all(b"data").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                WriteEvent::FileStart { hash: 1 },
+                WriteEvent::FileDone {
+                    hash: 1,
+                    compressed_size: 4,
+                    uncompressed_size: 4
+                },
+                WriteEvent::Finish {
+                    total_files: 1,
+                    total_size: (spec::Header::SIZE + spec::EntryV2::SIZE + 4) as u64
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Python bindings of ree-pak-core, built into the `ree_pak` module with maturin.
+//!
+//! ```python
+//! import ree_pak
+//!
+//! table = ree_pak.FileNameTable.load("assets/filelist/MHWs.list")
+//! pak = ree_pak.open("re_chunk_000.pak", table)
+//! data = pak.read("natives/stm/message/ui.msg.23")
+//! pak.extract_all("out", include=["natives/stm/sound/**"])
+//! ```
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyIOError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use ree_pak_core::error::PakError;
+use ree_pak_core::export::{DirExporter, ExportSink};
+use ree_pak_core::filename::{self, FileName};
+use ree_pak_core::filter::PathFilter;
+use ree_pak_core::pak::CompressionMethod;
+use ree_pak_core::read::multi::MultiPak;
+
+fn to_py_err(e: PakError) -> PyErr {
+    match e {
+        PakError::IO(e) => e.into(),
+        e => PyIOError::new_err(e.to_string()),
+    }
+}
+
+/// Maps path hashes to file names, loaded from a project list.
+#[pyclass(module = "ree_pak", frozen)]
+#[derive(Clone, Default)]
+struct FileNameTable {
+    inner: filename::FileNameTable,
+}
+
+#[pymethods]
+impl FileNameTable {
+    #[new]
+    #[pyo3(signature = (names=None))]
+    fn new(names: Option<Vec<String>>) -> Self {
+        let mut inner = filename::FileNameTable::default();
+        for name in names.iter().flatten() {
+            inner.push_str(name);
+        }
+        Self { inner }
+    }
+
+    /// Load a `.list` file, or a zstd compressed `.list.zst` file.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let inner = if path.extension().is_some_and(|ext| ext == "zst") {
+            let list = zstd::decode_all(std::fs::File::open(&path)?)?;
+            filename::FileNameTable::from_list_bytes_with_options(list, Default::default())
+        } else {
+            filename::FileNameTable::from_list_file(path)
+        }
+        .map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    /// Hash of a path as stored in pak entries.
+    #[staticmethod]
+    fn hash(path: &str) -> u64 {
+        FileName::new(path).hash_mixed()
+    }
+
+    fn get(&self, hash: u64) -> Option<&str> {
+        self.inner.get_file_name(hash)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __contains__(&self, hash: u64) -> bool {
+        self.inner.get_file_name(hash).is_some()
+    }
+}
+
+/// An effective entry of a [`Pak`].
+#[pyclass(module = "ree_pak", frozen, get_all)]
+struct Entry {
+    hash: u64,
+    /// Path from the file name table, `None` if the hash is unknown.
+    path: Option<String>,
+    /// Index of the pak the entry is read from, in patch order.
+    pak_index: usize,
+    offset: u64,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    compression: &'static str,
+}
+
+#[pymethods]
+impl Entry {
+    fn __repr__(&self) -> String {
+        match &self.path {
+            Some(path) => format!("Entry({:?})", path),
+            None => format!("Entry(0x{:016X})", self.hash),
+        }
+    }
+}
+
+/// One pak or a base pak with its patches, later paks override earlier ones.
+#[pyclass(module = "ree_pak", frozen)]
+struct Pak {
+    multi_pak: MultiPak,
+    file_name_table: FileNameTable,
+    /// Index of each effective entry by hash.
+    index: HashMap<u64, usize>,
+}
+
+impl Pak {
+    fn resolve_path(&self, hash: u64) -> String {
+        self.file_name_table
+            .inner
+            .get_file_name(hash)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("_Unknown/{:08X}", hash))
+    }
+}
+
+#[pymethods]
+impl Pak {
+    fn entries(&self) -> Vec<Entry> {
+        self.multi_pak
+            .entries()
+            .iter()
+            .map(|item| Entry {
+                hash: item.entry.hash(),
+                path: self.file_name_table.get(item.entry.hash()).map(|name| name.to_string()),
+                pak_index: item.pak_index,
+                offset: item.entry.offset(),
+                compressed_size: item.entry.compressed_size(),
+                uncompressed_size: item.entry.uncompressed_size(),
+                compression: compression_name(item.entry.compression_method()),
+            })
+            .collect()
+    }
+
+    /// Read the decoded bytes of an entry by path or hash.
+    fn read<'py>(&self, py: Python<'py>, key: PathOrHash) -> PyResult<Bound<'py, PyBytes>> {
+        let hash = key.hash();
+        let item = self
+            .index
+            .get(&hash)
+            .map(|&i| &self.multi_pak.entries()[i])
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))?;
+        let data = py.allow_threads(|| -> Result<Vec<u8>, PakError> {
+            let mut reader = self.multi_pak.open_entry(item)?;
+            // the size is not trusted, the buffer grows with the decoded data
+            let mut data = vec![];
+            reader.read_to_end(&mut data)?;
+            Ok(data)
+        });
+
+        Ok(PyBytes::new(py, &data.map_err(to_py_err)?))
+    }
+
+    /// Extract the entries selected by the globs under `dir`, returning the number of files written.
+    ///
+    /// Globs match resolved paths, entries with unknown hashes are named `_Unknown/<hash>`.
+    #[pyo3(signature = (dir, include=None, exclude=None))]
+    fn extract_all(
+        &self,
+        py: Python<'_>,
+        dir: PathBuf,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    ) -> PyResult<usize> {
+        let mut builder = PathFilter::builder();
+        for pattern in include.iter().flatten() {
+            builder = builder
+                .include_glob(pattern)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        }
+        for pattern in exclude.iter().flatten() {
+            builder = builder
+                .exclude_glob(pattern)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        }
+        let filter = builder.build().map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        py.allow_threads(|| -> Result<usize, PakError> {
+            let mut readers = (0..self.multi_pak.paks().len())
+                .map(|pak_index| self.multi_pak.open_reader(pak_index))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut exporter = DirExporter::new(dir);
+            for item in self.multi_pak.entries() {
+                let path = self.resolve_path(item.entry.hash());
+                if filter.is_match(&path) {
+                    exporter.add_entry(&mut readers[item.pak_index], &item.entry, &path)?;
+                }
+            }
+            Ok(exporter.len())
+        })
+        .map_err(to_py_err)
+    }
+
+    /// Number of paks, the base pak followed by its patches.
+    #[getter]
+    fn pak_count(&self) -> usize {
+        self.multi_pak.paks().len()
+    }
+
+    fn __len__(&self) -> usize {
+        self.multi_pak.entries().len()
+    }
+
+    fn __contains__(&self, key: PathOrHash) -> bool {
+        self.index.contains_key(&key.hash())
+    }
+}
+
+/// An entry key, a path or its hash.
+#[derive(FromPyObject)]
+enum PathOrHash {
+    Hash(u64),
+    Path(String),
+}
+
+impl PathOrHash {
+    fn hash(&self) -> u64 {
+        match self {
+            PathOrHash::Hash(hash) => *hash,
+            PathOrHash::Path(path) => FileName::new(path).hash_mixed(),
+        }
+    }
+}
+
+impl std::fmt::Display for PathOrHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathOrHash::Hash(hash) => write!(f, "0x{:016X}", hash),
+            PathOrHash::Path(path) => f.write_str(path),
+        }
+    }
+}
+
+fn compression_name(method: CompressionMethod) -> &'static str {
+    match method {
+        CompressionMethod::None => "none",
+        CompressionMethod::Deflate => "deflate",
+        CompressionMethod::Zstd => "zstd",
+    }
+}
+
+/// Open a pak file, or every pak in a directory layered in patch order.
+#[pyfunction]
+#[pyo3(signature = (path, table=None))]
+fn open(py: Python<'_>, path: PathBuf, table: Option<FileNameTable>) -> PyResult<Pak> {
+    let multi_pak = py
+        .allow_threads(|| {
+            if path.is_dir() {
+                MultiPak::open_dir(&path)
+            } else {
+                MultiPak::open(&[&path])
+            }
+        })
+        .map_err(to_py_err)?;
+    let index = multi_pak
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (item.entry.hash(), i))
+        .collect();
+
+    Ok(Pak {
+        multi_pak,
+        file_name_table: table.unwrap_or_default(),
+        index,
+    })
+}
+
+#[pymodule]
+fn ree_pak(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<FileNameTable>()?;
+    m.add_class::<Pak>()?;
+    m.add_class::<Entry>()?;
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_compressed_list() {
+        let dir = std::env::temp_dir().join(format!("ree-pak-py-list-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list = b"natives/stm/a.txt\nnatives/stm/b.txt\n";
+        std::fs::write(dir.join("T.list"), list).unwrap();
+        std::fs::write(dir.join("T.list.zst"), zstd::encode_all(&list[..], 0).unwrap()).unwrap();
+
+        let plain = FileNameTable::load(dir.join("T.list")).unwrap();
+        let compressed = FileNameTable::load(dir.join("T.list.zst")).unwrap();
+        let hash = FileNameTable::hash("natives/stm/a.txt");
+        assert_eq!(compressed.__len__(), 2);
+        assert_eq!(compressed.get(hash), Some("natives/stm/a.txt"));
+        assert_eq!(plain.get(hash), compressed.get(hash));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}